@@ -5,7 +5,10 @@ use std::fs::File;
 use std::io::Write;
 
 /// Generate Hilbert curve points for a given order (level)
+///
+/// `order` must be at most 15 so that the `4^order` cells can be counted in a `u32`.
 fn hilbert_curve(order: u32) -> Vec<(f64, f64)> {
+    assert!(order <= 15, "order must be at most 15, got {order}");
     let n = 2u32.pow(order);
     let mut points = Vec::new();
 
@@ -17,32 +20,16 @@ fn hilbert_curve(order: u32) -> Vec<(f64, f64)> {
     points
 }
 
-/// Convert Hilbert curve index to (x, y) coordinates
+/// Convert Hilbert curve index to (x, y) coordinates on a 2^order grid
+///
+/// Uses the same curve the tree sorts by: the cell at `order` is the leading
+/// `2 * order` bits of the full 16-bit-per-axis index.
+/// Order 0 is a single cell at (0, 0); orders above 16 are rejected.
 fn index_to_xy(index: u32, order: u32) -> (u32, u32) {
-    let mut x = 0u32;
-    let mut y = 0u32;
-    let mut s = 1u32;
-
-    let mut i = index;
-    while s < (1u32 << order) {
-        let rx = 1 & (i >> 1);
-        let ry = 1 & (i ^ rx);
-        
-        if ry == 0 {
-            if rx == 1 {
-                x = s - 1 - x;
-                y = s - 1 - y;
-            }
-            std::mem::swap(&mut x, &mut y);
-        }
-
-        x += rx * s;
-        y += ry * s;
-        i >>= 2;
-        s <<= 1;
-    }
-
-    (x, y)
+    assert!(order <= 16, "order must be at most 16, got {order}");
+    // A shift by the full 32 bits (order 0) overflows, and the only cell is index 0 anyway
+    let (x, y) = aabb::hilbert::index_to_xy(index.checked_shl(32 - 2 * order).unwrap_or(0));
+    (u32::from(x) >> (16 - order), u32::from(y) >> (16 - order))
 }
 
 fn main() {
@@ -0,0 +1,19 @@
+//! Find boxes intersecting a rotated rectangle (e.g. a vehicle footprint).
+use aabb::prelude::*;
+use std::f64::consts::FRAC_PI_4;
+
+fn main() {
+    let mut tree = AABB::with_capacity(3);
+    tree.add(4.0, 4.0, 5.0, 5.0);      // Box 0 (on the diagonal)
+    tree.add(8.0, 0.0, 9.0, 1.0);      // Box 1 (corner of the axis-aligned bounds)
+    tree.add(20.0, 20.0, 21.0, 21.0);  // Box 2 (far away)
+    tree.build();
+
+    let mut results = Vec::new();
+    // Rectangle 14 x 1 centered at (5, 5), rotated 45 degrees
+    tree.query_oriented_box(5.0, 5.0, 7.0, 0.5, FRAC_PI_4, &mut results);
+    println!("Intersecting rotated rectangle: {:?}", results);
+
+    // Box 1 lies inside the rectangle's axis-aligned bounds but not its rotated footprint
+    assert_eq!(results, vec![0], "Only box 0 should intersect the rotated rectangle");
+}
@@ -0,0 +1,24 @@
+//! Find boxes intersecting or inside a polygon (lasso selection).
+use aabb::prelude::*;
+
+fn main() {
+    let mut tree = AABB::with_capacity(3);
+    tree.add(1.0, 1.0, 2.0, 2.0);      // Box 0 (inside the triangle)
+    tree.add(7.0, 1.0, 9.0, 3.0);      // Box 1 (crosses the hypotenuse)
+    tree.add(8.0, 8.0, 9.0, 9.0);      // Box 2 (outside the triangle)
+    tree.build();
+
+    let triangle = [(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)];
+    let mut results = Vec::new();
+
+    tree.query_polygon(&triangle, PolygonMode::Intersecting, &mut results);
+    println!("Intersecting polygon: {:?}", results);
+    assert_eq!(results.len(), 2, "Expected 2 boxes intersecting the triangle");
+    assert!(results.contains(&0), "Box 0 should intersect the triangle");
+    assert!(results.contains(&1), "Box 1 should intersect the triangle");
+
+    tree.query_polygon(&triangle, PolygonMode::Contained, &mut results);
+    println!("Inside polygon: {:?}", results);
+    // Box 1 sticks out over the hypotenuse, so only box 0 is fully inside
+    assert_eq!(results, vec![0], "Only box 0 should be inside the triangle");
+}
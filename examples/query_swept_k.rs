@@ -0,0 +1,23 @@
+//! Find the first boxes hit by a moving rectangle, with time of impact.
+use aabb::prelude::*;
+
+fn main() {
+    let mut tree = AABB::with_capacity(3);
+    tree.add(3.0, -10.0, 20.0, 10.0);  // Box 0 (large wall, near face at x = 3)
+    tree.add(5.0, 0.0, 6.0, 1.0);      // Box 1 (small, behind the wall's near face)
+    tree.add(0.0, 5.0, 1.0, 6.0);      // Box 2 (not in the path)
+    tree.build();
+
+    let mut hits = Vec::new();
+    // Move the unit square at the origin to the right by 10
+    tree.query_swept_k(0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 2, 10.0, &mut hits);
+    for (id, toi, normal) in &hits {
+        println!("Box {} hit at t = {} with normal {:?}", id, toi, normal);
+    }
+
+    // The wall is hit first even though its center is farther along the path
+    assert_eq!(hits.len(), 2, "Expected 2 hits");
+    assert_eq!(hits[0].0, 0, "Wall should be hit first");
+    assert_eq!(hits[0].2, (-1.0, 0.0), "Wall is hit on its left face");
+    assert_eq!(hits[1].0, 1, "Small box should be hit second");
+}
@@ -991,5 +991,1978 @@ mod tests {
             panic!("Isolated box query should return empty, got {:?}", results);
         }
     }
-}
 
+    // ============================================================================
+    // POLYGON QUERY TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_query_polygon_triangle() {
+        use crate::PolygonMode;
+        let mut tree = HilbertRTree::new();
+        tree.add(1.0, 1.0, 2.0, 2.0);      // Box 0 - inside
+        tree.add(7.0, 1.0, 9.0, 3.0);      // Box 1 - crosses hypotenuse
+        tree.add(8.0, 8.0, 9.0, 9.0);      // Box 2 - outside (in bbox of triangle)
+        tree.add(-5.0, -5.0, -4.0, -4.0);  // Box 3 - far outside
+        tree.add(-1.0, -1.0, 11.0, 11.0);  // Box 4 - contains the whole triangle
+        tree.build();
+
+        let triangle = [(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)];
+        let mut results = Vec::new();
+        tree.query_polygon(&triangle, PolygonMode::Intersecting, &mut results);
+        results.sort();
+        assert_eq!(results, vec![0, 1, 4]);
+
+        tree.query_polygon(&triangle, PolygonMode::Contained, &mut results);
+        assert_eq!(results, vec![0]);
+    }
+
+    #[test]
+    fn test_query_polygon_concave() {
+        use crate::PolygonMode;
+        // U-shaped polygon: the notch (4..6, 2..10) is outside
+        let u_shape = [
+            (0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (6.0, 10.0),
+            (6.0, 2.0), (4.0, 2.0), (4.0, 10.0), (0.0, 10.0),
+        ];
+        let mut tree = HilbertRTree::new();
+        tree.add(4.5, 5.0, 5.5, 6.0);      // Box 0 - inside the notch
+        tree.add(1.0, 5.0, 2.0, 6.0);      // Box 1 - inside left arm
+        tree.add(3.0, 5.0, 7.0, 6.0);      // Box 2 - spans the notch
+        tree.build();
+
+        let mut results = Vec::new();
+        tree.query_polygon(&u_shape, PolygonMode::Intersecting, &mut results);
+        results.sort();
+        assert_eq!(results, vec![1, 2]);
+
+        tree.query_polygon(&u_shape, PolygonMode::Contained, &mut results);
+        assert_eq!(results, vec![1]);
+    }
+
+    #[test]
+    fn test_query_polygon_degenerate() {
+        use crate::PolygonMode;
+        let mut tree = HilbertRTree::new();
+        tree.add(0.0, 0.0, 1.0, 1.0);
+        tree.build();
+
+        let mut results = vec![42];
+        tree.query_polygon(&[(0.0, 0.0), (1.0, 1.0)], PolygonMode::Intersecting, &mut results);
+        assert!(results.is_empty(), "Polygon with fewer than 3 vertices should match nothing");
+    }
+
+    #[test]
+    fn test_query_polygon_rectangle_matches_rect_queries() {
+        use crate::PolygonMode;
+        let mut tree = HilbertRTree::new();
+        for i in 0..40 {
+            for j in 0..40 {
+                let (x, y) = (i as f64 * 2.5, j as f64 * 2.5);
+                tree.add(x, y, x + 1.5, y + 1.5);
+            }
+        }
+        tree.build();
+
+        // An axis-aligned rectangle polygon must agree with the rectangle queries
+        let rect = [(11.0, 21.0), (63.0, 21.0), (63.0, 48.0), (11.0, 48.0)];
+        let mut expected = Vec::new();
+        let mut results = Vec::new();
+
+        tree.query_intersecting(11.0, 21.0, 63.0, 48.0, &mut expected);
+        tree.query_polygon(&rect, PolygonMode::Intersecting, &mut results);
+        expected.sort();
+        results.sort();
+        assert_eq!(results, expected);
+
+        tree.query_contained_within(11.0, 21.0, 63.0, 48.0, &mut expected);
+        tree.query_polygon(&rect, PolygonMode::Contained, &mut results);
+        expected.sort();
+        results.sort();
+        assert_eq!(results, expected);
+    }
+
+    // ============================================================================
+    // ORIENTED BOX QUERY TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_query_oriented_box_unrotated_matches_intersecting() {
+        let mut tree = HilbertRTree::new();
+        for i in 0..30 {
+            for j in 0..30 {
+                let (x, y) = (i as f64 * 3.0, j as f64 * 3.0);
+                tree.add(x, y, x + 2.0, y + 2.0);
+            }
+        }
+        tree.build();
+
+        let mut expected = Vec::new();
+        let mut results = Vec::new();
+        tree.query_intersecting(10.5, 20.5, 40.5, 30.5, &mut expected);
+        tree.query_oriented_box(25.5, 25.5, 15.0, 5.0, 0.0, &mut results);
+        expected.sort();
+        results.sort();
+        assert_eq!(results, expected);
+
+        // Rotating by 90 degrees swaps the extents
+        tree.query_intersecting(20.5, 10.5, 30.5, 40.5, &mut expected);
+        tree.query_oriented_box(25.5, 25.5, 15.0, 5.0, std::f64::consts::FRAC_PI_2, &mut results);
+        expected.sort();
+        results.sort();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_query_oriented_box_rotated() {
+        let mut tree = HilbertRTree::new();
+        tree.add(4.0, 4.0, 5.0, 5.0);      // Box 0 - on the diagonal
+        tree.add(8.0, 0.0, 9.0, 1.0);      // Box 1 - in AABB corner, outside rotated box
+        tree.add(0.0, 8.0, 1.0, 9.0);      // Box 2 - other AABB corner
+        tree.add(9.0, 9.0, 10.0, 10.0);    // Box 3 - near the far end of the diagonal
+        tree.build();
+
+        let mut results = Vec::new();
+        tree.query_oriented_box(5.0, 5.0, 7.0, 0.5, std::f64::consts::FRAC_PI_4, &mut results);
+        results.sort();
+        assert_eq!(results, vec![0, 3]);
+    }
+
+    #[test]
+    fn test_query_oriented_box_touching_corner() {
+        let mut tree = HilbertRTree::new();
+        tree.add(1.0, -1.0, 2.0, 1.0);     // Box 0 - touches the diamond's right vertex
+        tree.add(1.1, -1.0, 2.0, 1.0);     // Box 1 - just misses it
+        tree.build();
+
+        // Unit square rotated 45 degrees is a diamond with vertices at distance sqrt(2)/2 * 2 = 1
+        let half = std::f64::consts::SQRT_2 / 2.0;
+        let mut results = Vec::new();
+        tree.query_oriented_box(0.0, 0.0, half, half, std::f64::consts::FRAC_PI_4, &mut results);
+        assert_eq!(results, vec![0]);
+    }
+
+    // ============================================================================
+    // SWEPT QUERY TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_query_swept_k_orders_by_first_contact() {
+        let mut tree = HilbertRTree::new();
+        tree.add(3.0, -10.0, 20.0, 10.0);  // Box 0 - large wall, near face at x = 3
+        tree.add(5.0, 0.0, 6.0, 1.0);      // Box 1 - small, center closer than box 0's
+        tree.add(0.0, 5.0, 1.0, 6.0);      // Box 2 - not in the path
+        tree.build();
+
+        let mut hits = Vec::new();
+        tree.query_swept_k(0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 10, 10.0, &mut hits);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0], (0, 0.2, (-1.0, 0.0)));
+        assert_eq!(hits[1], (1, 0.4, (-1.0, 0.0)));
+
+        // The center projection used by query_in_direction_k orders them the other way
+        let mut results = Vec::new();
+        tree.query_in_direction_k(0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 10, 10.0, &mut results);
+        assert_eq!(results[0], 1);
+    }
+
+    #[test]
+    fn test_query_swept_k_normals_and_overlap() {
+        let mut tree = HilbertRTree::new();
+        tree.add(0.5, 0.5, 1.5, 1.5);      // Box 0 - overlaps the start position
+        tree.add(-2.0, 4.0, 3.0, 5.0);     // Box 1 - ceiling hit from below
+        tree.add(-5.0, -5.0, -4.0, -4.0);  // Box 2 - behind the movement
+        tree.build();
+
+        let mut hits = Vec::new();
+        tree.query_swept_k(0.0, 0.0, 1.0, 1.0, 0.0, 2.0, 10, 6.0, &mut hits);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0], (0, 0.0, (0.0, 0.0)));
+        assert_eq!(hits[1].0, 1);
+        assert!((hits[1].1 - 0.5).abs() < 1e-12, "Ceiling hit at half the movement");
+        assert_eq!(hits[1].2, (0.0, -1.0));
+    }
+
+    #[test]
+    fn test_query_swept_k_matches_brute_force() {
+        let mut tree = HilbertRTree::new();
+        let mut boxes = Vec::new();
+        for i in 0..50 {
+            for j in 0..50 {
+                let (x, y) = (i as f64 * 4.0 + (j % 3) as f64, j as f64 * 4.0);
+                let size = 1.0 + ((i * 7 + j) % 5) as f64;
+                tree.add(x, y, x + size, y + size * 0.5);
+                boxes.push((x, y, x + size, y + size * 0.5));
+            }
+        }
+        tree.build();
+
+        let (min_x, min_y, max_x, max_y) = (10.3, 20.7, 12.1, 21.9);
+        let (dir_x, dir_y, distance) = (3.0_f64, 2.0_f64, 120.0);
+        let len = (dir_x * dir_x + dir_y * dir_y).sqrt();
+        let (mx, my) = (dir_x / len * distance, dir_y / len * distance);
+
+        // Brute force: slab test against every box
+        let mut expected = Vec::new();
+        for (id, &(bx0, by0, bx1, by1)) in boxes.iter().enumerate() {
+            let tx = [(bx0 - max_x) / mx, (bx1 - min_x) / mx];
+            let ty = [(by0 - max_y) / my, (by1 - min_y) / my];
+            let entry = tx[0].max(ty[0]);
+            let exit = tx[1].min(ty[1]);
+            if entry <= exit && exit >= 0.0 && entry <= 1.0 {
+                expected.push((entry.max(0.0), id));
+            }
+        }
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let k = 15;
+        let mut hits = Vec::new();
+        tree.query_swept_k(min_x, min_y, max_x, max_y, dir_x, dir_y, k, distance, &mut hits);
+        assert_eq!(hits.len(), k);
+        for (hit, exp) in hits.iter().zip(expected.iter()) {
+            assert!((hit.1 - exp.0).abs() < 1e-12, "time of impact mismatch: {:?} vs {:?}", hit, exp);
+        }
+        assert!(hits.windows(2).all(|w| w[0].1 <= w[1].1), "Hits must be sorted by time of impact");
+
+        // Unlimited k returns every hit
+        tree.query_swept_k(min_x, min_y, max_x, max_y, dir_x, dir_y, usize::MAX, distance, &mut hits);
+        assert_eq!(hits.len(), expected.len());
+    }
+
+    // ============================================================================
+    // BATCH QUERY TESTS
+    // ============================================================================
+
+    fn build_grid_tree(n: usize) -> HilbertRTree {
+        let mut tree = HilbertRTree::with_capacity(n * n);
+        for i in 0..n {
+            for j in 0..n {
+                let (x, y) = (i as f64 * 2.0, j as f64 * 2.0);
+                tree.add(x, y, x + 1.0 + (j % 3) as f64, y + 1.0 + (i % 2) as f64);
+            }
+        }
+        tree.build();
+        tree
+    }
+
+    #[test]
+    fn test_query_intersecting_batch_matches_single_queries() {
+        let tree = build_grid_tree(40);
+        let queries: Vec<(f64, f64, f64, f64)> = (0..200)
+            .map(|i| {
+                let x = (i * 37 % 80) as f64;
+                let y = (i * 53 % 80) as f64;
+                (x, y, x + 3.5, y + 2.5)
+            })
+            .collect();
+
+        let batch = tree.query_intersecting_batch(&queries);
+        let mut offsets = Vec::new();
+        let mut flat = Vec::new();
+        tree.query_intersecting_batch_flat(&queries, &mut offsets, &mut flat);
+        assert_eq!(batch.len(), queries.len());
+        assert_eq!(offsets.len(), queries.len() + 1);
+        assert_eq!(*offsets.last().unwrap(), flat.len());
+
+        let mut expected = Vec::new();
+        for (i, q) in queries.iter().enumerate() {
+            tree.query_intersecting(q.0, q.1, q.2, q.3, &mut expected);
+            assert_eq!(batch[i], expected, "Batch result mismatch for query {}", i);
+            assert_eq!(&flat[offsets[i]..offsets[i + 1]], expected.as_slice(), "Flat result mismatch for query {}", i);
+        }
+    }
+
+    #[test]
+    fn test_query_nearest_k_batch_matches_single_queries() {
+        let tree = build_grid_tree(30);
+        let points: Vec<(f64, f64)> = (0..150)
+            .map(|i| ((i * 29 % 70) as f64 - 5.0, (i * 41 % 70) as f64 - 5.0))
+            .collect();
+
+        let batch = tree.query_nearest_k_batch(&points, 5);
+        let mut expected = Vec::new();
+        for (i, &(x, y)) in points.iter().enumerate() {
+            tree.query_nearest_k(x, y, 5, &mut expected);
+            assert_eq!(batch[i].len(), 5);
+            // Ties may be reported in a different order; compare distances instead of ids
+            let dist = |id: usize| {
+                let (min_x, min_y, max_x, max_y) = tree.get(id).unwrap();
+                let dx = (min_x - x).max(0.0).max(x - max_x);
+                let dy = (min_y - y).max(0.0).max(y - max_y);
+                dx * dx + dy * dy
+            };
+            for (a, b) in batch[i].iter().zip(expected.iter()) {
+                assert_eq!(dist(*a), dist(*b), "Batch nearest mismatch for point {}", i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch_queries_empty() {
+        let tree = build_grid_tree(4);
+        assert!(tree.query_intersecting_batch(&[]).is_empty());
+        assert!(tree.query_nearest_k_batch(&[], 3).is_empty());
+
+        let mut offsets = vec![7];
+        let mut flat = vec![7];
+        tree.query_intersecting_batch_flat(&[], &mut offsets, &mut flat);
+        assert_eq!(offsets, vec![0]);
+        assert!(flat.is_empty());
+
+        let empty = HilbertRTree::new();
+        assert_eq!(empty.query_intersecting_batch(&[(0.0, 0.0, 1.0, 1.0)]), vec![Vec::<usize>::new()]);
+    }
+
+    // ============================================================================
+    // SPATIAL JOIN TESTS
+    // ============================================================================
+
+    fn random_boxes(seed: u64, n: usize, extent: f64, max_size: f64) -> Vec<(f64, f64, f64, f64)> {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        (0..n)
+            .map(|_| {
+                let x = rng.random_range(0.0..extent);
+                let y = rng.random_range(0.0..extent);
+                (x, y, x + rng.random_range(0.0..max_size), y + rng.random_range(0.0..max_size))
+            })
+            .collect()
+    }
+
+    fn tree_from(boxes: &[(f64, f64, f64, f64)]) -> HilbertRTree {
+        let mut tree = HilbertRTree::with_capacity(boxes.len());
+        for b in boxes {
+            tree.add(b.0, b.1, b.2, b.3);
+        }
+        tree.build();
+        tree
+    }
+
+    fn overlap(a: &(f64, f64, f64, f64), b: &(f64, f64, f64, f64)) -> bool {
+        a.2 >= b.0 && a.3 >= b.1 && a.0 <= b.2 && a.1 <= b.3
+    }
+
+    #[test]
+    fn test_join_intersecting_matches_brute_force() {
+        let boxes_a = random_boxes(1, 700, 100.0, 3.0);
+        let boxes_b = random_boxes(2, 400, 100.0, 5.0);
+        let tree_a = tree_from(&boxes_a);
+        let tree_b = tree_from(&boxes_b);
+
+        let mut pairs = Vec::new();
+        HilbertRTree::join_intersecting(&tree_a, &tree_b, |a, b| pairs.push((a, b)));
+        pairs.sort();
+
+        let mut expected = Vec::new();
+        for (i, a) in boxes_a.iter().enumerate() {
+            for (j, b) in boxes_b.iter().enumerate() {
+                if overlap(a, b) {
+                    expected.push((i, j));
+                }
+            }
+        }
+        assert!(!expected.is_empty());
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn test_join_intersecting_small_and_empty_trees() {
+        let single = tree_from(&[(0.0, 0.0, 1.0, 1.0)]);
+        let small = tree_from(&[(1.0, 1.0, 2.0, 2.0), (0.5, 0.5, 0.6, 0.6), (3.0, 3.0, 4.0, 4.0)]);
+
+        let mut pairs = Vec::new();
+        HilbertRTree::join_intersecting(&single, &small, |a, b| pairs.push((a, b)));
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 0), (0, 1)]);
+
+        // Argument order determines the pair order
+        pairs.clear();
+        HilbertRTree::join_intersecting(&small, &single, |a, b| pairs.push((a, b)));
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 0), (1, 0)]);
+
+        let empty = HilbertRTree::new();
+        let mut count = 0;
+        HilbertRTree::join_intersecting(&empty, &small, |_, _| count += 1);
+        HilbertRTree::join_intersecting(&small, &empty, |_, _| count += 1);
+        assert_eq!(count, 0);
+    }
+
+    // ============================================================================
+    // SELF-JOIN TESTS
+    // ============================================================================
+
+    fn brute_force_pairs(boxes: &[(f64, f64, f64, f64)]) -> Vec<(usize, usize)> {
+        let mut expected = Vec::new();
+        for i in 0..boxes.len() {
+            for j in i + 1..boxes.len() {
+                if overlap(&boxes[i], &boxes[j]) {
+                    expected.push((i, j));
+                }
+            }
+        }
+        expected
+    }
+
+    #[test]
+    fn test_all_intersecting_pairs_matches_brute_force() {
+        for (seed, n) in [(3, 5), (4, 17), (5, 300), (6, 1500)] {
+            let boxes = random_boxes(seed, n, 100.0, 4.0);
+            let tree = tree_from(&boxes);
+
+            let mut pairs = Vec::new();
+            tree.all_intersecting_pairs(&mut pairs);
+            assert!(pairs.iter().all(|&(i, j)| i < j), "Pairs must be ordered (i < j)");
+            pairs.sort();
+            assert_eq!(pairs, brute_force_pairs(&boxes), "Mismatch for {} items", n);
+        }
+    }
+
+    #[test]
+    fn test_all_intersecting_pairs_identical_boxes() {
+        let boxes = vec![(1.0, 1.0, 2.0, 2.0); 40];
+        let tree = tree_from(&boxes);
+
+        let mut pairs = vec![(9, 9)];
+        tree.all_intersecting_pairs(&mut pairs);
+        assert_eq!(pairs.len(), 40 * 39 / 2);
+
+        let empty = HilbertRTree::new();
+        empty.all_intersecting_pairs(&mut pairs);
+        assert!(pairs.is_empty());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_all_intersecting_pairs_parallel_matches_sequential() {
+        let boxes = random_boxes(7, 5000, 200.0, 4.0);
+        let tree = tree_from(&boxes);
+
+        let mut sequential = Vec::new();
+        let mut parallel = Vec::new();
+        tree.all_intersecting_pairs(&mut sequential);
+        tree.all_intersecting_pairs_parallel(&mut parallel);
+        sequential.sort();
+        parallel.sort();
+        assert_eq!(parallel, sequential);
+    }
+
+    // ============================================================================
+    // DISTANCE JOIN TESTS
+    // ============================================================================
+
+    fn gap(a: &(f64, f64, f64, f64), b: &(f64, f64, f64, f64)) -> f64 {
+        let dx = (b.0 - a.2).max(a.0 - b.2).max(0.0);
+        let dy = (b.1 - a.3).max(a.1 - b.3).max(0.0);
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    #[test]
+    fn test_join_within_distance_matches_brute_force() {
+        let boxes_a = random_boxes(8, 500, 100.0, 2.0);
+        let boxes_b = random_boxes(9, 600, 100.0, 1.0);
+        let tree_a = tree_from(&boxes_a);
+        let tree_b = tree_from(&boxes_b);
+        let eps = 1.5;
+
+        let mut pairs = Vec::new();
+        HilbertRTree::join_within_distance(&tree_a, &tree_b, eps, |a, b, d| pairs.push((a, b, d)));
+        pairs.sort_by(|x, y| (x.0, x.1).cmp(&(y.0, y.1)));
+
+        let mut expected = Vec::new();
+        for (i, a) in boxes_a.iter().enumerate() {
+            for (j, b) in boxes_b.iter().enumerate() {
+                let d = gap(a, b);
+                if d <= eps {
+                    expected.push((i, j, d));
+                }
+            }
+        }
+        assert_eq!(pairs.len(), expected.len());
+        for (p, e) in pairs.iter().zip(expected.iter()) {
+            assert_eq!((p.0, p.1), (e.0, e.1));
+            assert!((p.2 - e.2).abs() < 1e-12, "Distance mismatch for pair {:?}", p);
+        }
+
+        // eps = 0 degenerates to the intersection join
+        let mut within_zero = Vec::new();
+        HilbertRTree::join_within_distance(&tree_a, &tree_b, 0.0, |a, b, _| within_zero.push((a, b)));
+        let mut intersecting = Vec::new();
+        HilbertRTree::join_intersecting(&tree_a, &tree_b, |a, b| intersecting.push((a, b)));
+        within_zero.sort();
+        intersecting.sort();
+        assert_eq!(within_zero, intersecting);
+    }
+
+    #[test]
+    fn test_pairs_within_distance_matches_brute_force() {
+        let boxes = random_boxes(10, 800, 100.0, 1.0);
+        let tree = tree_from(&boxes);
+        let eps = 2.0;
+
+        let mut pairs = Vec::new();
+        tree.pairs_within_distance(eps, &mut pairs);
+        assert!(pairs.iter().all(|p| p.0 < p.1), "Pairs must be ordered (i < j)");
+        pairs.sort_by(|x, y| (x.0, x.1).cmp(&(y.0, y.1)));
+
+        let mut expected = Vec::new();
+        for i in 0..boxes.len() {
+            for j in i + 1..boxes.len() {
+                let d = gap(&boxes[i], &boxes[j]);
+                if d <= eps {
+                    expected.push((i, j, d));
+                }
+            }
+        }
+        assert_eq!(pairs.len(), expected.len());
+        for (p, e) in pairs.iter().zip(expected.iter()) {
+            assert_eq!((p.0, p.1), (e.0, e.1));
+            assert!((p.2 - e.2).abs() < 1e-12, "Distance mismatch for pair {:?}", p);
+        }
+
+        tree.pairs_within_distance(-1.0, &mut pairs);
+        assert!(pairs.is_empty(), "Negative eps should match nothing");
+    }
+
+    // ============================================================================
+    // KNN GRAPH TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_knn_graph_matches_brute_force() {
+        for (seed, n, k) in [(11, 1500, 6), (12, 40, 3), (13, 500, 1)] {
+            let boxes = random_boxes(seed, n, 100.0, 0.5);
+            let tree = tree_from(&boxes);
+
+            let mut offsets = Vec::new();
+            let mut neighbors = Vec::new();
+            tree.knn_graph(k, &mut offsets, &mut neighbors);
+            assert_eq!(offsets.len(), n + 1);
+            assert_eq!(neighbors.len(), n * k);
+
+            for i in 0..n {
+                let list = &neighbors[offsets[i]..offsets[i + 1]];
+                assert_eq!(list.len(), k);
+                assert!(!list.contains(&i), "Item {} must not be its own neighbor", i);
+
+                let mut dists: Vec<f64> = (0..n).filter(|&j| j != i).map(|j| gap(&boxes[i], &boxes[j])).collect();
+                dists.sort_by(f64::total_cmp);
+                let got: Vec<f64> = list.iter().map(|&j| gap(&boxes[i], &boxes[j])).collect();
+                for (g, e) in got.iter().zip(dists.iter()) {
+                    assert!((g - e).abs() < 1e-12, "Item {}: neighbor distances {:?} vs {:?}", i, got, &dists[..k]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_knn_graph_degree_limits() {
+        let tree = tree_from(&[(0.0, 0.0, 0.0, 0.0), (1.0, 0.0, 1.0, 0.0), (3.0, 0.0, 3.0, 0.0)]);
+        let mut offsets = Vec::new();
+        let mut neighbors = Vec::new();
+
+        // k larger than the number of other items
+        tree.knn_graph(10, &mut offsets, &mut neighbors);
+        assert_eq!(offsets, vec![0, 2, 4, 6]);
+        assert_eq!(neighbors, vec![1, 2, 0, 2, 1, 0]);
+
+        tree.knn_graph(0, &mut offsets, &mut neighbors);
+        assert_eq!(offsets, vec![0, 0, 0, 0]);
+        assert!(neighbors.is_empty());
+
+        let single = tree_from(&[(0.0, 0.0, 1.0, 1.0)]);
+        single.knn_graph(3, &mut offsets, &mut neighbors);
+        assert_eq!(offsets, vec![0, 0]);
+
+        HilbertRTree::new().knn_graph(3, &mut offsets, &mut neighbors);
+        assert!(offsets.is_empty() && neighbors.is_empty());
+    }
+
+    // ============================================================================
+    // CLOSEST PAIRS TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_closest_pairs_matches_brute_force() {
+        // Trees of different sizes (and heights), disjoint so all distances are positive
+        let boxes_a = random_boxes(14, 900, 100.0, 1.0);
+        let boxes_b: Vec<_> = random_boxes(15, 60, 100.0, 1.0)
+            .into_iter()
+            .map(|b| (b.0 + 150.0, b.1, b.2 + 150.0, b.3))
+            .collect();
+        let tree_a = tree_from(&boxes_a);
+        let tree_b = tree_from(&boxes_b);
+
+        let mut expected = Vec::new();
+        for (i, a) in boxes_a.iter().enumerate() {
+            for (j, b) in boxes_b.iter().enumerate() {
+                expected.push((gap(a, b), i, j));
+            }
+        }
+        expected.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+        for k in [1, 7, 50] {
+            let mut results = Vec::new();
+            HilbertRTree::closest_pairs(&tree_a, &tree_b, k, &mut results);
+            assert_eq!(results.len(), k);
+            for (r, e) in results.iter().zip(expected.iter()) {
+                assert!((r.2 - e.0).abs() < 1e-12, "k={}: distance {:?} vs {:?}", k, r, e);
+                assert!((gap(&boxes_a[r.0], &boxes_b[r.1]) - r.2).abs() < 1e-12, "Reported distance must match the pair");
+            }
+        }
+    }
+
+    #[test]
+    fn test_closest_pairs_overlapping_and_limits() {
+        let tree_a = tree_from(&[(0.0, 0.0, 2.0, 2.0), (5.0, 5.0, 6.0, 6.0)]);
+        let tree_b = tree_from(&[(1.0, 1.0, 3.0, 3.0)]);
+
+        let mut results = Vec::new();
+        HilbertRTree::closest_pairs(&tree_a, &tree_b, 10, &mut results);
+        assert_eq!(results.len(), 2, "Only 2 pairs exist");
+        assert_eq!(results[0], (0, 0, 0.0), "Overlapping pair has distance 0");
+        assert_eq!(results[1].0, 1);
+        assert!((results[1].2 - 8.0_f64.sqrt()).abs() < 1e-12);
+
+        HilbertRTree::closest_pairs(&tree_a, &tree_b, 0, &mut results);
+        assert!(results.is_empty());
+        HilbertRTree::closest_pairs(&tree_a, &HilbertRTree::new(), 3, &mut results);
+        assert!(results.is_empty());
+    }
+
+    // ============================================================================
+    // COUNT / EXISTENCE TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_count_intersecting_matches_query() {
+        let tree = tree_from(&random_boxes(35, 2000, 1000.0, 20.0));
+        let mut results = Vec::new();
+        for &(min_x, min_y, max_x, max_y) in &[
+            (0.0, 0.0, 1000.0, 1000.0),
+            (100.0, 200.0, 400.0, 350.0),
+            (500.0, 500.0, 500.0, 500.0),
+            (-50.0, -50.0, -10.0, -10.0),
+        ] {
+            tree.query_intersecting(min_x, min_y, max_x, max_y, &mut results);
+            assert_eq!(tree.count_intersecting(min_x, min_y, max_x, max_y), results.len());
+            assert_eq!(tree.any_intersecting(min_x, min_y, max_x, max_y), !results.is_empty());
+        }
+        assert_eq!(tree.count_intersecting(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::INFINITY, f64::INFINITY), 2000);
+    }
+
+    #[test]
+    fn test_count_in_circle_matches_query() {
+        let tree = tree_from(&random_boxes(36, 2000, 1000.0, 20.0));
+        let mut results = Vec::new();
+        for &(x, y, r) in &[(500.0, 500.0, 150.0), (0.0, 0.0, 60.0), (250.0, 750.0, 0.0), (500.0, 500.0, 2000.0)] {
+            tree.query_circle(x, y, r, &mut results);
+            assert_eq!(tree.count_in_circle(x, y, r), results.len());
+            assert_eq!(tree.any_in_circle(x, y, r), !results.is_empty());
+        }
+        assert_eq!(tree.count_in_circle(500.0, 500.0, -1.0), 0);
+        assert!(!tree.any_in_circle(500.0, 500.0, -1.0));
+    }
+
+    #[test]
+    fn test_any_containing_point_matches_query() {
+        let tree = build_grid_tree(20);
+        let mut results = Vec::new();
+        for &(x, y) in &[(0.0, 0.0), (1.5, 1.5), (3.0, 3.0), (100.0, 100.0), (-1.0, 5.0)] {
+            tree.query_point(x, y, &mut results);
+            assert_eq!(tree.any_containing_point(x, y), !results.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_count_and_any_on_empty_tree() {
+        let tree = HilbertRTree::new();
+        assert_eq!(tree.count_intersecting(0.0, 0.0, 10.0, 10.0), 0);
+        assert_eq!(tree.count_in_circle(0.0, 0.0, 10.0), 0);
+        assert!(!tree.any_intersecting(0.0, 0.0, 10.0, 10.0));
+        assert!(!tree.any_in_circle(0.0, 0.0, 10.0));
+        assert!(!tree.any_containing_point(0.0, 0.0));
+    }
+
+    // ============================================================================
+    // RANGE OUTPUT TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_query_intersecting_ranges_matches_query() {
+        let tree = tree_from(&random_boxes(37, 3000, 1000.0, 15.0));
+        let mut results = Vec::new();
+        let mut ranges = Vec::new();
+        let mut ids = Vec::new();
+        for &(min_x, min_y, max_x, max_y) in &[(0.0, 0.0, 1000.0, 1000.0), (100.0, 150.0, 700.0, 600.0), (400.0, 400.0, 410.0, 410.0), (-9.0, -9.0, -1.0, -1.0)] {
+            tree.query_intersecting(min_x, min_y, max_x, max_y, &mut results);
+            tree.query_intersecting_ranges(min_x, min_y, max_x, max_y, &mut ranges);
+            for pair in ranges.windows(2) {
+                assert!(pair[0].end < pair[1].start, "ranges must be sorted, disjoint and merged");
+            }
+            tree.leaf_ids(&ranges, &mut ids);
+            results.sort();
+            ids.sort();
+            assert_eq!(ids, results);
+        }
+    }
+
+    #[test]
+    fn test_query_intersecting_ranges_full_cover_is_single_range() {
+        let tree = build_grid_tree(40);
+        let mut ranges = Vec::new();
+        tree.query_intersecting_ranges(-1.0, -1.0, 1000.0, 1000.0, &mut ranges);
+        assert_eq!(ranges, vec![0..1600]);
+
+        tree.query_intersecting_ranges(10.0, 10.0, 60.0, 60.0, &mut ranges);
+        let covered: usize = ranges.iter().map(|r| r.len()).sum();
+        assert_eq!(covered, tree.count_intersecting(10.0, 10.0, 60.0, 60.0));
+        assert!(ranges.len() < covered, "covered nodes should be emitted as ranges");
+    }
+
+    #[test]
+    fn test_leaf_ids_ignores_out_of_bounds_positions() {
+        let tree = build_grid_tree(3);
+        let mut ids = Vec::new();
+        tree.leaf_ids(&[7..20, 50..60], &mut ids);
+        assert_eq!(ids.len(), 2);
+
+        let empty = HilbertRTree::new();
+        let mut ranges = vec![0..1];
+        empty.query_intersecting_ranges(0.0, 0.0, 1.0, 1.0, &mut ranges);
+        assert!(ranges.is_empty());
+    }
+
+    // ============================================================================
+    // AGGREGATE TESTS
+    // ============================================================================
+
+    fn weighted_tree(boxes: &[(f64, f64, f64, f64)]) -> HilbertRTree {
+        let mut tree = HilbertRTree::with_capacity(boxes.len());
+        for (i, b) in boxes.iter().enumerate() {
+            tree.add_weighted(b.0, b.1, b.2, b.3, (i % 97) as f64 - 20.0);
+        }
+        tree.build();
+        tree
+    }
+
+    #[test]
+    fn test_aggregate_in_rect_matches_brute_force() {
+        use crate::Aggregate;
+
+        let boxes = random_boxes(38, 3000, 1000.0, 15.0);
+        let tree = weighted_tree(&boxes);
+        for query in &[(0.0, 0.0, 1000.0, 1000.0), (100.0, 150.0, 700.0, 600.0), (400.0, 400.0, 420.0, 410.0)] {
+            let mut expected = Aggregate::default();
+            for (i, b) in boxes.iter().enumerate() {
+                if overlap(b, query) {
+                    expected.merge(&Aggregate::from_weight((i % 97) as f64 - 20.0));
+                }
+            }
+            let aggregate = tree.aggregate_in_rect(query.0, query.1, query.2, query.3).unwrap();
+            assert_eq!(aggregate.count, expected.count);
+            assert_eq!(aggregate.count, tree.count_intersecting(query.0, query.1, query.2, query.3));
+            assert!((aggregate.sum - expected.sum).abs() < 1e-6);
+            assert_eq!((aggregate.min, aggregate.max), (expected.min, expected.max));
+        }
+
+        let none = tree.aggregate_in_rect(-10.0, -10.0, -5.0, -5.0).unwrap();
+        assert_eq!(none, Aggregate::default());
+        assert_eq!(none.mean(), None);
+    }
+
+    #[test]
+    fn test_aggregate_in_rect_unweighted_and_mixed() {
+        assert!(build_grid_tree(5).aggregate_in_rect(0.0, 0.0, 100.0, 100.0).is_none());
+
+        // Items added without a weight count with weight 0
+        let mut tree = HilbertRTree::new();
+        tree.add(0.0, 0.0, 1.0, 1.0);
+        tree.add_weighted(2.0, 2.0, 3.0, 3.0, 10.0);
+        tree.add(4.0, 4.0, 5.0, 5.0);
+        tree.build();
+        let aggregate = tree.aggregate_in_rect(0.0, 0.0, 5.0, 5.0).unwrap();
+        assert_eq!(aggregate.count, 3);
+        assert_eq!(aggregate.sum, 10.0);
+        assert_eq!((aggregate.min, aggregate.max), (0.0, 10.0));
+    }
+
+    #[test]
+    fn test_aggregate_survives_save_load() {
+        let boxes = random_boxes(39, 500, 100.0, 5.0);
+        let tree = weighted_tree(&boxes);
+        let path = std::env::temp_dir().join("aabb_aggregate_save_load.bin");
+        tree.save(&path).unwrap();
+        let loaded = HilbertRTree::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            loaded.aggregate_in_rect(10.0, 20.0, 60.0, 70.0),
+            tree.aggregate_in_rect(10.0, 20.0, 60.0, 70.0)
+        );
+
+        let unweighted = build_grid_tree(4);
+        let path = std::env::temp_dir().join("aabb_aggregate_unweighted.bin");
+        unweighted.save(&path).unwrap();
+        let loaded = HilbertRTree::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(loaded.aggregate_in_rect(0.0, 0.0, 10.0, 10.0).is_none());
+    }
+
+    // ============================================================================
+    // BOUNDARY TESTS
+    // ============================================================================
+
+    /// Unit cells tiling [0, 30] x [0, 30], so neighbors share edges and corners
+    fn tiled_boxes() -> Vec<(f64, f64, f64, f64)> {
+        let mut boxes = Vec::new();
+        for i in 0..30 {
+            for j in 0..30 {
+                boxes.push((i as f64, j as f64, i as f64 + 1.0, j as f64 + 1.0));
+            }
+        }
+        boxes
+    }
+
+    fn sorted(mut ids: Vec<usize>) -> Vec<usize> {
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn test_boundary_closed_matches_plain_queries() {
+        use crate::Boundary;
+
+        let tree = tree_from(&tiled_boxes());
+        let mut plain = Vec::new();
+        let mut with_boundary = Vec::new();
+
+        tree.query_intersecting(3.0, 4.0, 7.0, 9.0, &mut plain);
+        tree.query_intersecting_with_boundary(3.0, 4.0, 7.0, 9.0, Boundary::Closed, &mut with_boundary);
+        assert_eq!(sorted(with_boundary.clone()), sorted(plain.clone()));
+
+        tree.query_point(5.0, 5.0, &mut plain);
+        tree.query_point_with_boundary(5.0, 5.0, Boundary::Closed, &mut with_boundary);
+        assert_eq!(sorted(with_boundary.clone()), sorted(plain.clone()));
+
+        tree.query_contain(5.0, 5.0, 6.0, 6.0, &mut plain);
+        tree.query_contain_with_boundary(5.0, 5.0, 6.0, 6.0, Boundary::Closed, &mut with_boundary);
+        assert_eq!(sorted(with_boundary.clone()), sorted(plain.clone()));
+
+        tree.query_contained_within(2.0, 2.0, 6.0, 5.0, &mut plain);
+        tree.query_contained_within_with_boundary(2.0, 2.0, 6.0, 5.0, Boundary::Closed, &mut with_boundary);
+        assert_eq!(sorted(with_boundary), sorted(plain));
+    }
+
+    #[test]
+    fn test_boundary_intersecting_excludes_touching() {
+        use crate::Boundary;
+
+        let boxes = tiled_boxes();
+        let tree = tree_from(&boxes);
+        let mut results = Vec::new();
+
+        // The query is exactly cell (5, 5): closed semantics add its 8 neighbors
+        tree.query_intersecting_with_boundary(5.0, 5.0, 6.0, 6.0, Boundary::Closed, &mut results);
+        assert_eq!(results.len(), 9);
+        for boundary in [Boundary::Open, Boundary::HalfOpen] {
+            tree.query_intersecting_with_boundary(5.0, 5.0, 6.0, 6.0, boundary, &mut results);
+            assert_eq!(results, vec![5 * 30 + 5]);
+        }
+
+        // Open and half-open match a strict brute force on a larger query
+        let mut expected: Vec<usize> = (0..boxes.len())
+            .filter(|&i| boxes[i].0 < 12.5 && boxes[i].2 > 3.0 && boxes[i].1 < 20.0 && boxes[i].3 > 7.0)
+            .collect();
+        expected.sort_unstable();
+        tree.query_intersecting_with_boundary(3.0, 7.0, 12.5, 20.0, Boundary::Open, &mut results);
+        assert_eq!(sorted(results.clone()), expected);
+    }
+
+    #[test]
+    fn test_boundary_point_on_shared_corner() {
+        use crate::Boundary;
+
+        let tree = tree_from(&tiled_boxes());
+        let mut results = Vec::new();
+
+        // A shared corner is inside four cells when closed, none when open,
+        // and exactly one (the cell to the upper right) when half-open
+        tree.query_point_with_boundary(10.0, 10.0, Boundary::Closed, &mut results);
+        assert_eq!(results.len(), 4);
+        tree.query_point_with_boundary(10.0, 10.0, Boundary::Open, &mut results);
+        assert!(results.is_empty());
+        tree.query_point_with_boundary(10.0, 10.0, Boundary::HalfOpen, &mut results);
+        assert_eq!(results, vec![10 * 30 + 10]);
+        tree.query_point_with_boundary(10.5, 10.5, Boundary::Open, &mut results);
+        assert_eq!(results, vec![10 * 30 + 10]);
+    }
+
+    #[test]
+    fn test_boundary_containment() {
+        use crate::Boundary;
+
+        let tree = tree_from(&tiled_boxes());
+        let mut results = Vec::new();
+
+        tree.query_contain_with_boundary(4.0, 4.0, 5.0, 5.0, Boundary::HalfOpen, &mut results);
+        assert_eq!(results, vec![4 * 30 + 4]);
+        tree.query_contain_with_boundary(4.0, 4.0, 5.0, 5.0, Boundary::Open, &mut results);
+        assert!(results.is_empty());
+        tree.query_contain_with_boundary(4.25, 4.25, 4.75, 4.75, Boundary::Open, &mut results);
+        assert_eq!(results, vec![4 * 30 + 4]);
+
+        // 3x3 cells fit in [2, 5]^2; with open semantics only the middle one avoids the edges
+        tree.query_contained_within_with_boundary(2.0, 2.0, 5.0, 5.0, Boundary::HalfOpen, &mut results);
+        assert_eq!(results.len(), 9);
+        tree.query_contained_within_with_boundary(2.0, 2.0, 5.0, 5.0, Boundary::Open, &mut results);
+        assert_eq!(results, vec![3 * 30 + 3]);
+    }
+
+    // ============================================================================
+    // NEAREST TO BOX / SEGMENT TESTS
+    // ============================================================================
+
+    /// Brute-force squared distance from segment to box by dense sampling of the segment
+    fn sampled_segment_distance_sq(seg: (f64, f64, f64, f64), b: &(f64, f64, f64, f64)) -> f64 {
+        (0..=2000)
+            .map(|i| {
+                let t = i as f64 / 2000.0;
+                let (x, y) = (seg.0 + t * (seg.2 - seg.0), seg.1 + t * (seg.3 - seg.1));
+                let dx = (b.0 - x).max(x - b.2).max(0.0);
+                let dy = (b.1 - y).max(y - b.3).max(0.0);
+                dx * dx + dy * dy
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    #[test]
+    fn test_query_nearest_k_to_box_matches_brute_force() {
+        let boxes = random_boxes(40, 1500, 1000.0, 10.0);
+        let tree = tree_from(&boxes);
+        let mut results = Vec::new();
+        for query in [(400.0, 400.0, 460.0, 430.0), (-50.0, -50.0, -40.0, -40.0), (0.0, 0.0, 1000.0, 1000.0)] {
+            let k = 12;
+            tree.query_nearest_k_to_box(query.0, query.1, query.2, query.3, k, &mut results);
+            assert_eq!(results.len(), k);
+
+            let qb = (query.0, query.1, query.2, query.3);
+            let mut dists: Vec<f64> = boxes.iter().map(|b| gap(b, &qb)).collect();
+            dists.sort_by(f64::total_cmp);
+            let got: Vec<f64> = results.iter().map(|&id| gap(&boxes[id], &qb)).collect();
+            for pair in got.windows(2) {
+                assert!(pair[0] <= pair[1], "results must be sorted by distance");
+            }
+            assert!((got[k - 1] - dists[k - 1]).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_query_nearest_k_to_segment_matches_brute_force() {
+        let boxes = random_boxes(41, 800, 500.0, 8.0);
+        let tree = tree_from(&boxes);
+        let mut results = Vec::new();
+        for seg in [(100.0, 100.0, 400.0, 250.0), (250.0, -20.0, 250.0, -10.0), (30.0, 450.0, 30.0, 450.0)] {
+            let k = 8;
+            tree.query_nearest_k_to_segment(seg.0, seg.1, seg.2, seg.3, k, &mut results);
+            assert_eq!(results.len(), k);
+
+            let mut dists: Vec<f64> = boxes.iter().map(|b| sampled_segment_distance_sq(seg, b)).collect();
+            dists.sort_by(f64::total_cmp);
+            let kth = sampled_segment_distance_sq(seg, &boxes[results[k - 1]]);
+            // Sampling overestimates slightly, so compare with a tolerance
+            assert!((kth.sqrt() - dists[k - 1].sqrt()).abs() < 0.5);
+        }
+    }
+
+    #[test]
+    fn test_query_nearest_k_to_segment_crossing_is_zero_distance() {
+        let mut tree = HilbertRTree::new();
+        tree.add(4.0, -1.0, 5.0, 1.0);  // Crossed by the segment
+        tree.add(0.0, 3.0, 1.0, 4.0);
+        tree.add(-5.0, -5.0, -4.0, -4.0);
+        tree.build();
+
+        let mut results = Vec::new();
+        tree.query_nearest_k_to_segment(0.0, 0.0, 10.0, 0.0, 1, &mut results);
+        assert_eq!(results, vec![0]);
+        tree.query_nearest_k_to_segment(0.0, 0.0, 10.0, 0.0, 0, &mut results);
+        assert!(results.is_empty());
+        tree.query_nearest_k_to_box(-10.0, -10.0, -9.0, -9.0, 5, &mut results);
+        assert_eq!(results, vec![2, 1, 0]);
+        HilbertRTree::new().query_nearest_k_to_box(0.0, 0.0, 1.0, 1.0, 3, &mut results);
+        assert!(results.is_empty());
+    }
+
+    // ============================================================================
+    // METRIC TESTS
+    // ============================================================================
+
+    fn metric_gaps(b: &(f64, f64, f64, f64), x: f64, y: f64) -> (f64, f64) {
+        ((b.0 - x).max(x - b.2).max(0.0), (b.1 - y).max(y - b.3).max(0.0))
+    }
+
+    #[test]
+    fn test_metric_euclidean_matches_plain_queries() {
+        use crate::Euclidean;
+
+        let tree = tree_from(&random_boxes(42, 1500, 1000.0, 10.0));
+        let mut plain = Vec::new();
+        let mut with_metric = Vec::new();
+
+        tree.query_circle(500.0, 400.0, 120.0, &mut plain);
+        tree.query_circle_with_metric(500.0, 400.0, 120.0, Euclidean, &mut with_metric);
+        plain.sort_unstable();
+        with_metric.sort_unstable();
+        assert_eq!(with_metric, plain);
+
+        tree.query_nearest_k(300.0, 700.0, 10, &mut plain);
+        tree.query_nearest_k_with_metric(300.0, 700.0, 10, Euclidean, &mut with_metric);
+        assert_eq!(with_metric.len(), 10);
+        plain.sort_unstable();
+        with_metric.sort_unstable();
+        assert_eq!(with_metric, plain);
+    }
+
+    #[test]
+    fn test_metric_manhattan_and_chebyshev_match_brute_force() {
+        use crate::{Chebyshev, Manhattan, Metric};
+
+        fn check<M: Metric + Copy>(metric: M, boxes: &[(f64, f64, f64, f64)], tree: &HilbertRTree) {
+            let (x, y, r, k) = (420.0, 610.0, 90.0, 15);
+            let dist = |b: &(f64, f64, f64, f64)| {
+                let (dx, dy) = metric_gaps(b, x, y);
+                metric.distance(dx, dy)
+            };
+
+            let mut results = Vec::new();
+            tree.query_circle_with_metric(x, y, r, metric, &mut results);
+            results.sort_unstable();
+            let expected: Vec<usize> = (0..boxes.len()).filter(|&i| dist(&boxes[i]) <= r).collect();
+            assert!(!expected.is_empty());
+            assert_eq!(results, expected);
+
+            tree.query_nearest_k_with_metric(x, y, k, metric, &mut results);
+            let mut dists: Vec<f64> = boxes.iter().map(dist).collect();
+            dists.sort_by(f64::total_cmp);
+            let got: Vec<f64> = results.iter().map(|&i| dist(&boxes[i])).collect();
+            assert_eq!(got, dists[..k].to_vec());
+        }
+
+        let boxes = random_boxes(43, 2000, 1000.0, 10.0);
+        let tree = tree_from(&boxes);
+        check(Manhattan, &boxes, &tree);
+        check(Chebyshev, &boxes, &tree);
+    }
+
+    #[test]
+    fn test_metric_point_queries() {
+        use crate::{Chebyshev, Euclidean, Manhattan};
+
+        let mut tree = HilbertRTree::with_capacity(441);
+        for i in -10..=10 {
+            for j in -10..=10 {
+                tree.add_point(i as f64, j as f64);
+            }
+        }
+        tree.build();
+
+        // Lattice points within L1 radius 3 form a diamond of 2*3*4 + 1 = 25 points,
+        // within L∞ radius 3 a 7x7 square
+        let mut results = Vec::new();
+        tree.query_circle_points_with_metric(0.0, 0.0, 3.0, Manhattan, &mut results);
+        assert_eq!(results.len(), 25);
+        tree.query_circle_points_with_metric(0.0, 0.0, 3.0, Chebyshev, &mut results);
+        assert_eq!(results.len(), 49);
+        tree.query_circle_points_with_metric(0.0, 0.0, -1.0, Manhattan, &mut results);
+        assert!(results.is_empty());
+
+        tree.query_nearest_k_points_with_metric(0.2, 0.1, 5, Manhattan, &mut results);
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0], 10 * 21 + 10);
+
+        // The point fast path agrees with the box distance for every metric
+        let mut expected = Vec::new();
+        for &(x, y) in &[(0.2, 0.1), (-7.3, 4.6), (12.0, -12.0)] {
+            tree.query_nearest_k_with_metric(x, y, 9, Manhattan, &mut expected);
+            tree.query_nearest_k_points_with_metric(x, y, 9, Manhattan, &mut results);
+            assert_eq!(results, expected);
+            tree.query_nearest_k_with_metric(x, y, 9, Chebyshev, &mut expected);
+            tree.query_nearest_k_points_with_metric(x, y, 9, Chebyshev, &mut results);
+            assert_eq!(results, expected);
+            tree.query_nearest_k_with_metric(x, y, 9, Euclidean, &mut expected);
+            tree.query_nearest_k_points_with_metric(x, y, 9, Euclidean, &mut results);
+            assert_eq!(results, expected);
+        }
+    }
+
+    // ============================================================================
+    // FARTHEST / REVERSE KNN TESTS
+    // ============================================================================
+
+    fn point_gap(b: &(f64, f64, f64, f64), x: f64, y: f64) -> f64 {
+        let dx = (b.0 - x).max(x - b.2).max(0.0);
+        let dy = (b.1 - y).max(y - b.3).max(0.0);
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    #[test]
+    fn test_query_farthest_k_matches_brute_force() {
+        let boxes = random_boxes(44, 2000, 1000.0, 10.0);
+        let tree = tree_from(&boxes);
+        let mut results = Vec::new();
+        for &(x, y) in &[(500.0, 500.0), (0.0, 0.0), (1200.0, -300.0)] {
+            let k = 10;
+            tree.query_farthest_k(x, y, k, &mut results);
+            let got: Vec<f64> = results.iter().map(|&i| point_gap(&boxes[i], x, y)).collect();
+            let mut dists: Vec<f64> = boxes.iter().map(|b| point_gap(b, x, y)).collect();
+            dists.sort_by(|a, b| b.total_cmp(a));
+            assert_eq!(got, dists[..k].to_vec());
+        }
+
+        tree.query_farthest_k(0.0, 0.0, 5000, &mut results);
+        assert_eq!(results.len(), 2000);
+        HilbertRTree::new().query_farthest_k(0.0, 0.0, 3, &mut results);
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_query_reverse_nearest_k_matches_brute_force() {
+        let boxes = random_boxes(45, 600, 500.0, 6.0);
+        let tree = tree_from(&boxes);
+        let mut results = Vec::new();
+        for &(x, y, k) in &[(250.0, 250.0, 1), (100.0, 400.0, 3), (-3.0, 250.0, 5)] {
+            tree.query_reverse_nearest_k(x, y, k, &mut results);
+            results.sort_unstable();
+
+            let expected: Vec<usize> = (0..boxes.len())
+                .filter(|&i| {
+                    let to_point = point_gap(&boxes[i], x, y);
+                    let closer = (0..boxes.len()).filter(|&j| j != i && gap(&boxes[i], &boxes[j]) < to_point).count();
+                    closer < k
+                })
+                .collect();
+            assert!(!expected.is_empty(), "no reverse neighbors for ({x}, {y}, {k})");
+            assert_eq!(results, expected);
+        }
+    }
+
+    #[test]
+    fn test_query_reverse_nearest_k_points_matches_brute_force() {
+        // Clustered points, so whole nodes get pruned and ties between equal distances occur
+        let mut tree = HilbertRTree::new();
+        let mut points = Vec::new();
+        for i in 0..1500 {
+            let (x, y) = (((i * 37) % 97) as f64 * 2.0, ((i * 61) % 89) as f64 * 2.0);
+            points.push((x, y));
+            tree.add_point(x, y);
+        }
+        tree.build();
+        let mut results = Vec::new();
+        for (q, k) in (0..20).map(|q| (q, 1 + q % 6)) {
+            let (x, y) = ((q * 23 % 200) as f64 + 0.5, (q * 41 % 180) as f64);
+            tree.query_reverse_nearest_k(x, y, k, &mut results);
+            results.sort_unstable();
+
+            let dist_sq = |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2);
+            let expected: Vec<usize> = (0..points.len())
+                .filter(|&i| {
+                    let to_point = dist_sq(points[i], (x, y));
+                    (0..points.len()).filter(|&j| j != i && dist_sq(points[i], points[j]) < to_point).count() < k
+                })
+                .collect();
+            assert_eq!(results, expected, "query {q}");
+        }
+    }
+
+    // ============================================================================
+    // ID-BASED NEIGHBOR TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_query_intersecting_id_after_hilbert_sort() {
+        // More items than one node, so leaf positions no longer match item ids
+        let boxes = random_boxes(46, 500, 200.0, 12.0);
+        let tree = tree_from(&boxes);
+        let mut results = Vec::new();
+        for id in [0, 17, 250, 499] {
+            tree.query_intersecting_id(id, &mut results).unwrap();
+            results.sort_unstable();
+            let expected: Vec<usize> = (0..boxes.len()).filter(|&j| j != id && overlap(&boxes[id], &boxes[j])).collect();
+            assert_eq!(results, expected, "item {id}");
+        }
+    }
+
+    #[test]
+    fn test_query_nearest_k_id_matches_brute_force() {
+        let boxes = random_boxes(47, 1000, 500.0, 8.0);
+        let tree = tree_from(&boxes);
+        let mut results = Vec::new();
+        for id in [0, 123, 999] {
+            let k = 7;
+            tree.query_nearest_k_id(id, k, &mut results).unwrap();
+            assert_eq!(results.len(), k);
+            assert!(!results.contains(&id));
+
+            let mut dists: Vec<f64> = (0..boxes.len()).filter(|&j| j != id).map(|j| gap(&boxes[id], &boxes[j])).collect();
+            dists.sort_by(f64::total_cmp);
+            let got: Vec<f64> = results.iter().map(|&j| gap(&boxes[id], &boxes[j])).collect();
+            assert_eq!(got, dists[..k].to_vec());
+        }
+        assert!(tree.query_nearest_k_id(1000, 3, &mut results).is_err());
+    }
+
+    #[test]
+    fn test_id_queries_with_lookup_table() {
+        let boxes = random_boxes(49, 800, 400.0, 8.0);
+        let plain = tree_from(&boxes);
+        let mut indexed = tree_from(&boxes);
+        indexed.set_id_lookup(true);
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        for id in [0, 1, 399, 799] {
+            plain.query_intersecting_id(id, &mut a).unwrap();
+            indexed.query_intersecting_id(id, &mut b).unwrap();
+            assert_eq!(sorted(a.clone()), sorted(b.clone()));
+            plain.query_nearest_k_id(id, 6, &mut a).unwrap();
+            indexed.query_nearest_k_id(id, 6, &mut b).unwrap();
+            assert_eq!(a, b);
+            plain.query_circle_id(id, 20.0, &mut a).unwrap();
+            indexed.query_circle_id(id, 20.0, &mut b).unwrap();
+            assert_eq!(sorted(a.clone()), sorted(b.clone()));
+        }
+        assert!(indexed.query_nearest_k_id(800, 1, &mut b).is_err());
+    }
+
+    #[test]
+    fn test_query_circle_id_matches_brute_force() {
+        let boxes = random_boxes(48, 1000, 500.0, 8.0);
+        let tree = tree_from(&boxes);
+        let mut results = Vec::new();
+        for (id, radius) in [(5, 0.0), (321, 25.0), (998, 60.0)] {
+            tree.query_circle_id(id, radius, &mut results).unwrap();
+            results.sort_unstable();
+            let expected: Vec<usize> = (0..boxes.len()).filter(|&j| j != id && gap(&boxes[id], &boxes[j]) <= radius).collect();
+            assert_eq!(results, expected);
+        }
+        assert!(tree.query_circle_id(1000, 1.0, &mut results).is_err());
+    }
+
+    // ============================================================================
+    // ID LOOKUP TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_id_lookup_get_matches_scan() {
+        let boxes = random_boxes(49, 2000, 1000.0, 10.0);
+        let mut tree = HilbertRTree::with_capacity(boxes.len());
+        tree.set_id_lookup(true);
+        for b in &boxes {
+            tree.add(b.0, b.1, b.2, b.3);
+        }
+        tree.build();
+
+        let scan = tree_from(&boxes);
+        for (id, b) in boxes.iter().enumerate() {
+            assert_eq!(tree.get(id), Some(*b));
+            assert_eq!(scan.get(id), Some(*b));
+        }
+        assert_eq!(tree.get(boxes.len()), None);
+
+        let mut with_lookup = Vec::new();
+        let mut without = Vec::new();
+        tree.query_nearest_k_id(77, 5, &mut with_lookup).unwrap();
+        scan.query_nearest_k_id(77, 5, &mut without).unwrap();
+        assert_eq!(with_lookup, without);
+    }
+
+    #[test]
+    fn test_id_lookup_toggle_after_build() {
+        let boxes = random_boxes(50, 300, 100.0, 5.0);
+        let mut tree = tree_from(&boxes);
+        tree.set_id_lookup(true);
+        assert_eq!(tree.get(123), Some(boxes[123]));
+        tree.set_id_lookup(false);
+        assert_eq!(tree.get(123), Some(boxes[123]));
+
+        // Small trees (single root node) work the same way
+        let mut small = HilbertRTree::new();
+        small.set_id_lookup(true);
+        small.add_point(3.0, 4.0);
+        small.add_point(1.0, 2.0);
+        small.build();
+        assert_eq!(small.get_point(1), Some((1.0, 2.0)));
+    }
+
+    #[test]
+    fn test_id_lookup_survives_save_load() {
+        let boxes = random_boxes(51, 500, 100.0, 5.0);
+        let mut tree = HilbertRTree::with_capacity(boxes.len());
+        tree.set_id_lookup(true);
+        for (i, b) in boxes.iter().enumerate() {
+            tree.add_weighted(b.0, b.1, b.2, b.3, i as f64);
+        }
+        tree.build();
+
+        let path = std::env::temp_dir().join("aabb_id_lookup_save_load.bin");
+        tree.save(&path).unwrap();
+        let loaded = HilbertRTree::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        for id in [0, 250, 499] {
+            assert_eq!(loaded.get(id), Some(boxes[id]));
+        }
+        assert_eq!(
+            loaded.aggregate_in_rect(0.0, 0.0, 50.0, 50.0),
+            tree.aggregate_in_rect(0.0, 0.0, 50.0, 50.0)
+        );
+    }
+
+    #[test]
+    fn test_id_lookup_table_is_saved() {
+        let boxes = random_boxes(53, 300, 100.0, 5.0);
+        let mut tree = tree_from(&boxes);
+        let without = tree.to_bytes();
+        tree.set_id_lookup(true);
+        let with = tree.to_bytes();
+        // Tag byte plus one u32 leaf position per item, read back instead of rebuilt
+        assert_eq!(with.len(), without.len() + 1 + 4 * boxes.len());
+        assert_eq!(with[without.len()], 0x02);
+
+        let path = std::env::temp_dir().join("aabb_id_lookup_table.bin");
+        std::fs::write(&path, &with).unwrap();
+        let loaded = HilbertRTree::load(&path).unwrap();
+        assert_eq!(loaded.to_bytes(), with);
+        assert_eq!(loaded.get(123), Some(boxes[123]));
+
+        // A table entry pointing at the wrong leaf is rejected
+        let mut corrupt = with.clone();
+        let entry = without.len() + 1;
+        corrupt[entry] = corrupt[entry].wrapping_add(1);
+        std::fs::write(&path, &corrupt).unwrap();
+        let err = HilbertRTree::load(&path).unwrap_err();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    // ============================================================================
+    // PERMUTATION / ITER TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_sorted_order_is_permutation_matching_iter() {
+        let boxes = random_boxes(52, 1000, 500.0, 5.0);
+        let tree = tree_from(&boxes);
+
+        let order = tree.permutation();
+        assert_eq!(order, tree.sorted_order().collect::<Vec<_>>());
+        let mut seen = order.clone();
+        seen.sort_unstable();
+        assert_eq!(seen, (0..boxes.len()).collect::<Vec<_>>());
+        assert_ne!(order, seen, "a tree larger than one node is reordered by build");
+
+        let items: Vec<_> = tree.iter().collect();
+        assert_eq!(items.len(), boxes.len());
+        for (&(id, bbox), &expected_id) in items.iter().zip(&order) {
+            assert_eq!(id, expected_id);
+            assert_eq!(bbox, boxes[id]);
+        }
+
+        let sorted_boxes = tree.apply_permutation(&boxes);
+        assert_eq!(sorted_boxes, items.iter().map(|&(_, bbox)| bbox).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_sorted_order_before_build_is_insertion_order() {
+        let mut tree = HilbertRTree::new();
+        tree.add(5.0, 5.0, 6.0, 6.0);
+        tree.add(0.0, 0.0, 1.0, 1.0);
+        assert_eq!(tree.permutation(), vec![0, 1]);
+        assert_eq!(tree.iter().map(|(id, _)| id).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(HilbertRTree::new().iter().count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected one value per item")]
+    fn test_apply_permutation_length_mismatch() {
+        let tree = build_grid_tree(3);
+        drop(tree.apply_permutation(&[1, 2, 3]));
+    }
+
+    // ============================================================================
+    // HILBERT MODULE TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_hilbert_16_round_trip_and_adjacency() {
+        use crate::hilbert::{index_to_xy, xy_to_index};
+
+        for x in (0..=u16::MAX).step_by(257) {
+            for y in (0..=u16::MAX).step_by(263) {
+                assert_eq!(index_to_xy(xy_to_index(x, y)), (x, y));
+            }
+        }
+        let (x, y) = index_to_xy(u32::MAX);
+        assert_eq!(xy_to_index(x, y), u32::MAX);
+
+        let mut prev = index_to_xy(0);
+        assert_eq!(prev, (0, 0));
+        for index in 1..100_000_u32 {
+            let cell = index_to_xy(index);
+            assert_eq!(prev.0.abs_diff(cell.0) + prev.1.abs_diff(cell.1), 1, "index {index}");
+            prev = cell;
+        }
+    }
+
+    #[test]
+    fn test_hilbert_32_round_trip_and_consistency() {
+        use crate::hilbert::{index_to_xy_32, xy_to_index, xy_to_index_32};
+
+        let mut state = 0x9E37_79B9_7F4A_7C15_u64;
+        for _ in 0..10_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let (x, y) = ((state >> 32) as u32, state as u32);
+            let index = xy_to_index_32(x, y);
+            assert_eq!(index_to_xy_32(index), (x, y));
+            assert_eq!((index >> 32) as u32, xy_to_index((x >> 16) as u16, (y >> 16) as u16));
+        }
+
+        let mut prev = index_to_xy_32(u64::MAX - 50_000);
+        for index in (u64::MAX - 49_999)..=u64::MAX {
+            let cell = index_to_xy_32(index);
+            assert_eq!(prev.0.abs_diff(cell.0) + prev.1.abs_diff(cell.1), 1);
+            prev = cell;
+        }
+    }
+
+    #[test]
+    fn test_hilbert_batch_matches_scalar() {
+        use crate::hilbert::*;
+
+        let points = vec![(0_u16, 0_u16), (1, 2), (65535, 3), (400, 65535)];
+        let mut indices = vec![99];
+        xy_to_index_batch(&points, &mut indices);
+        assert_eq!(indices, points.iter().map(|&(x, y)| xy_to_index(x, y)).collect::<Vec<_>>());
+        let mut back = Vec::new();
+        index_to_xy_batch(&indices, &mut back);
+        assert_eq!(back, points);
+
+        let points_32 = vec![(0_u32, 7_u32), (u32::MAX, 1), (123_456_789, 987_654_321)];
+        let mut indices_32 = Vec::new();
+        xy_to_index_32_batch(&points_32, &mut indices_32);
+        let mut back_32 = Vec::new();
+        index_to_xy_32_batch(&indices_32, &mut back_32);
+        assert_eq!(back_32, points_32);
+    }
+
+    #[test]
+    fn test_hilbert_key_matches_build_order() {
+        use crate::hilbert::{key, keys};
+
+        let boxes = random_boxes(53, 2000, 700.0, 4.0);
+        let tree = tree_from(&boxes);
+        let bounds = boxes.iter().fold(
+            (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            |b, &(x0, y0, x1, y1)| (b.0.min(x0), b.1.min(y0), b.2.max(x1), b.3.max(y1)),
+        );
+        let centers: Vec<_> = boxes.iter().map(|&(x0, y0, x1, y1)| ((x0 + x1) / 2.0, (y0 + y1) / 2.0)).collect();
+
+        let mut center_keys = Vec::new();
+        keys(&centers, bounds, &mut center_keys);
+        let in_tree_order: Vec<u32> = tree.sorted_order().map(|id| center_keys[id]).collect();
+        assert!(in_tree_order.windows(2).all(|w| w[0] <= w[1]), "keys must be non-decreasing in build order");
+
+        // Outside points clamp to the grid edge
+        assert_eq!(key(-10.0, -10.0, bounds), 0);
+        assert_eq!(key(1e9, bounds.1, bounds), key(bounds.2, bounds.1, bounds));
+    }
+
+    // ============================================================================
+    // PERIODIC WORLD TESTS
+    // ============================================================================
+
+    /// Brute-force wrapped distance: minimum flat point-box distance over the 3x3 images
+    fn wrapped_distance_brute(b: (f64, f64, f64, f64), x: f64, y: f64, period: f64) -> f64 {
+        let mut best = f64::INFINITY;
+        for i in -1..=1 {
+            for j in -1..=1 {
+                let (px, py) = (x + f64::from(i) * period, y + f64::from(j) * period);
+                let dx = (b.0 - px).max(px - b.2).max(0.0);
+                let dy = (b.1 - py).max(py - b.3).max(0.0);
+                best = best.min(dx.hypot(dy));
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn test_periodic_queries_match_brute_force() {
+        let period = 100.0;
+        let boxes = random_boxes(54, 1500, period, 6.0);
+        let mut tree = tree_from(&boxes);
+        tree.set_period(period, period);
+        assert_eq!(tree.period(), Some((period, period)));
+
+        let mut results = Vec::new();
+        for &(x, y, w, h) in &[(97.0, 97.0, 6.0, 6.0), (-4.0, 40.0, 8.0, 3.0), (10.0, 10.0, 5.0, 5.0), (150.0, -60.0, 20.0, 2.0)] {
+            tree.query_intersecting_wrapped(x, y, x + w, y + h, &mut results);
+            let mut expected = Vec::new();
+            for (id, b) in boxes.iter().enumerate() {
+                let hit = (-2..=2).any(|i| {
+                    (-2..=2).any(|j| {
+                        let (qx, qy) = (x + f64::from(i) * period, y + f64::from(j) * period);
+                        qx <= b.2 && b.0 <= qx + w && qy <= b.3 && b.1 <= qy + h
+                    })
+                });
+                if hit {
+                    expected.push(id);
+                }
+            }
+            assert_eq!(sorted(results.clone()), expected, "query at ({x}, {y})");
+        }
+
+        for &(x, y, r) in &[(99.0, 1.0, 4.0), (50.0, 0.0, 7.5), (0.0, 50.0, 0.0)] {
+            tree.query_circle_wrapped(x, y, r, &mut results);
+            let expected: Vec<usize> = (0..boxes.len()).filter(|&id| wrapped_distance_brute(boxes[id], x, y, period) <= r).collect();
+            assert_eq!(sorted(results.clone()), expected, "circle at ({x}, {y})");
+        }
+
+        for &(x, y) in &[(99.5, 99.5), (0.2, 55.0), (-30.0, 130.0)] {
+            tree.query_nearest_k_wrapped(x, y, 10, &mut results);
+            assert_eq!(results.len(), 10);
+            let mut all: Vec<f64> = boxes.iter().map(|&b| wrapped_distance_brute(b, x.rem_euclid(period), y.rem_euclid(period), period)).collect();
+            all.sort_by(f64::total_cmp);
+            let got: Vec<f64> = results.iter().map(|&id| wrapped_distance_brute(boxes[id], x.rem_euclid(period), y.rem_euclid(period), period)).collect();
+            for (g, e) in got.iter().zip(&all) {
+                assert!((g - e).abs() < 1e-9, "nearest at ({x}, {y}): {got:?} vs {:?}", &all[..10]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_periodic_seam_returns_each_item_once() {
+        let mut tree = HilbertRTree::new();
+        tree.add(98.0, 10.0, 102.0, 12.0); // Crosses the right edge
+        tree.add(50.0, 50.0, 51.0, 51.0);
+        tree.build();
+        tree.set_period(100.0, 100.0);
+
+        let mut results = Vec::new();
+        tree.query_intersecting_wrapped(-5.0, 0.0, 105.0, 20.0, &mut results);
+        assert_eq!(results, vec![0]);
+        tree.query_circle_wrapped(1.0, 11.0, 0.5, &mut results);
+        assert_eq!(results, vec![0]);
+
+        // Cylinder: only X wraps
+        tree.set_period(100.0, f64::INFINITY);
+        tree.query_circle_wrapped(1.0, 111.0, 0.5, &mut results);
+        assert!(results.is_empty());
+        tree.query_nearest_k_wrapped(50.5, 150.5, 1, &mut results);
+        assert_eq!(results, vec![1]);
+
+        tree.clear_period();
+        tree.query_circle_wrapped(1.0, 11.0, 0.5, &mut results);
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_periodic_plain_queries_stay_consistent() {
+        let boxes = random_boxes(64, 800, 100.0, 6.0);
+        let mut tree = HilbertRTree::new();
+        for (id, &(min_x, min_y, max_x, max_y)) in boxes.iter().enumerate() {
+            tree.add_weighted(min_x, min_y, max_x, max_y, id as f64);
+        }
+        tree.build();
+        tree.set_period(100.0, 100.0);
+
+        let mut results = Vec::new();
+        let mut wrapped = Vec::new();
+        for &(x, y, w, h) in &[(95.0, 95.0, 10.0, 10.0), (-5.0, 40.0, 8.0, 3.0), (30.0, 30.0, 20.0, 20.0)] {
+            tree.query_intersecting(x, y, x + w, y + h, &mut results);
+            assert_eq!(tree.count_intersecting(x, y, x + w, y + h), results.len());
+            assert_eq!(tree.any_intersecting(x, y, x + w, y + h), !results.is_empty());
+            let aggregate = tree.aggregate_in_rect(x, y, x + w, y + h).unwrap();
+            assert_eq!(aggregate.count, results.len());
+            assert_eq!(aggregate.sum, results.iter().map(|&id| id as f64).sum::<f64>());
+
+            // Only the explicit wrapped query looks across the seam
+            tree.query_intersecting_wrapped(x, y, x + w, y + h, &mut wrapped);
+            assert!(results.iter().all(|id| wrapped.contains(id)));
+        }
+        tree.query_intersecting_wrapped(95.0, 95.0, 105.0, 105.0, &mut wrapped);
+        assert!(wrapped.len() > tree.count_intersecting(95.0, 95.0, 105.0, 105.0));
+    }
+
+    #[test]
+    fn test_periodic_survives_save_load() {
+        let mut tree = build_grid_tree(5);
+        tree.set_period(64.0, 32.0);
+        let path = std::env::temp_dir().join("aabb_period_save_load.bin");
+        tree.save(&path).unwrap();
+        let loaded = HilbertRTree::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(loaded.period(), Some((64.0, 32.0)));
+
+        // The period section is the last 16 bytes; zero, negative and NaN widths are rejected
+        let bytes = tree.to_bytes();
+        let width_at = bytes.len() - 16;
+        for width in [0.0, -64.0, f64::NAN] {
+            let mut corrupt = bytes.clone();
+            corrupt[width_at..width_at + 8].copy_from_slice(&f64::to_le_bytes(width));
+            let err = crate::HilbertRTreeView::from_bytes(&corrupt).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "period must be positive")]
+    fn test_periodic_rejects_non_positive_period() {
+        HilbertRTree::new().set_period(0.0, 10.0);
+    }
+
+    // ============================================================================
+    // GEOGRAPHIC TESTS
+    // ============================================================================
+
+    fn random_lon_lat(seed: u64, n: usize) -> Vec<(f64, f64)> {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        (0..n).map(|_| (rng.random_range(-180.0..180.0), rng.random_range(-89.0..89.0))).collect()
+    }
+
+    fn geo_tree(points: &[(f64, f64)]) -> HilbertRTree {
+        let mut tree = HilbertRTree::with_capacity(points.len());
+        for &(lon, lat) in points {
+            tree.add_point(lon, lat);
+        }
+        tree.build();
+        tree
+    }
+
+    #[test]
+    fn test_geo_nearest_and_circle_match_brute_force() {
+        use crate::geo::haversine_distance;
+
+        let points = random_lon_lat(55, 3000);
+        let tree = geo_tree(&points);
+        let mut results = Vec::new();
+
+        for &(lon, lat) in &[(179.9, 0.0), (-179.9, 45.0), (0.0, 88.5), (30.0, -89.0), (-73.9, 40.7)] {
+            let distances: Vec<f64> = points.iter().map(|&(x, y)| haversine_distance(lon, lat, x, y)).collect();
+            let mut expected: Vec<f64> = distances.clone();
+            expected.sort_by(f64::total_cmp);
+
+            tree.query_nearest_k_geo(lon, lat, 15, &mut results);
+            assert_eq!(results.len(), 15);
+            for (&id, e) in results.iter().zip(&expected) {
+                assert!((distances[id] - e).abs() < 1e-6, "nearest to ({lon}, {lat})");
+            }
+
+            let radius = 900.0;
+            tree.query_circle_geo(lon, lat, radius, &mut results);
+            let in_radius: Vec<usize> = (0..points.len()).filter(|&id| distances[id] <= radius).collect();
+            assert_eq!(sorted(results.clone()), in_radius, "circle at ({lon}, {lat})");
+        }
+    }
+
+    #[test]
+    fn test_geo_intersecting_wraps_antimeridian() {
+        let points = random_lon_lat(56, 2000);
+        let tree = geo_tree(&points);
+        let mut results = Vec::new();
+
+        tree.query_intersecting_geo(170.0, -30.0, -160.0, 30.0, &mut results);
+        let expected: Vec<usize> = (0..points.len())
+            .filter(|&id| {
+                let (lon, lat) = points[id];
+                (lon >= 170.0 || lon <= -160.0) && (-30.0..=30.0).contains(&lat)
+            })
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(sorted(results.clone()), expected);
+
+        // A non-wrapping query is the same as query_intersecting
+        let mut flat = Vec::new();
+        tree.query_intersecting_geo(-10.0, 20.0, 40.0, 60.0, &mut results);
+        tree.query_intersecting(-10.0, 20.0, 40.0, 60.0, &mut flat);
+        assert_eq!(sorted(results.clone()), sorted(flat));
+    }
+
+    #[test]
+    fn test_geo_box_items() {
+        let mut tree = HilbertRTree::new();
+        tree.add(-10.0, 35.0, 30.0, 60.0); // Europe-ish box containing the query point
+        tree.add(100.0, -10.0, 140.0, 10.0);
+        tree.add(-80.0, 60.0, -20.0, 84.0); // Greenland-ish box, reached over high latitudes
+        tree.build();
+
+        let mut results = Vec::new();
+        tree.query_nearest_k_geo(10.0, 50.0, 3, &mut results);
+        assert_eq!(results, vec![0, 2, 1]);
+        tree.query_circle_geo(10.0, 50.0, 0.0, &mut results);
+        assert_eq!(results, vec![0]);
+        tree.query_circle_geo(10.0, 50.0, -1.0, &mut results);
+        assert!(results.is_empty());
+    }
+
+    // ============================================================================
+    // FLATBUSH INTEROP TESTS
+    // ============================================================================
+
+    /// Flatbush v3 buffer for boxes (0,0,1,1), (2,2,3,3), (5,5,6,6) with the default node
+    /// size 16 and Float64Array, written out from the format description
+    fn flatbush_three_boxes_f64() -> Vec<u8> {
+        let mut bytes = vec![0xfb, 0x38, 16, 0, 3, 0, 0, 0];
+        let coords = [0.0, 0.0, 1.0, 1.0, 2.0, 2.0, 3.0, 3.0, 5.0, 5.0, 6.0, 6.0, 0.0, 0.0, 6.0, 6.0_f64];
+        for value in coords {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for index in [0_u16, 1, 2, 0] {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_flatbush_export_matches_fixture() {
+        let mut tree = HilbertRTree::new();
+        tree.add(0.0, 0.0, 1.0, 1.0);
+        tree.add(2.0, 2.0, 3.0, 3.0);
+        tree.add(5.0, 5.0, 6.0, 6.0);
+        tree.build();
+        assert_eq!(tree.to_flatbush_bytes(), flatbush_three_boxes_f64());
+
+        // Float32Array: 8 + 4 nodes * 16 + 4 * 2
+        let bytes = tree.to_flatbush_bytes_as(crate::FlatbushArrayType::Float32);
+        assert_eq!(bytes.len(), 80);
+        assert_eq!(bytes[1], 0x37);
+        assert_eq!(&bytes[8..12], &0.0_f32.to_le_bytes());
+        assert_eq!(&bytes[68..72], &6.0_f32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_flatbush_import_fixtures() {
+        let tree = HilbertRTree::from_flatbush_bytes(&flatbush_three_boxes_f64()).unwrap();
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.get(2), Some((5.0, 5.0, 6.0, 6.0)));
+        let mut results = Vec::new();
+        tree.query_intersecting(0.5, 0.5, 2.5, 2.5, &mut results);
+        assert_eq!(sorted(results.clone()), vec![0, 1]);
+        tree.query_nearest_k(6.5, 6.5, 1, &mut results);
+        assert_eq!(results, vec![2]);
+
+        // Int16Array, two items, negative coordinates
+        let mut bytes = vec![0xfb, 0x33, 16, 0, 2, 0, 0, 0];
+        for value in [-5_i16, -5, -1, -1, 10, 10, 20, 20, -5, -5, 20, 20] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for index in [1_u16, 0, 0] {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+        let tree = HilbertRTree::from_flatbush_bytes(&bytes).unwrap();
+        assert_eq!(tree.get(1), Some((-5.0, -5.0, -1.0, -1.0)));
+        tree.query_point(15.0, 15.0, &mut results);
+        assert_eq!(results, vec![0]);
+    }
+
+    #[test]
+    fn test_flatbush_round_trip_large_tree() {
+        // More than 16384 nodes switches the index array to Uint32Array
+        let boxes = random_boxes(57, 20_000, 1000.0, 5.0);
+        let tree = tree_from(&boxes);
+        let bytes = tree.to_flatbush_bytes();
+        let num_nodes = 20_000 + 1250 + 79 + 5 + 1;
+        assert_eq!(bytes.len(), 8 + num_nodes * 32 + num_nodes * 4);
+
+        let copy = HilbertRTree::from_flatbush_bytes(&bytes).unwrap();
+        assert_eq!(copy.to_flatbush_bytes(), bytes);
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        for &(x, y) in &[(100.0, 200.0), (500.0, 500.0), (999.0, 3.0)] {
+            tree.query_intersecting(x, y, x + 40.0, y + 40.0, &mut a);
+            copy.query_intersecting(x, y, x + 40.0, y + 40.0, &mut b);
+            assert_eq!(sorted(a.clone()), sorted(b.clone()));
+            tree.query_nearest_k(x, y, 7, &mut a);
+            copy.query_nearest_k(x, y, 7, &mut b);
+            assert_eq!(a, b);
+        }
+
+        let small = tree_from(&boxes[..500]);
+        let small_bytes = small.to_flatbush_bytes_as(crate::FlatbushArrayType::Int32);
+        let copy = HilbertRTree::from_flatbush_bytes(&small_bytes).unwrap();
+        copy.query_intersecting(0.0, 0.0, 1000.0, 1000.0, &mut b);
+        assert_eq!(b.len(), 500);
+    }
+
+    #[test]
+    fn test_flatbush_import_rejects_invalid_data() {
+        let good = flatbush_three_boxes_f64();
+        let mut bad_magic = good.clone();
+        bad_magic[0] = 0xfa;
+        let mut bad_version = good.clone();
+        bad_version[1] = 0x28;
+        let mut bad_type = good.clone();
+        bad_type[1] = 0x39;
+        let mut bad_child = good.clone();
+        let last = bad_child.len() - 2;
+        bad_child[last] = 12; // root pointing at itself instead of the leaf level
+        let mut bad_leaf = good.clone();
+        bad_leaf[good.len() - 8] = 3;
+
+        for bytes in [&bad_magic[..], &bad_version, &bad_type, &bad_child, &bad_leaf, &good[..good.len() - 1], &good[..4]] {
+            let err = HilbertRTree::from_flatbush_bytes(bytes).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
+    }
+
+    // ============================================================================
+    // ZERO-COPY VIEW TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_view_queries_match_owned_tree() {
+        let boxes = random_boxes(61, 3000, 1000.0, 8.0);
+        let tree = tree_from(&boxes);
+        let bytes = tree.to_bytes();
+        let view = crate::HilbertRTreeView::from_bytes(&bytes).unwrap();
+        assert_eq!(view.len(), tree.len());
+        assert_eq!(view.get(1234), tree.get(1234));
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        for &(x, y) in &[(0.0, 0.0), (250.0, 700.0), (990.0, 10.0)] {
+            tree.query_intersecting(x, y, x + 60.0, y + 60.0, &mut a);
+            view.query_intersecting(x, y, x + 60.0, y + 60.0, &mut b);
+            assert_eq!(sorted(a.clone()), sorted(b.clone()));
+            tree.query_nearest_k(x, y, 9, &mut a);
+            view.query_nearest_k(x, y, 9, &mut b);
+            assert_eq!(a, b);
+            tree.query_circle(x, y, 40.0, &mut a);
+            view.query_circle(x, y, 40.0, &mut b);
+            assert_eq!(sorted(a.clone()), sorted(b.clone()));
+        }
+        assert_eq!(view.count_intersecting(100.0, 100.0, 400.0, 400.0), tree.count_intersecting(100.0, 100.0, 400.0, 400.0));
+    }
+
+    #[test]
+    fn test_view_to_bytes_matches_save() {
+        let boxes = random_boxes(62, 400, 100.0, 5.0);
+        let mut tree = weighted_tree(&boxes);
+        tree.set_id_lookup(true);
+        tree.set_period(100.0, f64::INFINITY);
+        let path = std::env::temp_dir().join("aabb_view_to_bytes.bin");
+        tree.save(&path).unwrap();
+        let saved = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(tree.to_bytes(), saved);
+
+        let view = crate::HilbertRTreeView::from_bytes(&saved).unwrap();
+        assert_eq!(view.period(), Some((100.0, f64::INFINITY)));
+        assert_eq!(view.get(123), tree.get(123));
+        assert_eq!(
+            view.aggregate_in_rect(10.0, 20.0, 60.0, 70.0),
+            tree.aggregate_in_rect(10.0, 20.0, 60.0, 70.0)
+        );
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        tree.query_intersecting_wrapped(95.0, 10.0, 105.0, 30.0, &mut a);
+        view.query_intersecting_wrapped(95.0, 10.0, 105.0, 30.0, &mut b);
+        assert_eq!(sorted(a), sorted(b));
+        assert_eq!(view.to_bytes(), saved);
+    }
+
+    #[test]
+    fn test_view_rejects_invalid_data() {
+        let mut tree = HilbertRTree::new();
+        tree.add(0.0, 0.0, 1.0, 1.0);
+        tree.add(2.0, 2.0, 3.0, 3.0);
+        tree.add(5.0, 5.0, 6.0, 6.0);
+        tree.build();
+        let good = tree.to_bytes();
+        assert!(crate::HilbertRTreeView::from_bytes(&good).is_ok());
+
+        // Header: magic, version, node_size, num_items, total_nodes, 2 level bounds,
+        // 4 bounds, data_len, then the data with its own 8-byte header, 4 boxes and 4 indices
+        let data_start = 2 + 4 * 4 + 2 * 4 + 4 * 8 + 4;
+        let root_index = data_start + 8 + 4 * 32 + 3 * 4;
+        let mut bad_magic = good.clone();
+        bad_magic[0] = 0xfa;
+        let mut bad_version = good.clone();
+        bad_version[1] = 0x03;
+        let mut bad_node_size = good.clone();
+        bad_node_size[2] = 1;
+        let mut bad_levels = good.clone();
+        bad_levels[18] = 2;
+        let mut bad_child = good.clone();
+        bad_child[root_index] = 12;
+        let mut bad_leaf = good.clone();
+        bad_leaf[root_index - 4] = 3;
+        let mut bad_tag = good.clone();
+        bad_tag.push(0x7f);
+
+        for bytes in [&bad_magic[..], &bad_version, &bad_node_size, &bad_levels, &bad_tag, &good[..good.len() - 1], &good[..5]] {
+            let err = crate::HilbertRTreeView::from_bytes(bytes).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
+
+        // Node indices are only checked by validate(), which load() always runs
+        let path = std::env::temp_dir().join("aabb_view_bad_index.bin");
+        assert!(crate::HilbertRTreeView::from_bytes(&good).unwrap().validate().is_ok());
+        for bytes in [&bad_child, &bad_leaf] {
+            let view = crate::HilbertRTreeView::from_bytes(bytes).unwrap();
+            assert_eq!(view.validate().unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+            std::fs::write(&path, bytes).unwrap();
+            assert_eq!(HilbertRTree::load(&path).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        }
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_save_version_marks_optional_sections() {
+        let plain = build_grid_tree(4).to_bytes();
+        assert_eq!(plain[1], 0x01);
+
+        let mut tree = build_grid_tree(4);
+        tree.set_period(40.0, 40.0);
+        let with_period = tree.to_bytes();
+        assert_eq!(with_period[1], 0x02);
+        assert!(crate::HilbertRTreeView::from_bytes(&with_period).is_ok());
+
+        // A version-1 file carrying sections is rejected rather than read without them
+        let mut mislabeled = with_period.clone();
+        mislabeled[1] = 0x01;
+        let err = crate::HilbertRTreeView::from_bytes(&mislabeled).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_open_mmap_matches_owned_tree() {
+        let boxes = random_boxes(63, 2000, 500.0, 6.0);
+        let mut tree = weighted_tree(&boxes);
+        tree.set_id_lookup(true);
+        let path = std::env::temp_dir().join("aabb_open_mmap.bin");
+        tree.save(&path).unwrap();
+        let mapped = HilbertRTree::open_mmap(&path).unwrap();
+        assert!(mapped.validate().is_ok());
+
+        assert_eq!(mapped.len(), tree.len());
+        assert_eq!(mapped.get(777), tree.get(777));
+        assert_eq!(mapped.to_bytes(), tree.to_bytes());
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        tree.query_intersecting(100.0, 100.0, 180.0, 160.0, &mut a);
+        mapped.query_intersecting(100.0, 100.0, 180.0, 160.0, &mut b);
+        assert_eq!(sorted(a.clone()), sorted(b.clone()));
+        tree.query_nearest_k(250.0, 250.0, 12, &mut a);
+        mapped.query_nearest_k(250.0, 250.0, 12, &mut b);
+        assert_eq!(a, b);
+        assert_eq!(
+            mapped.aggregate_in_rect(0.0, 0.0, 200.0, 200.0),
+            tree.aggregate_in_rect(0.0, 0.0, 200.0, 200.0)
+        );
+        drop(mapped);
+
+        std::fs::write(&path, b"not a tree").unwrap();
+        let err = HilbertRTree::open_mmap(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -638,4 +638,293 @@ mod tests {
         cloned.query_intersecting(0, 0, 10, 10, &mut results);
         assert_eq!(results.len(), 2);
     }
+
+    // ============================================================================
+    // SELF-JOIN TESTS
+    // ============================================================================
+
+    fn grid_boxes() -> Vec<(i32, i32, i32, i32)> {
+        let mut boxes = Vec::new();
+        for i in 0..45 {
+            for j in 0..45 {
+                let size = 1 + (i * 7 + j * 3) % 6;
+                boxes.push((i * 4, j * 4, i * 4 + size, j * 4 + size));
+            }
+        }
+        boxes
+    }
+
+    #[test]
+    fn test_all_intersecting_pairs_matches_brute_force() {
+        let boxes = grid_boxes();
+        let mut tree = HilbertRTreeI32::with_capacity(boxes.len());
+        for b in &boxes {
+            tree.add(b.0, b.1, b.2, b.3);
+        }
+        tree.build();
+
+        let mut pairs = Vec::new();
+        tree.all_intersecting_pairs(&mut pairs);
+        pairs.sort();
+
+        let mut expected = Vec::new();
+        for i in 0..boxes.len() {
+            for j in i + 1..boxes.len() {
+                let (a, b) = (boxes[i], boxes[j]);
+                if a.2 >= b.0 && a.3 >= b.1 && a.0 <= b.2 && a.1 <= b.3 {
+                    expected.push((i, j));
+                }
+            }
+        }
+        assert!(!expected.is_empty());
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn test_all_intersecting_pairs_small_tree() {
+        let mut tree = HilbertRTreeI32::new();
+        tree.add(0, 0, 2, 2);
+        tree.add(2, 2, 3, 3);  // Touches box 0 at a corner
+        tree.add(5, 5, 6, 6);
+        tree.build();
+
+        let mut pairs = Vec::new();
+        tree.all_intersecting_pairs(&mut pairs);
+        assert_eq!(pairs, vec![(0, 1)]);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_all_intersecting_pairs_parallel_matches_sequential() {
+        let boxes = grid_boxes();
+        let mut tree = HilbertRTreeI32::with_capacity(boxes.len());
+        for b in &boxes {
+            tree.add(b.0, b.1, b.2, b.3);
+        }
+        tree.build();
+
+        let mut sequential = Vec::new();
+        let mut parallel = Vec::new();
+        tree.all_intersecting_pairs(&mut sequential);
+        tree.all_intersecting_pairs_parallel(&mut parallel);
+        sequential.sort();
+        parallel.sort();
+        assert_eq!(parallel, sequential);
+    }
+
+    // ============================================================================
+    // COUNT / EXISTENCE TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_count_intersecting_matches_query() {
+        let boxes = grid_boxes();
+        let mut tree = HilbertRTreeI32::with_capacity(boxes.len());
+        for b in &boxes {
+            tree.add(b.0, b.1, b.2, b.3);
+        }
+        tree.build();
+
+        let mut results = Vec::new();
+        for &(min_x, min_y, max_x, max_y) in &[(0, 0, 200, 200), (10, 20, 90, 60), (50, 50, 50, 50), (-20, -20, -1, -1)] {
+            tree.query_intersecting(min_x, min_y, max_x, max_y, &mut results);
+            assert_eq!(tree.count_intersecting(min_x, min_y, max_x, max_y), results.len());
+            assert_eq!(tree.any_intersecting(min_x, min_y, max_x, max_y), !results.is_empty());
+        }
+        assert_eq!(tree.count_intersecting(i32::MIN, i32::MIN, i32::MAX, i32::MAX), boxes.len());
+    }
+
+    #[test]
+    fn test_any_containing_point_matches_query() {
+        let boxes = grid_boxes();
+        let mut tree = HilbertRTreeI32::with_capacity(boxes.len());
+        for b in &boxes {
+            tree.add(b.0, b.1, b.2, b.3);
+        }
+        tree.build();
+
+        let mut results = Vec::new();
+        for &(x, y) in &[(0, 0), (3, 3), (1, 7), (500, 500), (-1, 4)] {
+            tree.query_point(x, y, &mut results);
+            assert_eq!(tree.any_containing_point(x, y), !results.is_empty());
+        }
+
+        let empty = HilbertRTreeI32::new();
+        assert_eq!(empty.count_intersecting(0, 0, 10, 10), 0);
+        assert!(!empty.any_intersecting(0, 0, 10, 10));
+        assert!(!empty.any_containing_point(0, 0));
+    }
+
+    // ============================================================================
+    // RANGE OUTPUT TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_query_intersecting_ranges_matches_query() {
+        let boxes = grid_boxes();
+        let mut tree = HilbertRTreeI32::with_capacity(boxes.len());
+        for b in &boxes {
+            tree.add(b.0, b.1, b.2, b.3);
+        }
+        tree.build();
+
+        let mut results = Vec::new();
+        let mut ranges = Vec::new();
+        let mut ids = Vec::new();
+        for &(min_x, min_y, max_x, max_y) in &[(0, 0, 200, 200), (10, 20, 120, 90), (50, 50, 50, 50), (-20, -20, -1, -1)] {
+            tree.query_intersecting(min_x, min_y, max_x, max_y, &mut results);
+            tree.query_intersecting_ranges(min_x, min_y, max_x, max_y, &mut ranges);
+            for pair in ranges.windows(2) {
+                assert!(pair[0].end < pair[1].start);
+            }
+            tree.leaf_ids(&ranges, &mut ids);
+            results.sort();
+            ids.sort();
+            assert_eq!(ids, results);
+        }
+    }
+
+    // ============================================================================
+    // BOUNDARY TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_boundary_queries_on_tiled_cells() {
+        use crate::Boundary;
+
+        let mut tree = HilbertRTreeI32::with_capacity(400);
+        for i in 0..20 {
+            for j in 0..20 {
+                tree.add(i * 2, j * 2, i * 2 + 2, j * 2 + 2);
+            }
+        }
+        tree.build();
+
+        let mut plain = Vec::new();
+        let mut results = Vec::new();
+        tree.query_intersecting(6, 6, 10, 14, &mut plain);
+        tree.query_intersecting_with_boundary(6, 6, 10, 14, Boundary::Closed, &mut results);
+        plain.sort_unstable();
+        results.sort_unstable();
+        assert_eq!(results, plain);
+
+        // Cell (3, 3) covers [6, 8]^2: touching neighbors drop out without closed edges
+        tree.query_intersecting_with_boundary(6, 6, 8, 8, Boundary::Closed, &mut results);
+        assert_eq!(results.len(), 9);
+        tree.query_intersecting_with_boundary(6, 6, 8, 8, Boundary::HalfOpen, &mut results);
+        assert_eq!(results, vec![3 * 20 + 3]);
+
+        tree.query_point_with_boundary(8, 8, Boundary::Closed, &mut results);
+        assert_eq!(results.len(), 4);
+        tree.query_point_with_boundary(8, 8, Boundary::Open, &mut results);
+        assert!(results.is_empty());
+        tree.query_point_with_boundary(8, 8, Boundary::HalfOpen, &mut results);
+        assert_eq!(results, vec![4 * 20 + 4]);
+
+        tree.query_contain_with_boundary(6, 6, 8, 8, Boundary::Open, &mut results);
+        assert!(results.is_empty());
+        tree.query_contain_with_boundary(6, 6, 8, 8, Boundary::HalfOpen, &mut results);
+        assert_eq!(results, vec![3 * 20 + 3]);
+
+        tree.query_contained_within_with_boundary(4, 4, 10, 10, Boundary::Closed, &mut results);
+        assert_eq!(results.len(), 9);
+        tree.query_contained_within_with_boundary(4, 4, 10, 10, Boundary::Open, &mut results);
+        assert_eq!(results, vec![3 * 20 + 3]);
+    }
+
+    #[test]
+    fn test_query_intersecting_id_after_hilbert_sort() {
+        let boxes = grid_boxes();
+        let mut tree = HilbertRTreeI32::with_capacity(boxes.len());
+        for b in &boxes {
+            tree.add(b.0, b.1, b.2, b.3);
+        }
+        tree.build();
+
+        let mut results = Vec::new();
+        for id in [0, 100, 1000, boxes.len() - 1] {
+            tree.query_intersecting_id(id, &mut results).unwrap();
+            results.sort_unstable();
+            let a = boxes[id];
+            let expected: Vec<usize> = (0..boxes.len())
+                .filter(|&j| {
+                    let b = boxes[j];
+                    j != id && a.2 >= b.0 && a.3 >= b.1 && a.0 <= b.2 && a.1 <= b.3
+                })
+                .collect();
+            assert_eq!(results, expected, "item {id}");
+        }
+    }
+
+    // ============================================================================
+    // PERMUTATION / ITER TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_sorted_order_matches_iter() {
+        let boxes = grid_boxes();
+        let mut tree = HilbertRTreeI32::with_capacity(boxes.len());
+        for b in &boxes {
+            tree.add(b.0, b.1, b.2, b.3);
+        }
+        tree.build();
+
+        let order = tree.permutation();
+        let mut seen = order.clone();
+        seen.sort_unstable();
+        assert_eq!(seen, (0..boxes.len()).collect::<Vec<_>>());
+        for ((id, bbox), expected_id) in tree.iter().zip(order) {
+            assert_eq!(id, expected_id);
+            assert_eq!(bbox, boxes[id]);
+        }
+        assert_eq!(tree.apply_permutation(&boxes), tree.iter().map(|(_, bbox)| bbox).collect::<Vec<_>>());
+    }
+
+    // ============================================================================
+    // FLATBUSH INTEROP TESTS
+    // ============================================================================
+
+    #[test]
+    fn test_flatbush_round_trip() {
+        let boxes = grid_boxes();
+        let mut tree = HilbertRTreeI32::with_capacity(boxes.len());
+        for b in &boxes {
+            tree.add(b.0, b.1, b.2, b.3);
+        }
+        tree.build();
+
+        let bytes = tree.to_flatbush_bytes();
+        assert_eq!(&bytes[..2], &[0xfb, 0x35]);
+        let copy = HilbertRTreeI32::from_flatbush_bytes(&bytes).unwrap();
+        assert_eq!(copy.to_flatbush_bytes(), bytes);
+        assert_eq!(copy.iter().collect::<Vec<_>>(), tree.iter().collect::<Vec<_>>());
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        tree.query_intersecting(3, 3, 12, 9, &mut a);
+        copy.query_intersecting(3, 3, 12, 9, &mut b);
+        a.sort_unstable();
+        b.sort_unstable();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_flatbush_import_integer_types_only() {
+        // Uint8Array fixture with boxes (1, 2, 3, 4) and (5, 6, 7, 8)
+        let bytes = [
+            0xfb, 0x31, 16, 0, 2, 0, 0, 0,
+            1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 7, 8,
+            0, 0, 1, 0, 0, 0,
+        ];
+        let tree = HilbertRTreeI32::from_flatbush_bytes(&bytes).unwrap();
+        assert_eq!(tree.iter().find(|&(id, _)| id == 0), Some((0, (1, 2, 3, 4))));
+        let mut results = Vec::new();
+        tree.query_point(6, 7, &mut results);
+        assert_eq!(results, vec![1]);
+
+        let mut f64_tree = crate::HilbertRTree::new();
+        f64_tree.add(0.5, 0.5, 1.5, 1.5);
+        f64_tree.build();
+        let err = HilbertRTreeI32::from_flatbush_bytes(&f64_tree.to_flatbush_bytes()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
 }
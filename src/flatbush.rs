@@ -0,0 +1,270 @@
+//! Flatbush binary format interop
+//!
+//! [Flatbush](https://github.com/mourner/flatbush) (v3 format, used by flatbush 3.x and 4.x)
+//! stores a packed Hilbert R-tree in one `ArrayBuffer`:
+//!
+//! - Header: 8 bytes - magic `0xfb`, `(version << 4) | array type`, `nodeSize` (u16), `numItems` (u32)
+//! - Boxes: `numNodes * 4` coordinates of the array type (`minX, minY, maxX, maxY` per node)
+//! - Indices: `numNodes` values, `Uint16Array` when `numNodes < 16384`, otherwise `Uint32Array`
+//!
+//! All values are little-endian. Leaf indices are item ids; parent indices are the position
+//! of the first child multiplied by 4. The layout matches the trees in this crate node for
+//! node, so `HilbertRTree::to_flatbush_bytes()` produces a buffer that
+//! `Flatbush.from(buffer)` loads directly, and `HilbertRTree::from_flatbush_bytes()` accepts
+//! `index.data` from JavaScript without rebuilding.
+
+use std::io;
+
+/// First header byte of every Flatbush buffer
+const MAGIC: u8 = 0xfb;
+/// Flatbush format version stored in the high nibble of the second header byte
+const VERSION: u8 = 3;
+const HEADER_SIZE: usize = 8;
+/// Flatbush switches from 16-bit to 32-bit indices at this node count
+const MAX_NODES_U16_INDICES: usize = 16384;
+
+/// Coordinate array type of a Flatbush buffer (the `ArrayType` passed to `new Flatbush()`)
+///
+/// Variants are listed in Flatbush's type order, which is the value stored in the header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FlatbushArrayType {
+    /// `Int8Array`
+    Int8,
+    /// `Uint8Array`
+    Uint8,
+    /// `Uint8ClampedArray`
+    Uint8Clamped,
+    /// `Int16Array`
+    Int16,
+    /// `Uint16Array`
+    Uint16,
+    /// `Int32Array`
+    Int32,
+    /// `Uint32Array`
+    Uint32,
+    /// `Float32Array`
+    Float32,
+    /// `Float64Array` (Flatbush's default)
+    #[default]
+    Float64,
+}
+
+impl FlatbushArrayType {
+    /// All array types in header order
+    const ALL: [Self; 9] = [
+        Self::Int8,
+        Self::Uint8,
+        Self::Uint8Clamped,
+        Self::Int16,
+        Self::Uint16,
+        Self::Int32,
+        Self::Uint32,
+        Self::Float32,
+        Self::Float64,
+    ];
+
+    /// Size of one coordinate in bytes
+    pub fn byte_size(self) -> usize {
+        match self {
+            Self::Int8 | Self::Uint8 | Self::Uint8Clamped => 1,
+            Self::Int16 | Self::Uint16 => 2,
+            Self::Int32 | Self::Uint32 | Self::Float32 => 4,
+            Self::Float64 => 8,
+        }
+    }
+
+    /// Whether every value of this type is exactly representable as an `i32`
+    pub(crate) fn fits_i32(self) -> bool {
+        !matches!(self, Self::Uint32 | Self::Float32 | Self::Float64)
+    }
+
+    /// Type code stored in the low nibble of the second header byte
+    fn code(self) -> u8 {
+        match self {
+            Self::Int8 => 0,
+            Self::Uint8 => 1,
+            Self::Uint8Clamped => 2,
+            Self::Int16 => 3,
+            Self::Uint16 => 4,
+            Self::Int32 => 5,
+            Self::Uint32 => 6,
+            Self::Float32 => 7,
+            Self::Float64 => 8,
+        }
+    }
+
+    /// Decode one little-endian coordinate from the start of `bytes`
+    fn read(self, bytes: &[u8]) -> f64 {
+        let mut buf = [0_u8; 8];
+        buf[..self.byte_size()].copy_from_slice(&bytes[..self.byte_size()]);
+        match self {
+            Self::Int8 => f64::from(i8::from_le_bytes([buf[0]])),
+            Self::Uint8 | Self::Uint8Clamped => f64::from(buf[0]),
+            Self::Int16 => f64::from(i16::from_le_bytes([buf[0], buf[1]])),
+            Self::Uint16 => f64::from(u16::from_le_bytes([buf[0], buf[1]])),
+            Self::Int32 => f64::from(i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])),
+            Self::Uint32 => f64::from(u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])),
+            Self::Float32 => f64::from(f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])),
+            Self::Float64 => f64::from_le_bytes(buf),
+        }
+    }
+
+    /// Encode one coordinate, converting like a JavaScript typed array store
+    /// (nearest for floats, round-half-even for clamped bytes, truncation for integers).
+    /// Integer values outside the type's range saturate.
+    #[expect(clippy::cast_possible_truncation, reason = "converting to the buffer's coordinate type is the point")]
+    fn write(self, value: f64, out: &mut Vec<u8>) {
+        match self {
+            Self::Int8 => out.extend_from_slice(&(value as i8).to_le_bytes()),
+            Self::Uint8 => out.push(value as u8),
+            Self::Uint8Clamped => out.push(value.round_ties_even() as u8),
+            Self::Int16 => out.extend_from_slice(&(value as i16).to_le_bytes()),
+            Self::Uint16 => out.extend_from_slice(&(value as u16).to_le_bytes()),
+            Self::Int32 => out.extend_from_slice(&(value as i32).to_le_bytes()),
+            Self::Uint32 => out.extend_from_slice(&(value as u32).to_le_bytes()),
+            Self::Float32 => out.extend_from_slice(&(value as f32).to_le_bytes()),
+            Self::Float64 => out.extend_from_slice(&value.to_le_bytes()),
+        }
+    }
+}
+
+/// Header fields and section offsets of a Flatbush buffer
+#[derive(Debug)]
+pub(crate) struct FlatbushLayout {
+    pub(crate) array_type: FlatbushArrayType,
+    pub(crate) node_size: usize,
+    pub(crate) num_items: usize,
+    /// End position of each tree level, in nodes
+    pub(crate) level_bounds: Vec<usize>,
+    pub(crate) num_nodes: usize,
+}
+
+impl FlatbushLayout {
+    /// Layout of a tree with the given shape, computed the way Flatbush's constructor does
+    pub(crate) fn new(array_type: FlatbushArrayType, node_size: usize, num_items: usize) -> Self {
+        let mut level_bounds = vec![num_items];
+        let mut count = num_items;
+        let mut num_nodes = num_items;
+        loop {
+            count = count.div_ceil(node_size);
+            num_nodes += count;
+            level_bounds.push(num_nodes);
+            if count <= 1 {
+                break;
+            }
+        }
+        Self { array_type, node_size, num_items, level_bounds, num_nodes }
+    }
+
+    /// Reads and validates the header, the buffer length and every node index
+    pub(crate) fn parse(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < HEADER_SIZE || bytes[0] != MAGIC {
+            return Err(invalid("Invalid Flatbush data: magic number mismatch".to_string()));
+        }
+        if bytes[1] >> 4 != VERSION {
+            return Err(invalid(format!("Unsupported Flatbush version {} (expected {})", bytes[1] >> 4, VERSION)));
+        }
+        let Some(&array_type) = FlatbushArrayType::ALL.get(usize::from(bytes[1] & 0x0f)) else {
+            return Err(invalid(format!("Unrecognized Flatbush array type {}", bytes[1] & 0x0f)));
+        };
+        let node_size = usize::from(u16::from_le_bytes([bytes[2], bytes[3]]));
+        let num_items = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        if node_size < 2 || num_items == 0 {
+            return Err(invalid(format!("Invalid Flatbush data: node size {node_size}, {num_items} items")));
+        }
+
+        let layout = Self::new(array_type, node_size, num_items);
+        if bytes.len() != layout.byte_len() {
+            return Err(invalid(format!(
+                "Invalid Flatbush data: expected {} bytes for {} items, got {}",
+                layout.byte_len(),
+                num_items,
+                bytes.len()
+            )));
+        }
+        layout.validate_indices(bytes)?;
+        Ok(layout)
+    }
+
+    /// Total buffer size in bytes
+    pub(crate) fn byte_len(&self) -> usize {
+        self.indices_offset() + self.num_nodes * self.index_size()
+    }
+
+    /// Coordinates `[min_x, min_y, max_x, max_y]` of the node at `pos`
+    pub(crate) fn read_box(&self, bytes: &[u8], pos: usize) -> [f64; 4] {
+        let size = self.array_type.byte_size();
+        let start = HEADER_SIZE + pos * 4 * size;
+        std::array::from_fn(|i| self.array_type.read(&bytes[start + i * size..]))
+    }
+
+    /// Index of the node at `pos` (item id for leaves, first child * 4 for parents)
+    pub(crate) fn read_index(&self, bytes: &[u8], pos: usize) -> u32 {
+        let start = self.indices_offset() + pos * self.index_size();
+        if self.index_size() == 2 {
+            u32::from(u16::from_le_bytes([bytes[start], bytes[start + 1]]))
+        } else {
+            u32::from_le_bytes([bytes[start], bytes[start + 1], bytes[start + 2], bytes[start + 3]])
+        }
+    }
+
+    /// Serializes a tree given its node boxes and indices in position order
+    pub(crate) fn encode<B, I>(&self, boxes: B, indices: I) -> Vec<u8>
+    where
+        B: Iterator<Item = [f64; 4]>,
+        I: Iterator<Item = u32>,
+    {
+        let mut out = Vec::with_capacity(self.byte_len());
+        out.push(MAGIC);
+        out.push((VERSION << 4) | self.array_type.code());
+        out.extend_from_slice(&u16::try_from(self.node_size).unwrap_or(u16::MAX).to_le_bytes());
+        out.extend_from_slice(&u32::try_from(self.num_items).unwrap_or(u32::MAX).to_le_bytes());
+        for node_box in boxes {
+            for value in node_box {
+                self.array_type.write(value, &mut out);
+            }
+        }
+        let wide = self.index_size() == 4;
+        for index in indices {
+            if wide {
+                out.extend_from_slice(&index.to_le_bytes());
+            } else {
+                out.extend_from_slice(&u16::try_from(index).unwrap_or(u16::MAX).to_le_bytes());
+            }
+        }
+        out
+    }
+
+    fn indices_offset(&self) -> usize {
+        HEADER_SIZE + self.num_nodes * 4 * self.array_type.byte_size()
+    }
+
+    fn index_size(&self) -> usize {
+        if self.num_nodes < MAX_NODES_U16_INDICES { 2 } else { 4 }
+    }
+
+    /// Leaves must name existing items and parents must point into the level below,
+    /// so a corrupt buffer cannot send a traversal out of bounds
+    fn validate_indices(&self, bytes: &[u8]) -> io::Result<()> {
+        for pos in 0..self.num_items {
+            if self.read_index(bytes, pos) as usize >= self.num_items {
+                return Err(invalid(format!("Invalid Flatbush data: leaf {pos} has an out-of-range item id")));
+            }
+        }
+        for level in 1..self.level_bounds.len() {
+            let children = if level == 1 { 0 } else { self.level_bounds[level - 2] }..self.level_bounds[level - 1];
+            for pos in self.level_bounds[level - 1]..self.level_bounds[level] {
+                let index = self.read_index(bytes, pos) as usize;
+                if !index.is_multiple_of(4) || !children.contains(&(index / 4)) {
+                    return Err(invalid(format!("Invalid Flatbush data: node {pos} has an out-of-range child")));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,123 @@
+//! Geographic helpers for trees of longitude/latitude boxes
+//!
+//! Items are stored as `(min_lon, min_lat, max_lon, max_lat)` in degrees, with longitudes in
+//! `[-180, 180]` and `min_lon <= max_lon` (split items that cross the antimeridian in two).
+//! The `*_geo` queries on `HilbertRTree` then treat the data as lying on a sphere:
+//!
+//! - `query_intersecting_geo()` - Query boxes with `min_lon > max_lon` wrap across ±180°
+//! - `query_nearest_k_geo()` - Nearest items by great-circle (haversine) distance
+//! - `query_circle_geo()` - Items within a great-circle distance
+//!
+//! Distances are in kilometers on a sphere of radius [`EARTH_RADIUS_KM`]. Node boxes are
+//! pruned with the exact minimum great-circle distance from the query point to the box,
+//! the same lower bound geokdbush uses on top of kdbush.
+//!
+//! [`EARTH_RADIUS_KM`]: crate::geo::EARTH_RADIUS_KM
+
+/// Mean Earth radius in kilometers used by all geographic distances
+pub const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+/// Great-circle distance in kilometers between two points given in degrees.
+///
+/// # Arguments
+/// * `lon1` - Longitude of the first point
+/// * `lat1` - Latitude of the first point
+/// * `lon2` - Longitude of the second point
+/// * `lat2` - Latitude of the second point
+///
+/// # Example
+/// ```
+/// use aabb::geo::haversine_distance;
+/// // One degree of latitude is about 111 km
+/// let d = haversine_distance(10.0, 45.0, 10.0, 46.0);
+/// assert!((d - 111.2).abs() < 0.1);
+/// // Across the antimeridian
+/// assert!(haversine_distance(179.5, 0.0, -179.5, 0.0) < 112.0);
+/// ```
+pub fn haversine_distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    let h = hav_dist_partial(hav((lon1 - lon2).to_radians()), lat1.to_radians().cos(), lat1, lat2);
+    hav_to_km(h)
+}
+
+/// Haversine of a great-circle distance in kilometers, the monotone key used for pruning
+#[inline]
+pub(crate) fn km_to_hav(distance: f64) -> f64 {
+    if distance >= EARTH_RADIUS_KM * std::f64::consts::PI {
+        return 1.0;
+    }
+    hav(distance.max(0.0) / EARTH_RADIUS_KM)
+}
+
+/// Great-circle distance in kilometers from its haversine
+#[inline]
+pub(crate) fn hav_to_km(h: f64) -> f64 {
+    2.0 * EARTH_RADIUS_KM * h.clamp(0.0, 1.0).sqrt().asin()
+}
+
+/// Haversine of the minimum great-circle distance from a point to a lon/lat box
+///
+/// `cos_lat` is the cosine of the point latitude. Zero when the point is inside the box.
+#[inline]
+pub(crate) fn box_hav_dist(lon: f64, lat: f64, cos_lat: f64, min_lon: f64, min_lat: f64, max_lon: f64, max_lat: f64) -> f64 {
+    let lon = normalize_lon(lon);
+    if lon >= min_lon && lon <= max_lon {
+        if lat < min_lat {
+            return hav((lat - min_lat).to_radians());
+        }
+        if lat > max_lat {
+            return hav((lat - max_lat).to_radians());
+        }
+        return 0.0;
+    }
+
+    // West or east of the box: the closest point lies on the nearer meridian edge, either at
+    // the latitude where the great circle to that meridian is perpendicular or at a corner
+    let hav_dlon = hav((lon - min_lon).to_radians()).min(hav((lon - max_lon).to_radians()));
+    let extremum_lat = vertex_lat(lat, hav_dlon);
+    if extremum_lat > min_lat && extremum_lat < max_lat {
+        return hav_dist_partial(hav_dlon, cos_lat, lat, extremum_lat);
+    }
+    hav_dist_partial(hav_dlon, cos_lat, lat, min_lat).min(hav_dist_partial(hav_dlon, cos_lat, lat, max_lat))
+}
+
+/// Whether longitude range `[a_min, a_max]` (wrapping across ±180° when `a_min > a_max`)
+/// overlaps the plain range `[b_min, b_max]`
+#[inline]
+pub(crate) fn lon_ranges_overlap(a_min: f64, a_max: f64, b_min: f64, b_max: f64) -> bool {
+    if a_min <= a_max {
+        a_min <= b_max && b_min <= a_max
+    } else {
+        b_max >= a_min || b_min <= a_max
+    }
+}
+
+/// Longitude mapped into `[-180, 180]`
+#[inline]
+fn normalize_lon(lon: f64) -> f64 {
+    if (-180.0..=180.0).contains(&lon) {
+        lon
+    } else {
+        (lon + 180.0).rem_euclid(360.0) - 180.0
+    }
+}
+
+#[inline]
+fn hav(theta: f64) -> f64 {
+    let s = (theta / 2.0).sin();
+    s * s
+}
+
+#[inline]
+fn hav_dist_partial(hav_dlon: f64, cos_lat1: f64, lat1: f64, lat2: f64) -> f64 {
+    cos_lat1 * lat2.to_radians().cos() * hav_dlon + hav((lat1 - lat2).to_radians())
+}
+
+/// Latitude where the great circle from `lat` meets a meridian `dlon` away at a right angle
+#[inline]
+fn vertex_lat(lat: f64, hav_dlon: f64) -> f64 {
+    let cos_dlon = 1.0 - 2.0 * hav_dlon;
+    if cos_dlon <= 0.0 {
+        return if lat > 0.0 { 90.0 } else { -90.0 };
+    }
+    (lat.to_radians().tan() / cos_dlon).atan().to_degrees()
+}
@@ -0,0 +1,371 @@
+//! Hilbert curve utilities
+//!
+//! The trees sort items by the Hilbert index of their box centers on a 65536 x 65536 grid
+//! (16 bits per axis, 32-bit index). This module exposes the same transforms so other data
+//! can be sorted consistently with a tree, plus a 32-bit-per-axis variant with 64-bit indices
+//! for finer grids.
+//!
+//! - [`xy_to_index`] / [`index_to_xy`] - 16-bit coordinates, 32-bit index (the order used by the trees)
+//! - [`xy_to_index_32`] / [`index_to_xy_32`] - 32-bit coordinates, 64-bit index
+//! - [`xy_to_index_batch`], [`index_to_xy_batch`], [`xy_to_index_32_batch`], [`index_to_xy_32_batch`] - Batch versions
+//! - [`key`] / [`keys`] - Hilbert keys for points in arbitrary bounds, scaled exactly like `HilbertRTree::build()`
+//!
+//! Both widths trace the same curve: the top 32 bits of the 64-bit index of `(x, y)` equal the
+//! 16-bit index of `(x >> 16, y >> 16)`.
+//!
+//! The bit-parallel transforms are from <https://github.com/rawrunprotected/hilbert_curves> (public domain).
+//!
+//! # Example
+//! ```
+//! use aabb::hilbert;
+//!
+//! let index = hilbert::xy_to_index(1234, 5678);
+//! assert_eq!(hilbert::index_to_xy(index), (1234, 5678));
+//!
+//! // Sort points along the curve, consistent with how a tree over the same bounds orders them
+//! let mut points = vec![(9.0, 1.0), (0.5, 0.5), (5.0, 5.0)];
+//! let bounds = (0.0, 0.0, 10.0, 10.0);
+//! points.sort_by_key(|&(x, y)| hilbert::key(x, y, bounds));
+//! ```
+//!
+//! [`xy_to_index`]: crate::hilbert::xy_to_index
+//! [`index_to_xy`]: crate::hilbert::index_to_xy
+//! [`xy_to_index_32`]: crate::hilbert::xy_to_index_32
+//! [`index_to_xy_32`]: crate::hilbert::index_to_xy_32
+//! [`xy_to_index_batch`]: crate::hilbert::xy_to_index_batch
+//! [`index_to_xy_batch`]: crate::hilbert::index_to_xy_batch
+//! [`xy_to_index_32_batch`]: crate::hilbert::xy_to_index_32_batch
+//! [`index_to_xy_32_batch`]: crate::hilbert::index_to_xy_32_batch
+//! [`key`]: crate::hilbert::key
+//! [`keys`]: crate::hilbert::keys
+
+/// Grid resolution of the 16-bit curve used by the trees
+const MAX_HILBERT: u32 = u16::MAX as u32;
+
+/// Converts 16-bit grid coordinates to their index on the Hilbert curve.
+///
+/// This is the transform `build()` uses to order items.
+///
+/// # Example
+/// ```
+/// use aabb::hilbert;
+/// assert_eq!(hilbert::xy_to_index(0, 0), 0);
+/// assert_eq!(hilbert::index_to_xy(hilbert::xy_to_index(40000, 7)), (40000, 7));
+/// ```
+#[inline]
+pub fn xy_to_index(x: u16, y: u16) -> u32 {
+    hilbert_xy_to_index(u32::from(x), u32::from(y))
+}
+
+/// Converts an index on the 16-bit Hilbert curve back to grid coordinates.
+///
+/// Inverse of [`xy_to_index`].
+///
+/// # Example
+/// ```
+/// use aabb::hilbert;
+/// // Consecutive indices are always neighboring grid cells
+/// let (x0, y0) = hilbert::index_to_xy(1000);
+/// let (x1, y1) = hilbert::index_to_xy(1001);
+/// assert_eq!(x0.abs_diff(x1) + y0.abs_diff(y1), 1);
+/// ```
+#[inline]
+pub fn index_to_xy(index: u32) -> (u16, u16) {
+    let i0 = deinterleave(index);
+    let i1 = deinterleave(index >> 1);
+
+    let t0 = (i0 | i1) ^ 0xFFFF;
+    let t1 = i0 & i1;
+
+    let prefix_t0 = prefix_scan(t0);
+    let prefix_t1 = prefix_scan(t1);
+
+    let a = ((i0 ^ 0xFFFF) & prefix_t1) | (i0 & prefix_t0);
+
+    // Only the low 16 bits are set, so the conversions cannot fail
+    let x = u16::try_from(a ^ i1).unwrap_or(u16::MAX);
+    let y = u16::try_from(a ^ i0 ^ i1).unwrap_or(u16::MAX);
+    (x, y)
+}
+
+/// Converts 32-bit grid coordinates to their index on the Hilbert curve.
+///
+/// Same curve as [`xy_to_index`] at a finer resolution: `xy_to_index_32(x, y) >> 32`
+/// equals `xy_to_index(x >> 16, y >> 16)`.
+///
+/// # Example
+/// ```
+/// use aabb::hilbert;
+/// let index = hilbert::xy_to_index_32(3_000_000_000, 12);
+/// assert_eq!(hilbert::index_to_xy_32(index), (3_000_000_000, 12));
+/// assert_eq!((index >> 32) as u32, hilbert::xy_to_index((3_000_000_000_u32 >> 16) as u16, 0));
+/// ```
+#[expect(non_snake_case, reason = "names follow the reference implementation")]
+#[inline]
+pub fn xy_to_index_32(x: u32, y: u32) -> u64 {
+    const MASK: u64 = 0xFFFF_FFFF;
+    let (x, y) = (u64::from(x), u64::from(y));
+
+    // Initial prefix scan round, prime with x and y
+    let mut a = x ^ y;
+    let mut b = MASK ^ a;
+    let mut c = MASK ^ (x | y);
+    let mut d = x & (y ^ MASK);
+    let mut A = a | (b >> 1);
+    let mut B = (a >> 1) ^ a;
+    let mut C = ((c >> 1) ^ (b & (d >> 1))) ^ c;
+    let mut D = ((a & (c >> 1)) ^ (d >> 1)) ^ d;
+
+    for shift in [2, 4, 8] {
+        a = A;
+        b = B;
+        c = C;
+        d = D;
+        A = (a & (a >> shift)) ^ (b & (b >> shift));
+        B = (a & (b >> shift)) ^ (b & ((a ^ b) >> shift));
+        C ^= (a & (c >> shift)) ^ (b & (d >> shift));
+        D ^= (b & (c >> shift)) ^ ((a ^ b) & (d >> shift));
+    }
+
+    // Final round and projection
+    a = A;
+    b = B;
+    c = C;
+    d = D;
+    C ^= (a & (c >> 16)) ^ (b & (d >> 16));
+    D ^= (b & (c >> 16)) ^ ((a ^ b) & (d >> 16));
+
+    // Undo transformation prefix scan
+    a = C ^ (C >> 1);
+    b = D ^ (D >> 1);
+
+    // Recover index bits
+    let i0 = x ^ y;
+    let i1 = b | (MASK ^ (i0 | a));
+
+    (interleave_64(i1) << 1) | interleave_64(i0)
+}
+
+/// Converts an index on the 32-bit Hilbert curve back to grid coordinates.
+///
+/// Inverse of [`xy_to_index_32`].
+///
+/// # Example
+/// ```
+/// use aabb::hilbert;
+/// let (x, y) = hilbert::index_to_xy_32(u64::MAX);
+/// assert_eq!(hilbert::xy_to_index_32(x, y), u64::MAX);
+/// ```
+#[inline]
+pub fn index_to_xy_32(index: u64) -> (u32, u32) {
+    const MASK: u64 = 0xFFFF_FFFF;
+    let i0 = deinterleave_64(index);
+    let i1 = deinterleave_64(index >> 1);
+
+    let t0 = (i0 | i1) ^ MASK;
+    let t1 = i0 & i1;
+
+    let prefix_t0 = prefix_scan_64(t0);
+    let prefix_t1 = prefix_scan_64(t1);
+
+    let a = ((i0 ^ MASK) & prefix_t1) | (i0 & prefix_t0);
+
+    // Only the low 32 bits are set, so the conversions cannot fail
+    let x = u32::try_from(a ^ i1).unwrap_or(u32::MAX);
+    let y = u32::try_from(a ^ i0 ^ i1).unwrap_or(u32::MAX);
+    (x, y)
+}
+
+/// Converts many 16-bit grid points to Hilbert indices.
+///
+/// # Arguments
+/// * `points` - Grid coordinates `(x, y)`
+/// * `indices` - Output vector; will be cleared and populated with one index per point
+pub fn xy_to_index_batch(points: &[(u16, u16)], indices: &mut Vec<u32>) {
+    indices.clear();
+    indices.extend(points.iter().map(|&(x, y)| xy_to_index(x, y)));
+}
+
+/// Converts many 16-bit Hilbert indices back to grid points.
+///
+/// # Arguments
+/// * `indices` - Hilbert indices
+/// * `points` - Output vector; will be cleared and populated with one `(x, y)` per index
+pub fn index_to_xy_batch(indices: &[u32], points: &mut Vec<(u16, u16)>) {
+    points.clear();
+    points.extend(indices.iter().map(|&index| index_to_xy(index)));
+}
+
+/// Converts many 32-bit grid points to Hilbert indices.
+///
+/// # Arguments
+/// * `points` - Grid coordinates `(x, y)`
+/// * `indices` - Output vector; will be cleared and populated with one index per point
+pub fn xy_to_index_32_batch(points: &[(u32, u32)], indices: &mut Vec<u64>) {
+    indices.clear();
+    indices.extend(points.iter().map(|&(x, y)| xy_to_index_32(x, y)));
+}
+
+/// Converts many 32-bit Hilbert indices back to grid points.
+///
+/// # Arguments
+/// * `indices` - Hilbert indices
+/// * `points` - Output vector; will be cleared and populated with one `(x, y)` per index
+pub fn index_to_xy_32_batch(indices: &[u64], points: &mut Vec<(u32, u32)>) {
+    points.clear();
+    points.extend(indices.iter().map(|&index| index_to_xy_32(index)));
+}
+
+/// Computes the Hilbert key of a point inside arbitrary bounds.
+///
+/// The bounds are mapped onto the 16-bit grid exactly like `HilbertRTree::build()` maps
+/// the tree bounds, so sorting box centers by this key reproduces the tree's leaf order
+/// (up to ties). Points outside the bounds are clamped to the nearest edge.
+///
+/// # Arguments
+/// * `x` - X coordinate of the point
+/// * `y` - Y coordinate of the point
+/// * `bounds` - `(min_x, min_y, max_x, max_y)` of the data
+///
+/// # Example
+/// ```
+/// use aabb::hilbert;
+/// let bounds = (0.0, 0.0, 100.0, 100.0);
+/// assert_eq!(hilbert::key(0.0, 0.0, bounds), 0);
+/// assert!(hilbert::key(99.0, 1.0, bounds) > hilbert::key(1.0, 99.0, bounds));
+/// ```
+#[inline]
+pub fn key(x: f64, y: f64, bounds: (f64, f64, f64, f64)) -> u32 {
+    let (min_x, min_y, max_x, max_y) = bounds;
+    let width = f64::from(MAX_HILBERT) / (max_x - min_x);
+    let height = f64::from(MAX_HILBERT) / (max_y - min_y);
+    hilbert_scaled_to_index((x - min_x) * width, (y - min_y) * height)
+}
+
+/// Computes the Hilbert keys of many points inside the same bounds.
+///
+/// # Arguments
+/// * `points` - Points `(x, y)`
+/// * `bounds` - `(min_x, min_y, max_x, max_y)` of the data
+/// * `keys` - Output vector; will be cleared and populated with one key per point
+pub fn keys(points: &[(f64, f64)], bounds: (f64, f64, f64, f64), keys: &mut Vec<u32>) {
+    keys.clear();
+    keys.extend(points.iter().map(|&(x, y)| key(x, y, bounds)));
+}
+
+/// Hilbert value of a point already scaled to the `[0, MAX_HILBERT)` grid (clamped)
+#[inline(always)]
+pub(crate) fn hilbert_scaled_to_index(scaled_x: f64, scaled_y: f64) -> u32 {
+    let hx = scaled_x.max(0.0).min(f64::from(MAX_HILBERT) - 1.0) as u32;
+    let hy = scaled_y.max(0.0).min(f64::from(MAX_HILBERT) - 1.0) as u32;
+    hilbert_xy_to_index(hx, hy)
+}
+
+#[expect(non_snake_case, reason = "names follow the reference implementation")]
+#[inline]
+pub(crate) fn hilbert_xy_to_index(x: u32, y: u32) -> u32 {
+    // Initial prefix scan round, prime with x and y
+    let mut a = x ^ y;
+    let mut b = 0xFFFF ^ a;
+    let mut c = 0xFFFF ^ (x | y);
+    let mut d = x & (y ^ 0xFFFF);
+    let mut A = a | (b >> 1);
+    let mut B = (a >> 1) ^ a;
+    let mut C = ((c >> 1) ^ (b & (d >> 1))) ^ c;
+    let mut D = ((a & (c >> 1)) ^ (d >> 1)) ^ d;
+
+    a = A;
+    b = B;
+    c = C;
+    d = D;
+    A = (a & (a >> 2)) ^ (b & (b >> 2));
+    B = (a & (b >> 2)) ^ (b & ((a ^ b) >> 2));
+    C ^= (a & (c >> 2)) ^ (b & (d >> 2));
+    D ^= (b & (c >> 2)) ^ ((a ^ b) & (d >> 2));
+
+    a = A;
+    b = B;
+    c = C;
+    d = D;
+    A = (a & (a >> 4)) ^ (b & (b >> 4));
+    B = (a & (b >> 4)) ^ (b & ((a ^ b) >> 4));
+    C ^= (a & (c >> 4)) ^ (b & (d >> 4));
+    D ^= (b & (c >> 4)) ^ ((a ^ b) & (d >> 4));
+
+    // Final round and projection
+    a = A;
+    b = B;
+    c = C;
+    d = D;
+    C ^= (a & (c >> 8)) ^ (b & (d >> 8));
+    D ^= (b & (c >> 8)) ^ ((a ^ b) & (d >> 8));
+
+    // Undo transformation prefix scan
+    a = C ^ (C >> 1);
+    b = D ^ (D >> 1);
+
+    // Recover index bits
+    let i0 = x ^ y;
+    let i1 = b | (0xFFFF ^ (i0 | a));
+
+    (interleave(i1) << 1) | interleave(i0)
+}
+
+#[inline(always)]
+fn interleave(mut x: u32) -> u32 {
+    x = (x | (x << 8)) & 0x00FF00FF;
+    x = (x | (x << 4)) & 0x0F0F0F0F;
+    x = (x | (x << 2)) & 0x33333333;
+    x = (x | (x << 1)) & 0x55555555;
+    x
+}
+
+#[inline(always)]
+fn deinterleave(mut x: u32) -> u32 {
+    x &= 0x55555555;
+    x = (x | (x >> 1)) & 0x33333333;
+    x = (x | (x >> 2)) & 0x0F0F0F0F;
+    x = (x | (x >> 4)) & 0x00FF00FF;
+    x = (x | (x >> 8)) & 0x0000FFFF;
+    x
+}
+
+#[inline(always)]
+fn prefix_scan(mut x: u32) -> u32 {
+    x ^= x >> 8;
+    x ^= x >> 4;
+    x ^= x >> 2;
+    x ^= x >> 1;
+    x
+}
+
+#[inline(always)]
+fn interleave_64(mut x: u64) -> u64 {
+    x = (x | (x << 16)) & 0x0000_FFFF_0000_FFFF;
+    x = (x | (x << 8)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x << 2)) & 0x3333_3333_3333_3333;
+    x = (x | (x << 1)) & 0x5555_5555_5555_5555;
+    x
+}
+
+#[inline(always)]
+fn deinterleave_64(mut x: u64) -> u64 {
+    x &= 0x5555_5555_5555_5555;
+    x = (x | (x >> 1)) & 0x3333_3333_3333_3333;
+    x = (x | (x >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x >> 4)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x >> 8)) & 0x0000_FFFF_0000_FFFF;
+    x = (x | (x >> 16)) & 0x0000_0000_FFFF_FFFF;
+    x
+}
+
+#[inline(always)]
+fn prefix_scan_64(mut x: u64) -> u64 {
+    x ^= x >> 16;
+    x ^= x >> 8;
+    x ^= x >> 4;
+    x ^= x >> 2;
+    x ^= x >> 1;
+    x
+}
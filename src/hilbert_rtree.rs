@@ -9,6 +9,11 @@
 use std::mem::size_of;
 use std::collections::VecDeque;
 
+use crate::flatbush::{FlatbushArrayType, FlatbushLayout};
+use crate::geo;
+use crate::join::{self, JoinNodes};
+use crate::hilbert::hilbert_scaled_to_index;
+
 /// Box structure: minX, minY, maxX, maxY
 #[derive(Clone, Copy, Debug)]
 pub(crate) struct Box {
@@ -24,6 +29,160 @@ impl Box {
     }
 }
 
+/// Matching rule for [`HilbertRTree::query_polygon`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PolygonMode {
+    /// Boxes that intersect the polygon (touching edges or corners count)
+    Intersecting,
+    /// Boxes that lie completely inside the polygon
+    Contained,
+}
+
+/// Boundary semantics for the `*_with_boundary` queries
+///
+/// Controls whether box edges belong to the box. The plain queries (`query_intersecting`,
+/// `query_point`, ...) always use [`Boundary::Closed`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Boundary {
+    /// Edges are included: boxes touching at an edge or corner intersect, a point on an
+    /// edge is inside, and containment may share edges
+    #[default]
+    Closed,
+    /// Edges are excluded: boxes must overlap with positive area to intersect, a point
+    /// must lie strictly inside, and contained boxes must not touch the container's edges
+    Open,
+    /// Each axis is `[min, max)`: boxes sharing only an edge do not intersect, a point on
+    /// a min edge is inside but not on a max edge, and containment may share edges
+    HalfOpen,
+}
+
+impl Boundary {
+    /// Whether the intervals `[a_min, a_max]` and `[b_min, b_max]` overlap under these semantics
+    #[inline(always)]
+    pub(crate) fn overlaps<T: PartialOrd>(self, a_min: T, a_max: T, b_min: T, b_max: T) -> bool {
+        match self {
+            Self::Closed => a_min <= b_max && b_min <= a_max,
+            Self::Open | Self::HalfOpen => a_min < b_max && b_min < a_max,
+        }
+    }
+
+    /// Whether `value` lies in the interval `[min, max]` under these semantics
+    #[inline(always)]
+    pub(crate) fn contains_value<T: PartialOrd>(self, min: T, max: T, value: T) -> bool {
+        match self {
+            Self::Closed => min <= value && value <= max,
+            Self::Open => min < value && value < max,
+            Self::HalfOpen => min <= value && value < max,
+        }
+    }
+
+    /// Whether the interval `[outer_min, outer_max]` contains `[inner_min, inner_max]`
+    #[inline(always)]
+    pub(crate) fn contains_range<T: PartialOrd>(self, outer_min: T, outer_max: T, inner_min: T, inner_max: T) -> bool {
+        match self {
+            Self::Closed | Self::HalfOpen => outer_min <= inner_min && inner_max <= outer_max,
+            Self::Open => outer_min < inner_min && inner_max < outer_max,
+        }
+    }
+}
+
+/// Distance metric for the `*_with_metric` nearest and radius queries
+///
+/// A metric maps the per-axis gaps `dx >= 0` and `dy >= 0` between the query point and
+/// a box to a distance. It must be non-decreasing in both `dx` and `dy`, so the distance
+/// to a node box is a lower bound for every box inside it.
+pub trait Metric {
+    /// Whether the ball of radius `r` is the axis-aligned square of half-size `r`, in which
+    /// case radius queries are answered as rectangle queries
+    const BALL_IS_BOX: bool = false;
+
+    /// Distance for per-axis gaps `dx` and `dy`
+    fn distance(&self, dx: f64, dy: f64) -> f64;
+}
+
+/// Euclidean (L2) distance: `sqrt(dx² + dy²)`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Euclidean;
+
+impl Metric for Euclidean {
+    #[inline(always)]
+    fn distance(&self, dx: f64, dy: f64) -> f64 {
+        dx.hypot(dy)
+    }
+}
+
+/// Manhattan (L1) distance: `dx + dy`, e.g. for 4-connected grid movement
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Manhattan;
+
+impl Metric for Manhattan {
+    #[inline(always)]
+    fn distance(&self, dx: f64, dy: f64) -> f64 {
+        dx + dy
+    }
+}
+
+/// Chebyshev (L∞) distance: `max(dx, dy)`, e.g. king moves on a chessboard
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Chebyshev;
+
+impl Metric for Chebyshev {
+    const BALL_IS_BOX: bool = true;
+
+    #[inline(always)]
+    fn distance(&self, dx: f64, dy: f64) -> f64 {
+        dx.max(dy)
+    }
+}
+
+/// Summary of item weights: count, sum, minimum and maximum
+///
+/// Returned by [`HilbertRTree::aggregate_in_rect`]. Aggregates form a monoid: the empty
+/// aggregate (`Aggregate::default()`) is the identity and [`Aggregate::merge`] combines two.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aggregate {
+    /// Number of items
+    pub count: usize,
+    /// Sum of item weights
+    pub sum: f64,
+    /// Smallest item weight (`f64::INFINITY` when empty)
+    pub min: f64,
+    /// Largest item weight (`f64::NEG_INFINITY` when empty)
+    pub max: f64,
+}
+
+impl Aggregate {
+    /// Aggregate of a single item with the given weight
+    pub fn from_weight(weight: f64) -> Self {
+        Self { count: 1, sum: weight, min: weight, max: weight }
+    }
+
+    /// Combines another aggregate into this one
+    pub fn merge(&mut self, other: &Self) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Mean item weight, or `None` when empty
+    pub fn mean(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.sum / self.count as f64)
+        }
+    }
+}
+
+impl Default for Aggregate {
+    fn default() -> Self {
+        Self { count: 0, sum: 0.0, min: f64::INFINITY, max: f64::NEG_INFINITY }
+    }
+}
+
 /// Hilbert R-tree for spatial queries - following flatbush algorithm
 ///
 /// Memory layout (in single buffer):
@@ -33,10 +192,13 @@ impl Box {
 ///
 /// Leaf nodes occupy positions [0, `num_items`), parent nodes appended after.
 /// Tree is built bottom-up with Hilbert curve ordering for spatial locality.
+///
+/// `S` is the buffer storage: `Vec<u8>` for an owned tree, `&[u8]` for a read-only
+/// [`HilbertRTreeView`]. Queries are shared by both.
 #[derive(Clone, Debug)]
-pub struct HilbertRTree {
-    /// Single buffer: header + boxes + indices
-    data: Vec<u8>,
+pub struct HilbertRTree<S = Vec<u8>> {
+    /// Single buffer: header + boxes + indices (owned), or a view's buffer from the header on
+    data: S,
     /// Level boundaries: end position of each tree level
     pub(crate) level_bounds: Vec<usize>,
     /// Node size for tree construction
@@ -51,11 +213,69 @@ pub struct HilbertRTree {
     total_nodes: usize,
     /// Pre-allocated capacity in bytes (0 if not pre-allocated)
     allocated_capacity: usize,
+    /// Item weights in insertion order (empty unless `add_weighted` was used)
+    weights: Vec<f64>,
+    /// Weight aggregate of every node by position (empty unless weighted)
+    aggregates: Vec<Aggregate>,
+    /// Whether `build()` stores the item id -> leaf position table
+    id_lookup: bool,
+    /// Leaf position of every item id (empty unless `id_lookup` is enabled and built)
+    positions: Vec<u32>,
+    /// World period (width, height) for wrap-around queries, `None` for a flat world
+    period: Option<(f64, f64)>,
+    /// Where a view finds the saved sections in `data` (`None` for an owned tree)
+    sections: Option<SavedSections>,
+}
+
+/// Byte offsets of the parts of a saved tree, relative to the start of the node data
+#[derive(Clone, Copy, Debug)]
+struct SavedSections {
+    /// End of the header, boxes and indices
+    nodes_end: usize,
+    /// Start of the item weights, if saved
+    weights: Option<usize>,
+    /// Start of the ID lookup table, if saved
+    positions: Option<usize>,
+    /// Start of the per-node weight aggregates, if saved
+    aggregates: Option<usize>,
+}
+
+/// Read-only Hilbert R-tree borrowing a serialized buffer instead of owning it
+///
+/// Created with [`HilbertRTreeView::from_bytes`] from bytes written by `save()` or
+/// `to_bytes()`, e.g. a memory-mapped file or `include_bytes!`. Every query runs on the
+/// borrowed buffer through the same code as the owned tree; nothing is copied or rebuilt.
+pub type HilbertRTreeView<'a> = HilbertRTree<&'a [u8]>;
+
+/// Read-only Hilbert R-tree over a memory-mapped saved file, opened with
+/// [`HilbertRTree::open_mmap`]
+#[cfg(feature = "mmap")]
+pub type HilbertRTreeMmap = HilbertRTree<MmapBuffer>;
+
+/// Tree buffer inside a read-only memory map of a file written by `save()`
+///
+/// Keeps the whole file mapped and exposes the node data and the sections after it
+/// to the query code.
+#[cfg(feature = "mmap")]
+#[derive(Debug)]
+pub struct MmapBuffer {
+    map: memmap2::Mmap,
+    start: usize,
+}
+
+#[cfg(feature = "mmap")]
+impl AsRef<[u8]> for MmapBuffer {
+    #[inline(always)]
+    fn as_ref(&self) -> &[u8] {
+        &self.map[self.start..]
+    }
 }
 
 const MAX_HILBERT: u32 = u16::MAX as u32;
 const DEFAULT_NODE_SIZE: usize = 16;
 const HEADER_SIZE: usize = 8; // bytes
+/// Saved size of one node aggregate: count (u64), sum, min and max (f64)
+const AGGREGATE_SIZE: usize = 32;
 
 /// Helper: Estimate total nodes in tree given item count
 /// For a tree with node_size, total nodes ≈ N + N/node_size + N/node_size^2 + ...
@@ -119,6 +339,12 @@ impl HilbertRTree {
             position: 0,
             bounds: Box::new(f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
             total_nodes: 0,
+            weights: Vec::new(),
+            aggregates: Vec::new(),
+            id_lookup: false,
+            positions: Vec::new(),
+            period: None,
+            sections: None,
         }
     }
 
@@ -156,6 +382,37 @@ impl HilbertRTree {
         self.num_items += 1;
     }
 
+    /// Adds a bounding box with a numeric weight to the tree.
+    ///
+    /// When any item has a weight, `build()` stores the count, sum, minimum and maximum
+    /// weight of every node, which `aggregate_in_rect()` uses to answer range aggregate
+    /// queries (e.g. total population of parcels in a rectangle) without visiting every
+    /// leaf. Items added with `add()` or `add_point()` get weight `0.0`.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of the box
+    /// * `min_y` - Bottom edge of the box
+    /// * `max_x` - Right edge of the box
+    /// * `max_y` - Top edge of the box
+    /// * `weight` - Numeric value attached to the item
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add_weighted(0.0, 0.0, 1.0, 1.0, 120.0);  // Parcel 0, population 120
+    /// tree.add_weighted(2.0, 2.0, 3.0, 3.0, 80.0);   // Parcel 1, population 80
+    /// tree.build();
+    ///
+    /// let total = tree.aggregate_in_rect(0.0, 0.0, 5.0, 5.0).unwrap();
+    /// assert_eq!(total.sum, 200.0);
+    /// ```
+    pub fn add_weighted(&mut self, min_x: f64, min_y: f64, max_x: f64, max_y: f64, weight: f64) {
+        self.weights.resize(self.num_items, 0.0);
+        self.weights.push(weight);
+        self.add(min_x, min_y, max_x, max_y);
+    }
+
     /// Adds a point to the tree.
     ///
     /// This is a convenience method for adding point data. A point is stored internally
@@ -313,6 +570,8 @@ impl HilbertRTree {
             
             // For single-node case, no sorting happens
             // No need to populate sorted_order since we use lazy lookup
+            self.build_aggregates();
+            self.build_positions();
             return;
         }
 
@@ -325,9 +584,7 @@ impl HilbertRTree {
             let box_data = self.get_box(i);
             let center_x = ((box_data.min_x + box_data.max_x) / 2.0 - self.bounds.min_x) * hilbert_width;
             let center_y = ((box_data.min_y + box_data.max_y) / 2.0 - self.bounds.min_y) * hilbert_height;
-            let hx = center_x.max(0.0).min(MAX_HILBERT as f64 - 1.0) as u32;
-            let hy = center_y.max(0.0).min(MAX_HILBERT as f64 - 1.0) as u32;
-            hilbert_values.push(hilbert_xy_to_index(hx, hy));
+            hilbert_values.push(hilbert_scaled_to_index(center_x, center_y));
         }
 
         // Create an indirection array to track sorting permutations
@@ -420,6 +677,203 @@ impl HilbertRTree {
             }
             pos = level_end;
         }
+
+        self.build_aggregates();
+        self.build_positions();
+    }
+
+    /// Enables or disables constant-time lookup of items by ID.
+    ///
+    /// After `build()` leaves are stored in Hilbert order, so finding an item's position
+    /// otherwise scans all leaves. With the lookup enabled, `build()` also stores the
+    /// inverse permutation (4 bytes per item), making `get()`, `get_point()` and the
+    /// `*_id` queries O(1) lookups. `save()` writes the table and `load()` reads it back
+    /// instead of rebuilding it. Calling this on a built tree creates or drops the table
+    /// immediately.
+    ///
+    /// # Arguments
+    /// * `enabled` - Whether to store the ID lookup table
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(100);
+    /// tree.set_id_lookup(true);
+    /// for i in 0..100 {
+    ///     tree.add_point(i as f64, (i * 7 % 100) as f64);
+    /// }
+    /// tree.build();
+    ///
+    /// assert_eq!(tree.get_point(42), Some((42.0, 94.0)));
+    /// ```
+    pub fn set_id_lookup(&mut self, enabled: bool) {
+        self.id_lookup = enabled;
+        if self.level_bounds.is_empty() {
+            return;
+        }
+        self.build_positions();
+    }
+
+    /// Fill the item id -> leaf position table when enabled, drop it otherwise
+    fn build_positions(&mut self) {
+        if !self.id_lookup {
+            self.positions = Vec::new();
+            return;
+        }
+        let mut positions = vec![0; self.num_items];
+        for (pos, leaf) in (0..self.num_items).zip(0_u32..) {
+            positions[self.get_index(pos) as usize] = leaf;
+        }
+        self.positions = positions;
+    }
+
+    /// Compute per-node weight aggregates bottom-up (empty for unweighted trees)
+    fn build_aggregates(&mut self) {
+        self.aggregates.clear();
+        if self.weights.is_empty() {
+            return;
+        }
+        self.weights.resize(self.num_items, 0.0);
+
+        let mut aggregates = Vec::with_capacity(self.total_nodes);
+        for pos in 0..self.num_items {
+            aggregates.push(Aggregate::from_weight(self.weights[self.get_index(pos) as usize]));
+        }
+        // Children always precede their parent, so one pass in position order suffices
+        for pos in self.num_items..self.total_nodes {
+            let mut aggregate = Aggregate::default();
+            for child in self.children(pos) {
+                aggregate.merge(&aggregates[child]);
+            }
+            aggregates.push(aggregate);
+        }
+        self.aggregates = aggregates;
+    }
+}
+
+impl<S: AsRef<[u8]> + Sync> HilbertRTree<S> {
+    /// Header, boxes and indices (a view's buffer also holds the saved sections after them)
+    fn node_bytes(&self) -> &[u8] {
+        let data = self.data.as_ref();
+        self.sections.map_or(data, |sections| &data[..sections.nodes_end])
+    }
+
+    /// Whether items carry weights (added with `add_weighted()` or loaded from a save)
+    fn is_weighted(&self) -> bool {
+        !self.weights.is_empty() || self.sections.is_some_and(|sections| sections.weights.is_some())
+    }
+
+    /// Weight of an item (0.0 for items added without one)
+    fn weight(&self, item_id: usize) -> f64 {
+        match self.sections.and_then(|sections| sections.weights) {
+            Some(start) => {
+                let mut buf = [0_u8; 8];
+                let at = start + item_id * size_of::<f64>();
+                buf.copy_from_slice(&self.data.as_ref()[at..at + size_of::<f64>()]);
+                f64::from_le_bytes(buf)
+            }
+            None => self.weights.get(item_id).copied().unwrap_or(0.0),
+        }
+    }
+
+    /// Whether the ID lookup table covers every item
+    fn has_id_table(&self) -> bool {
+        match self.sections {
+            Some(sections) => sections.positions.is_some(),
+            None => self.id_lookup && self.positions.len() == self.num_items,
+        }
+    }
+
+    /// Leaf position of an item according to the ID lookup table, if there is one
+    fn table_position(&self, item_id: usize) -> Option<u32> {
+        match self.sections.and_then(|sections| sections.positions) {
+            Some(start) => {
+                let mut buf = [0_u8; 4];
+                let at = start + item_id * size_of::<u32>();
+                buf.copy_from_slice(&self.data.as_ref()[at..at + size_of::<u32>()]);
+                Some(u32::from_le_bytes(buf))
+            }
+            None => self.positions.get(item_id).copied(),
+        }
+    }
+
+    /// Whether per-node weight aggregates are available (weighted and built)
+    fn has_aggregates(&self) -> bool {
+        !self.aggregates.is_empty() || self.sections.is_some_and(|sections| sections.aggregates.is_some())
+    }
+
+    /// Weight aggregate of the node at `pos`
+    fn node_aggregate(&self, pos: usize) -> Aggregate {
+        let Some(start) = self.sections.and_then(|sections| sections.aggregates) else {
+            return self.aggregates[pos];
+        };
+        let at = start + pos * AGGREGATE_SIZE;
+        let bytes = &self.data.as_ref()[at..at + AGGREGATE_SIZE];
+        let mut fields = [0_u8; 8];
+        let mut read = |field: usize| {
+            fields.copy_from_slice(&bytes[field * 8..field * 8 + 8]);
+            fields
+        };
+        Aggregate {
+            count: usize::try_from(u64::from_le_bytes(read(0))).unwrap_or(usize::MAX),
+            sum: f64::from_le_bytes(read(1)),
+            min: f64::from_le_bytes(read(2)),
+            max: f64::from_le_bytes(read(3)),
+        }
+    }
+
+    /// Makes the world wrap around with the given period on each axis.
+    ///
+    /// For toroidal (periodic-boundary) worlds, coordinates `x` and `x + width` describe the
+    /// same place, and likewise for `y` and `y + height`. Once a period is set,
+    /// `query_intersecting_wrapped()`, `query_circle_wrapped()` and `query_nearest_k_wrapped()`
+    /// measure across the seam: query regions and boxes that cross an edge match their
+    /// wrapped counterparts, and nearest distances use the shortest way around. Each item is
+    /// returned at most once. Every other query, including `query_intersecting()`, stays flat,
+    /// so counts, aggregates and the plain queries always agree with each other.
+    ///
+    /// Items and queries may use any coordinates; they do not need to lie inside one period.
+    /// Pass `f64::INFINITY` for an axis that does not wrap (a cylinder world).
+    /// The period is kept by `save()`/`load()`.
+    ///
+    /// # Arguments
+    /// * `width` - Period along the X axis
+    /// * `height` - Period along the Y axis
+    ///
+    /// # Panics
+    /// Panics if `width` or `height` is not positive.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add_point(1.0, 50.0);   // Ship 0, just right of the seam
+    /// tree.add_point(60.0, 50.0);  // Ship 1
+    /// tree.build();
+    /// tree.set_period(100.0, 100.0);
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_nearest_k_wrapped(98.0, 50.0, 1, &mut results);
+    /// assert_eq!(results, vec![0]);  // 3 units away across the seam
+    ///
+    /// tree.query_intersecting_wrapped(95.0, 45.0, 105.0, 55.0, &mut results);
+    /// assert_eq!(results, vec![0]);
+    /// tree.query_intersecting(95.0, 45.0, 105.0, 55.0, &mut results);
+    /// assert!(results.is_empty());  // Plain queries ignore the period
+    /// ```
+    pub fn set_period(&mut self, width: f64, height: f64) {
+        assert!(is_valid_period(width, height), "period must be positive");
+        self.period = Some((width, height));
+    }
+
+    /// Removes the period set by `set_period()`, making the `*_wrapped` queries flat again
+    pub fn clear_period(&mut self) {
+        self.period = None;
+    }
+
+    /// Returns the world period `(width, height)`, if the world wraps around
+    pub fn period(&self) -> Option<(f64, f64)> {
+        self.period
     }
 
     /// Returns the number of items
@@ -453,26 +907,101 @@ impl HilbertRTree {
     /// assert_eq!(tree.get(2), None);
     /// ```
     pub fn get(&self, item_id: usize) -> Option<(f64, f64, f64, f64)> {
-        if item_id >= self.num_items {
-            return None;
-        }
+        self.position_of(item_id).map(|pos| {
+            let bbox = self.get_box(pos);
+            (bbox.min_x, bbox.min_y, bbox.max_x, bbox.max_y)
+        })
+    }
 
-        // If tree hasn't been built yet, items are in insertion order
-        if self.level_bounds.is_empty() {
-            let bbox = self.get_box(item_id);
-            return Some((bbox.min_x, bbox.min_y, bbox.max_x, bbox.max_y));
-        }
 
-        // After build(): search through leaf nodes to find which position has this item_id
-        for pos in 0..self.num_items {
-            if self.get_index(pos) as usize == item_id {
-                let bbox = self.get_box(pos);
-                return Some((bbox.min_x, bbox.min_y, bbox.max_x, bbox.max_y));
-            }
-        }
-        None
+    /// Returns the item IDs in the tree's spatial (Hilbert) order.
+    ///
+    /// After `build()` leaves are stored sorted by the Hilbert index of their centers;
+    /// this yields the item ID stored at each leaf position, first to last. Before
+    /// `build()` the order is the insertion order.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 1
+    /// tree.add(1.0, 1.0, 2.0, 2.0);  // Box 2
+    /// tree.build();
+    ///
+    /// let mut order: Vec<usize> = tree.sorted_order().collect();
+    /// assert_eq!(order.len(), 3);
+    /// order.sort();
+    /// assert_eq!(order, vec![0, 1, 2]);
+    /// ```
+    pub fn sorted_order(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.num_items).map(move |pos| {
+            if self.level_bounds.is_empty() { pos } else { self.get_index(pos) as usize }
+        })
+    }
+
+    /// Returns the build permutation: `permutation()[i]` is the item ID at leaf position `i`.
+    ///
+    /// Same as collecting `sorted_order()`.
+    pub fn permutation(&self) -> Vec<usize> {
+        self.sorted_order().collect()
+    }
+
+    /// Reorders a per-item slice into the tree's spatial order.
+    ///
+    /// `values[id]` holds an attribute of item `id` (color, flags, ...). The returned
+    /// vector holds the same values in leaf order, so element `i` belongs to the item
+    /// `permutation()[i]`. Scanning attributes in this order has the same locality as
+    /// the tree itself.
+    ///
+    /// # Arguments
+    /// * `values` - One value per item, indexed by item ID
+    ///
+    /// # Panics
+    /// Panics if `values.len()` differs from the number of items.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 1
+    /// tree.add(1.0, 1.0, 2.0, 2.0);  // Box 2
+    /// tree.build();
+    ///
+    /// let colors = ["red", "green", "blue"];
+    /// let sorted = tree.apply_permutation(&colors);
+    /// for (color, id) in sorted.iter().zip(tree.sorted_order()) {
+    ///     assert_eq!(*color, colors[id]);
+    /// }
+    /// ```
+    pub fn apply_permutation<T: Clone>(&self, values: &[T]) -> Vec<T> {
+        assert_eq!(values.len(), self.num_items, "expected one value per item");
+        self.sorted_order().map(|id| values[id].clone()).collect()
     }
 
+    /// Iterates over `(id, (min_x, min_y, max_x, max_y))` for all items in spatial order.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 1
+    /// tree.add(1.0, 1.0, 2.0, 2.0);  // Box 2
+    /// tree.build();
+    ///
+    /// for (id, bbox) in tree.iter() {
+    ///     assert_eq!(tree.get(id), Some(bbox));
+    /// }
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (usize, (f64, f64, f64, f64))> + '_ {
+        (0..self.num_items).map(move |pos| {
+            let bbox = self.get_box(pos);
+            let id = if self.level_bounds.is_empty() { pos } else { self.get_index(pos) as usize };
+            (id, (bbox.min_x, bbox.min_y, bbox.max_x, bbox.max_y))
+        })
+    }
 
     /// Finds all boxes that intersect with a given rectangular region.
     ///
@@ -597,6 +1126,9 @@ impl HilbertRTree {
     /// convenient than calling `query_intersecting()` with manually extracted bounds, and
     /// avoids redundant lookups and self-intersection checks.
     ///
+    /// The item's box is found in O(1) when `set_id_lookup(true)` is enabled; otherwise
+    /// a built tree scans its leaves for the item, which is O(n) per call.
+    ///
     /// # Arguments
     /// * `item_id` - The index of an item already in the tree (0 to `num_items - 1`)
     /// * `results` - Output vector; will be cleared and populated with matching box indices
@@ -627,8 +1159,8 @@ impl HilbertRTree {
             return Err(format!("item_id {} is out of bounds (tree has {} items)", item_id, self.num_items));
         }
         
-        // Get the bounding box of the query item
-        let query_box = self.get_box(item_id);
+        // Get the bounding box of the query item (leaves are in Hilbert order after build)
+        let query_box = self.get_box(self.position_of(item_id).unwrap_or(item_id));
         
         // Use the existing query_intersecting method with the box bounds
         self.query_intersecting(query_box.min_x, query_box.min_y, query_box.max_x, query_box.max_y, results);
@@ -883,58 +1415,400 @@ impl HilbertRTree {
         }
     }
 
-    /// Finds the K nearest point items (stored as (x, x, y, y)) to a query point.
-    ///
-    /// This is an optimized version of `query_nearest_k()` specifically for point data.
-    /// For point items where min_x==max_x and min_y==max_y, this method computes
-    /// distances directly without the axis_distance() helper function, providing
-    /// approximately 30% faster queries on point clouds.
+    /// Finds the K nearest boxes to a query rectangle.
     ///
-    /// **Important:** This method assumes all items in the tree are stored as points.
-    /// If the tree contains mixed data (both points and boxes), use `query_nearest_k()` instead
-    /// for correct results. Calling this on non-point data will produce incorrect results.
+    /// Uses the box-to-box distance (gap between the closest edges), which is zero for
+    /// boxes that overlap or touch the query rectangle. This is useful for questions like
+    /// "nearest 3 hydrants to this building footprint".
     ///
     /// # Arguments
-    /// * `point_x` - X coordinate of the query point
-    /// * `point_y` - Y coordinate of the query point
-    /// * `k` - Number of nearest points to find
-    /// * `results` - Output vector; will be cleared and populated with K nearest point indices,
-    ///              sorted by distance (closest first)
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    /// * `k` - Number of nearest boxes to find
+    /// * `results` - Output vector; will be cleared and populated with K nearest box indices,
+    ///   sorted by distance (closest first)
     ///
     /// # Example
     /// ```
     /// use aabb::prelude::*;
     /// let mut tree = AABB::with_capacity(3);
-    /// tree.add(0.0, 0.0, 0.0, 0.0);  // Point 0
-    /// tree.add(2.0, 2.0, 2.0, 2.0);  // Point 1
-    /// tree.add(5.0, 5.0, 5.0, 5.0);  // Point 2
+    /// tree.add_point(5.0, 1.0);   // Hydrant 0
+    /// tree.add_point(0.0, 9.0);   // Hydrant 1
+    /// tree.add_point(-3.0, 1.0);  // Hydrant 2
     /// tree.build();
     ///
     /// let mut results = Vec::new();
-    /// tree.query_nearest_k_points(0.0, 0.0, 2, &mut results);
-    /// // Results contain the 2 nearest points
+    /// tree.query_nearest_k_to_box(0.0, 0.0, 4.0, 2.0, 2, &mut results);
+    /// assert_eq!(results, vec![0, 2]);
     /// ```
-    pub fn query_nearest_k_points(
-        &self,
-        point_x: f64,
-        point_y: f64,
-        k: usize,
-        results: &mut Vec<usize>,
-    ) {
-        results.clear();
-        if self.num_items == 0 || self.level_bounds.is_empty() || k == 0 {
-            return;
-        }
-
-        use std::collections::BinaryHeap;
-        use std::cmp::Ordering;
+    pub fn query_nearest_k_to_box(&self, min_x: f64, min_y: f64, max_x: f64, max_y: f64, k: usize, results: &mut Vec<usize>) {
+        let query = Box::new(min_x, min_y, max_x, max_y);
+        self.nearest_k_by(|node_box| box_distance_sq(&query, node_box), k, results);
+    }
 
-        // Priority queue entry: (distance, node_pos, is_leaf)
-        // Use reverse ordering so we get min-heap (closest items first)
-        #[derive(Debug, Clone, Copy)]
-        struct NodeEntry {
-            dist_sq: f64,
-            pos: usize,
+    /// Finds the K nearest boxes to a line segment.
+    ///
+    /// Uses the distance between the segment and each box, which is zero for boxes the
+    /// segment crosses or touches. Useful for finding features closest to a road or wall.
+    ///
+    /// # Arguments
+    /// * `x1` - X coordinate of the segment start
+    /// * `y1` - Y coordinate of the segment start
+    /// * `x2` - X coordinate of the segment end
+    /// * `y2` - Y coordinate of the segment end
+    /// * `k` - Number of nearest boxes to find
+    /// * `results` - Output vector; will be cleared and populated with K nearest box indices,
+    ///   sorted by distance (closest first)
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(4.0, 1.0, 5.0, 2.0);   // Box 0, 1 above the segment
+    /// tree.add(4.0, -4.0, 5.0, -3.0); // Box 1, 3 below the segment
+    /// tree.add(20.0, 0.0, 21.0, 1.0); // Box 2, 10 past the segment end
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_nearest_k_to_segment(0.0, 0.0, 10.0, 0.0, 2, &mut results);
+    /// assert_eq!(results, vec![0, 1]);
+    /// ```
+    pub fn query_nearest_k_to_segment(&self, x1: f64, y1: f64, x2: f64, y2: f64, k: usize, results: &mut Vec<usize>) {
+        self.nearest_k_by(|node_box| segment_box_distance_sq(x1, y1, x2, y2, node_box), k, results);
+    }
+
+    /// Shared K-nearest search for query shapes given by a squared distance lower bound
+    fn nearest_k_by<D: Fn(&Box) -> f64>(&self, dist_sq: D, k: usize, results: &mut Vec<usize>) {
+        self.nearest_k_by_leaf(&dist_sq, &dist_sq, k, results);
+    }
+
+    /// `nearest_k_by()` with a separate (cheaper) distance for leaf boxes
+    fn nearest_k_by_leaf<N, L>(&self, node_dist: N, leaf_dist: L, k: usize, results: &mut Vec<usize>)
+    where
+        N: Fn(&Box) -> f64,
+        L: Fn(&Box) -> f64,
+    {
+        results.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() || k == 0 {
+            return;
+        }
+
+        let mut found = Vec::with_capacity(k);
+        self.nearest_leaves_by(usize::MAX, node_dist, leaf_dist, k, f64::INFINITY, &mut found);
+        results.extend(found.iter().map(|&(_, pos)| self.get_index(pos) as usize));
+    }
+
+    /// Finds the K nearest boxes to a box already in the index.
+    ///
+    /// Distances are box-to-box distances from the item's own bounding box (zero for
+    /// boxes that overlap or touch it), and the item itself is never part of the results.
+    /// This avoids looking up the item's coordinates with `get()` first.
+    ///
+    /// Locating the item is O(1) with `set_id_lookup(true)`. Without the lookup table a
+    /// built tree scans its leaves (O(n)), so enable it when querying many items by ID.
+    ///
+    /// # Arguments
+    /// * `item_id` - The index of an item already in the tree (0 to `num_items - 1`)
+    /// * `k` - Number of nearest boxes to find
+    /// * `results` - Output vector; will be cleared and populated with K nearest box indices,
+    ///   sorted by distance (closest first), excluding the query item itself
+    ///
+    /// # Errors
+    /// Returns an error if `item_id >= num_items` (the item doesn't exist in the tree).
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Item 0
+    /// tree.add(3.0, 0.0, 4.0, 1.0);  // Item 1
+    /// tree.add(9.0, 0.0, 9.5, 1.0);  // Item 2
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_nearest_k_id(1, 2, &mut results).unwrap();
+    /// assert_eq!(results, vec![0, 2]);
+    /// ```
+    pub fn query_nearest_k_id(&self, item_id: usize, k: usize, results: &mut Vec<usize>) -> Result<(), String> {
+        let Some(pos) = self.position_of(item_id) else {
+            return Err(format!("item_id {} is out of bounds (tree has {} items)", item_id, self.num_items));
+        };
+
+        results.clear();
+        if self.level_bounds.is_empty() || k == 0 {
+            return Ok(());
+        }
+
+        let item = self.get_box(pos);
+        let mut found = Vec::with_capacity(k);
+        self.nearest_leaves(pos, |node_box| box_distance_sq(&item, node_box), k, f64::INFINITY, &mut found);
+        results.extend(found.iter().map(|&(_, leaf)| self.get_index(leaf) as usize));
+        Ok(())
+    }
+
+    /// Finds all boxes within a distance of a box already in the index.
+    ///
+    /// The distance is measured from the item's bounding box (box-to-box distance), so
+    /// with `radius = 0` this returns the boxes overlapping or touching the item, like
+    /// `query_intersecting_id()`. The item itself is never part of the results.
+    ///
+    /// As with `query_nearest_k_id()`, the item is located in O(1) with
+    /// `set_id_lookup(true)` and by an O(n) scan of the leaves otherwise.
+    ///
+    /// # Arguments
+    /// * `item_id` - The index of an item already in the tree (0 to `num_items - 1`)
+    /// * `radius` - Maximum distance from the item's box
+    /// * `results` - Output vector; will be cleared and populated with matching box indices,
+    ///   excluding the query item itself
+    ///
+    /// # Errors
+    /// Returns an error if `item_id >= num_items` (the item doesn't exist in the tree).
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Item 0
+    /// tree.add(3.0, 0.0, 4.0, 1.0);  // Item 1, 2 away from item 0
+    /// tree.add(9.0, 0.0, 9.5, 1.0);  // Item 2, 8 away from item 0
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_circle_id(0, 2.5, &mut results).unwrap();
+    /// assert_eq!(results, vec![1]);
+    /// ```
+    pub fn query_circle_id(&self, item_id: usize, radius: f64, results: &mut Vec<usize>) -> Result<(), String> {
+        let Some(pos) = self.position_of(item_id) else {
+            return Err(format!("item_id {} is out of bounds (tree has {} items)", item_id, self.num_items));
+        };
+        if radius < 0.0 {
+            results.clear();
+            return Ok(());
+        }
+
+        let item = self.get_box(pos);
+        let radius_sq = radius * radius;
+        let within = |node_box: &Box| box_distance_sq(&item, node_box) <= radius_sq;
+        self.collect_leaves(within, within, results);
+        results.retain(|&idx| idx != item_id);
+        Ok(())
+    }
+
+    /// Finds the K farthest boxes from a point.
+    ///
+    /// The distance to a box is the Euclidean distance from the point to the nearest point
+    /// of the box, as in `query_nearest_k()`. The search is best-first on the largest
+    /// possible distance to each node box (its farthest corner), so only the nodes that can
+    /// still contain one of the K farthest boxes are expanded. Useful for facility-location
+    /// analysis, e.g. finding the customers worst served by a site.
+    ///
+    /// # Arguments
+    /// * `point_x` - X coordinate of the query point
+    /// * `point_y` - Y coordinate of the query point
+    /// * `k` - Number of farthest boxes to find
+    /// * `results` - Output vector; will be cleared and populated with K farthest box indices,
+    ///   sorted by distance (farthest first)
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add_point(1.0, 0.0);  // Point 0
+    /// tree.add_point(9.0, 0.0);  // Point 1
+    /// tree.add_point(0.0, 5.0);  // Point 2
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_farthest_k(0.0, 0.0, 2, &mut results);
+    /// assert_eq!(results, vec![1, 2]);
+    /// ```
+    pub fn query_farthest_k(&self, point_x: f64, point_y: f64, k: usize, results: &mut Vec<usize>) {
+        use std::collections::BinaryHeap;
+
+        results.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() || k == 0 {
+            return;
+        }
+
+        let max_dist_sq = |node_box: &Box| {
+            let dx = (point_x - node_box.min_x).abs().max((node_box.max_x - point_x).abs());
+            let dy = (point_y - node_box.min_y).abs().max((node_box.max_y - point_y).abs());
+            dx * dx + dy * dy
+        };
+
+        // Max-heap: leaves keyed by their exact distance, parents by an upper bound
+        let mut queue = BinaryHeap::new();
+        let root = self.total_nodes - 1;
+        queue.push(HeapEntry { dist: max_dist_sq(&self.get_box(root)), pos: root });
+
+        // A leaf popped from the heap is at least as far as every bound still queued
+        while let Some(entry) = queue.pop() {
+            if entry.pos < self.num_items {
+                results.push(self.get_index(entry.pos) as usize);
+                if results.len() == k {
+                    break;
+                }
+                continue;
+            }
+            for child in self.children(entry.pos) {
+                let child_box = self.get_box(child);
+                let dist_sq = if child < self.num_items {
+                    let dx = self.axis_distance(point_x, child_box.min_x, child_box.max_x);
+                    let dy = self.axis_distance(point_y, child_box.min_y, child_box.max_y);
+                    dx * dx + dy * dy
+                } else {
+                    max_dist_sq(&child_box)
+                };
+                queue.push(HeapEntry { dist: dist_sq, pos: child });
+            }
+        }
+    }
+
+    /// Finds all boxes that have a point among their K nearest neighbors (reverse kNN).
+    ///
+    /// An item is reported when fewer than `k` other items are strictly closer to it than
+    /// the query point, i.e. the point would be one of its K nearest neighbors if it were
+    /// inserted into the tree (ties count in favor of the point). Item-to-item distances
+    /// are box-to-box distances and item-to-point distances are point-to-box distances, as
+    /// in `knn_graph()` and `query_nearest_k()`. Useful for siting: the result is the set
+    /// of items a new facility at the point would "capture".
+    ///
+    /// The tree is searched top-down and a node is skipped when at least `k + 1` items are
+    /// closer than the point to everything inside it, so none of its items can qualify.
+    /// Only the leaves that survive this filter are verified with a K-nearest search
+    /// bounded by their distance to the point. The order of the results is unspecified.
+    ///
+    /// # Arguments
+    /// * `point_x` - X coordinate of the query point
+    /// * `point_y` - Y coordinate of the query point
+    /// * `k` - Neighborhood size
+    /// * `results` - Output vector; will be cleared and populated with matching item indices
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add_point(0.0, 0.0);  // Point 0
+    /// tree.add_point(1.0, 0.0);  // Point 1
+    /// tree.add_point(5.0, 0.0);  // Point 2
+    /// tree.build();
+    ///
+    /// // A site at x=4 is closer to point 2 than any other point; for point 1
+    /// // the site (distance 3) is farther than point 0 (distance 1)
+    /// let mut results = Vec::new();
+    /// tree.query_reverse_nearest_k(4.0, 0.0, 1, &mut results);
+    /// assert_eq!(results, vec![2]);
+    /// ```
+    pub fn query_reverse_nearest_k(&self, point_x: f64, point_y: f64, k: usize, results: &mut Vec<usize>) {
+        results.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() || k == 0 {
+            return;
+        }
+
+        let mut found = Vec::with_capacity(k);
+        let mut stack = vec![self.total_nodes - 1];
+        while let Some(pos) = stack.pop() {
+            let node_box = self.get_box(pos);
+            let dx = self.axis_distance(point_x, node_box.min_x, node_box.max_x);
+            let dy = self.axis_distance(point_y, node_box.min_y, node_box.max_y);
+            let point_dist_sq = dx * dx + dy * dy;
+
+            // Filter: every item inside has at least k others closer than the point
+            // (k + 1 witnesses, since one of them may be the item itself)
+            if self.count_closer_than(&node_box, point_dist_sq, k + 1) > k {
+                continue;
+            }
+            if pos >= self.num_items {
+                stack.extend(self.children(pos));
+                continue;
+            }
+
+            // Refine: up to k other items no farther than the point; only strictly closer ones count
+            self.nearest_leaves(pos, |other| box_distance_sq(&node_box, other), k, point_dist_sq, &mut found);
+            let closer = found.iter().filter(|&&(dist_sq, _)| dist_sq < point_dist_sq).count();
+            if closer < k {
+                results.push(self.get_index(pos) as usize);
+            }
+        }
+    }
+
+    /// Count items whose box is strictly closer than `sqrt(bound_sq)` to every point of
+    /// `region`, stopping early once `limit` is reached
+    fn count_closer_than(&self, region: &Box, bound_sq: f64, limit: usize) -> usize {
+        let mut count = 0;
+        let mut stack = vec![self.total_nodes - 1];
+        while let Some(pos) = stack.pop() {
+            let node_box = self.get_box(pos);
+            // Exact for leaves, a lower bound for every box inside a parent
+            if farthest_gap_sq(region, &node_box) >= bound_sq {
+                continue;
+            }
+            if pos < self.num_items || farthest_point_sq(region, &node_box) < bound_sq {
+                count += self.leaf_range(pos).len();
+                if count >= limit {
+                    break;
+                }
+            } else {
+                stack.extend(self.children(pos));
+            }
+        }
+        count
+    }
+
+    /// Finds the K nearest point items (stored as (x, x, y, y)) to a query point.
+    ///
+    /// This is an optimized version of `query_nearest_k()` specifically for point data.
+    /// For point items where min_x==max_x and min_y==max_y, this method computes
+    /// distances directly without the axis_distance() helper function, providing
+    /// approximately 30% faster queries on point clouds.
+    ///
+    /// **Important:** This method assumes all items in the tree are stored as points.
+    /// If the tree contains mixed data (both points and boxes), use `query_nearest_k()` instead
+    /// for correct results. Calling this on non-point data will produce incorrect results.
+    ///
+    /// # Arguments
+    /// * `point_x` - X coordinate of the query point
+    /// * `point_y` - Y coordinate of the query point
+    /// * `k` - Number of nearest points to find
+    /// * `results` - Output vector; will be cleared and populated with K nearest point indices,
+    ///              sorted by distance (closest first)
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 0.0, 0.0);  // Point 0
+    /// tree.add(2.0, 2.0, 2.0, 2.0);  // Point 1
+    /// tree.add(5.0, 5.0, 5.0, 5.0);  // Point 2
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_nearest_k_points(0.0, 0.0, 2, &mut results);
+    /// // Results contain the 2 nearest points
+    /// ```
+    pub fn query_nearest_k_points(
+        &self,
+        point_x: f64,
+        point_y: f64,
+        k: usize,
+        results: &mut Vec<usize>,
+    ) {
+        results.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() || k == 0 {
+            return;
+        }
+
+        use std::collections::BinaryHeap;
+        use std::cmp::Ordering;
+
+        // Priority queue entry: (distance, node_pos, is_leaf)
+        // Use reverse ordering so we get min-heap (closest items first)
+        #[derive(Debug, Clone, Copy)]
+        struct NodeEntry {
+            dist_sq: f64,
+            pos: usize,
             is_leaf: bool,
         }
 
@@ -1340,318 +2214,444 @@ impl HilbertRTree {
         }
     }
 
-    /// Finds the first K intersecting boxes within a rectangular region.
+    /// Finds all boxes that intersect with a rectangle under the given boundary semantics.
     ///
-    /// This query finds boxes intersecting a query rectangle and stops after collecting K results.
-    /// Unlike `query_intersecting` which finds all matches, this variant returns early when K
-    /// results are found, making it more efficient when only a limited number of results are needed.
-    /// Results are included in the order they are encountered during tree traversal.
+    /// Same as `query_intersecting()` with [`Boundary::Closed`]. With [`Boundary::Open`] or
+    /// [`Boundary::HalfOpen`] boxes that only share an edge or corner with the query are
+    /// not reported, e.g. adjacent rooms in a floor plan sharing a wall.
     ///
     /// # Arguments
     /// * `min_x` - Left edge of query rectangle
     /// * `min_y` - Bottom edge of query rectangle
     /// * `max_x` - Right edge of query rectangle
     /// * `max_y` - Top edge of query rectangle
-    /// * `k` - Maximum number of results to return
-    /// * `results` - Output vector; will be cleared and populated with up to K matching box indices
+    /// * `boundary` - Whether edges belong to the boxes
+    /// * `results` - Output vector; will be cleared and populated with matching box indices
     ///
     /// # Example
     /// ```
     /// use aabb::prelude::*;
-    /// let mut tree = AABB::with_capacity(5);
-    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
-    /// tree.add(0.5, 0.5, 1.5, 1.5);  // Box 1
-    /// tree.add(1.0, 1.0, 2.0, 2.0);  // Box 2
-    /// tree.add(1.5, 1.5, 2.5, 2.5);  // Box 3
-    /// tree.add(4.0, 4.0, 5.0, 5.0);  // Box 4
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(0.0, 0.0, 2.0, 2.0);  // Room 0
+    /// tree.add(2.0, 0.0, 4.0, 2.0);  // Room 1, shares a wall with room 0
     /// tree.build();
     ///
     /// let mut results = Vec::new();
-    /// tree.query_intersecting_k(0.0, 0.0, 2.0, 2.0, 2, &mut results);
-    /// // Results contain at most 2 of the intersecting boxes
+    /// tree.query_intersecting_with_boundary(0.0, 0.0, 2.0, 2.0, Boundary::Open, &mut results);
+    /// assert_eq!(results, vec![0]);
     /// ```
-    pub fn query_intersecting_k(
-        &self,
-        min_x: f64,
-        min_y: f64,
-        max_x: f64,
-        max_y: f64,
-        k: usize,
-        results: &mut Vec<usize>,
-    ) {
-        results.clear();
-        if self.num_items == 0 || self.level_bounds.is_empty() || k == 0 {
-            return;
-        }
-
-        let mut queue = VecDeque::with_capacity(self.level_bounds.len() * 2);
-        let mut node_index = self.total_nodes - 1;
-        
-        loop {
-            if results.len() >= k {
-                break;
-            }
+    pub fn query_intersecting_with_boundary(&self, min_x: f64, min_y: f64, max_x: f64, max_y: f64, boundary: Boundary, results: &mut Vec<usize>) {
+        let intersects = |node_box: &Box| {
+            boundary.overlaps(node_box.min_x, node_box.max_x, min_x, max_x)
+                && boundary.overlaps(node_box.min_y, node_box.max_y, min_y, max_y)
+        };
+        self.collect_leaves(intersects, intersects, results);
+    }
 
-            let node_end = self.upper_bound(node_index);
-            let end_pos = (node_index + self.node_size).min(node_end);
-            
-            for pos in node_index..end_pos {
-                if results.len() >= k {
-                    break;
-                }
-
-                let node_box = self.get_box(pos);
-                
-                if max_x < node_box.min_x || max_y < node_box.min_y ||
-                   min_x > node_box.max_x || min_y > node_box.max_y {
-                    continue;
-                }
-                
-                let index = self.get_index(pos);
-                if pos < self.num_items {
-                    results.push(index as usize);
-                } else {
-                    queue.push_back((index >> 2) as usize);
-                }
-            }
-            
-            if queue.is_empty() {
-                break;
-            }
+    /// Finds all boxes that contain a point under the given boundary semantics.
+    ///
+    /// Same as `query_point()` with [`Boundary::Closed`]. With [`Boundary::Open`] points on
+    /// an edge are not inside; with [`Boundary::HalfOpen`] points on a min edge are inside
+    /// but points on a max edge are not, so a point on a shared edge hits exactly one box.
+    ///
+    /// # Arguments
+    /// * `x` - X coordinate of the query point
+    /// * `y` - Y coordinate of the query point
+    /// * `boundary` - Whether edges belong to the boxes
+    /// * `results` - Output vector; will be cleared and populated with indices of all
+    ///   boxes that contain the point
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(0.0, 0.0, 2.0, 2.0);  // Box 0
+    /// tree.add(2.0, 0.0, 4.0, 2.0);  // Box 1
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_point_with_boundary(2.0, 1.0, Boundary::HalfOpen, &mut results);
+    /// assert_eq!(results, vec![1]);
+    /// ```
+    pub fn query_point_with_boundary(&self, x: f64, y: f64, boundary: Boundary, results: &mut Vec<usize>) {
+        let contains = |node_box: &Box| {
+            boundary.contains_value(node_box.min_x, node_box.max_x, x)
+                && boundary.contains_value(node_box.min_y, node_box.max_y, y)
+        };
+        self.collect_leaves(contains, contains, results);
+    }
 
-            node_index = queue.pop_front().unwrap();
-        }
+    /// Finds all boxes that contain a rectangle under the given boundary semantics.
+    ///
+    /// Same as `query_contain()` with [`Boundary::Closed`] or [`Boundary::HalfOpen`]. With
+    /// [`Boundary::Open`] the query rectangle must not touch the edges of a result box.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    /// * `boundary` - Whether edges belong to the boxes
+    /// * `results` - Output vector; will be cleared and populated with indices of boxes
+    ///   that contain the query rectangle
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(0.0, 0.0, 4.0, 4.0);  // Box 0
+    /// tree.add(1.0, 1.0, 3.0, 3.0);  // Box 1
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_contain_with_boundary(1.0, 1.0, 2.0, 2.0, Boundary::Open, &mut results);
+    /// assert_eq!(results, vec![0]);
+    /// ```
+    pub fn query_contain_with_boundary(&self, min_x: f64, min_y: f64, max_x: f64, max_y: f64, boundary: Boundary, results: &mut Vec<usize>) {
+        let contains = |node_box: &Box| {
+            boundary.contains_range(node_box.min_x, node_box.max_x, min_x, max_x)
+                && boundary.contains_range(node_box.min_y, node_box.max_y, min_y, max_y)
+        };
+        self.collect_leaves(contains, contains, results);
     }
 
-    /// Finds all boxes that intersect with a circular region.
+    /// Finds all boxes contained within a rectangle under the given boundary semantics.
     ///
-    /// This query returns all boxes whose bounding boxes intersect with the circle
-    /// centered at `(center_x, center_y)` with the given `radius`. The distance check
-    /// uses the Euclidean distance from the circle's center to the nearest point in each box.
-    /// This is useful for circular range queries, area-of-effect searches, and radial filtering.
+    /// Same as `query_contained_within()` with [`Boundary::Closed`] or [`Boundary::HalfOpen`].
+    /// With [`Boundary::Open`] result boxes must not touch the edges of the query rectangle.
     ///
     /// # Arguments
-    /// * `center_x` - X coordinate of circle center
-    /// * `center_y` - Y coordinate of circle center
-    /// * `radius` - Radius of the circular region
-    /// * `results` - Output vector; will be cleared and populated with indices of all boxes
-    ///              intersecting the circular region
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    /// * `boundary` - Whether edges belong to the boxes
+    /// * `results` - Output vector; will be cleared and populated with indices of boxes
+    ///   that are contained within the query rectangle
     ///
     /// # Example
     /// ```
     /// use aabb::prelude::*;
-    /// let mut tree = AABB::with_capacity(3);
-    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
-    /// tree.add(1.0, 1.0, 2.0, 2.0);  // Box 1
-    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 2
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0, touches the query edge
+    /// tree.add(1.0, 1.0, 2.0, 2.0);  // Box 1, strictly inside
     /// tree.build();
     ///
     /// let mut results = Vec::new();
-    /// tree.query_circle(1.0, 1.0, 1.5, &mut results);
-    /// // Results include boxes 0 and 1 (within circle), but not box 2
+    /// tree.query_contained_within_with_boundary(0.0, 0.0, 3.0, 3.0, Boundary::Open, &mut results);
+    /// assert_eq!(results, vec![1]);
     /// ```
-    pub fn query_circle(&self, center_x: f64, center_y: f64, radius: f64, results: &mut Vec<usize>) {
+    pub fn query_contained_within_with_boundary(&self, min_x: f64, min_y: f64, max_x: f64, max_y: f64, boundary: Boundary, results: &mut Vec<usize>) {
+        let query = Box::new(min_x, min_y, max_x, max_y);
+        self.collect_leaves(
+            |node_box| boxes_intersect(node_box, &query),
+            |node_box| {
+                boundary.contains_range(min_x, max_x, node_box.min_x, node_box.max_x)
+                    && boundary.contains_range(min_y, max_y, node_box.min_y, node_box.max_y)
+            },
+            results,
+        );
+    }
+
+    /// Collect the IDs of leaves accepted by `accept`, descending only into parent nodes
+    /// accepted by `descend`
+    fn collect_leaves<D, A>(&self, descend: D, accept: A, results: &mut Vec<usize>)
+    where
+        D: Fn(&Box) -> bool,
+        A: Fn(&Box) -> bool,
+    {
         results.clear();
-        if self.num_items == 0 || self.level_bounds.is_empty() || radius < 0.0 {
+        if self.num_items == 0 || self.level_bounds.is_empty() {
             return;
         }
-        
-        let radius_sq = radius * radius;
-        let mut queue = VecDeque::new();
-        let mut node_index = self.total_nodes - 1;
-        
-        loop {
-            let node_end = self.upper_bound(node_index);
-            let end_pos = (node_index + self.node_size).min(node_end);
-            
-            for pos in node_index..end_pos {
-                let node_box = self.get_box(pos);
-                // Distance from circle center to box
-                let dx = self.axis_distance(center_x, node_box.min_x, node_box.max_x);
-                let dy = self.axis_distance(center_y, node_box.min_y, node_box.max_y);
-                let dist_sq = dx * dx + dy * dy;
 
-                if dist_sq <= radius_sq {
-                    let index = self.get_index(pos);
-                    if pos >= self.num_items {
-                        queue.push_back((index >> 2) as usize);
-                    } else {
-                        results.push(index as usize);
-                    }
+        let mut stack = vec![self.total_nodes - 1];
+        while let Some(pos) = stack.pop() {
+            let node_box = self.get_box(pos);
+            if pos < self.num_items {
+                if accept(&node_box) {
+                    results.push(self.get_index(pos) as usize);
                 }
+            } else if descend(&node_box) {
+                stack.extend(self.children(pos));
             }
-            
-            if queue.is_empty() {
-                break;
-            }
-
-            node_index = queue.pop_front().unwrap();
         }
     }
 
-    /// Finds all point items (stored as (x, x, y, y)) within a circular region.
+    /// Finds all boxes that intersect a rectangle on a wrap-around world.
     ///
-    /// This is an optimized version of `query_circle()` specifically for point data.
-    /// For point items where min_x==max_x and min_y==max_y, this method computes
-    /// distances directly without the axis_distance() helper function for leaf nodes.
-    /// For parent nodes, axis_distance is used to maintain correctness.
-    /// This optimization provides approximately 30% faster queries on point clouds.
+    /// Like `query_intersecting()`, but with the period set by `set_period()` the rectangle
+    /// and the boxes also match across the seam. Each item is returned once. Without a period
+    /// this is the same as `query_intersecting()`.
     ///
-    /// **Important:** This method assumes all items in the tree are stored as points.
-    /// If the tree contains mixed data (both points and boxes), use `query_circle()` instead
-    /// for correct results. Calling this on non-point data will produce incorrect results.
+    /// # Arguments
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    /// * `results` - Output vector; will be cleared and populated with matching box indices
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(1.0, 1.0, 2.0, 2.0);    // Box 0, just right of the seam
+    /// tree.add(50.0, 50.0, 51.0, 51.0); // Box 1
+    /// tree.build();
+    /// tree.set_period(100.0, 100.0);
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_intersecting_wrapped(95.0, 0.0, 105.0, 5.0, &mut results);
+    /// assert_eq!(results, vec![0]);
+    /// ```
+    pub fn query_intersecting_wrapped(&self, min_x: f64, min_y: f64, max_x: f64, max_y: f64, results: &mut Vec<usize>) {
+        let Some(period) = self.period else {
+            self.query_intersecting(min_x, min_y, max_x, max_y, results);
+            return;
+        };
+        let intersects = |node_box: &Box| {
+            wrapped_axis_overlaps(min_x, max_x, node_box.min_x, node_box.max_x, period.0)
+                && wrapped_axis_overlaps(min_y, max_y, node_box.min_y, node_box.max_y, period.1)
+        };
+        self.collect_leaves(intersects, intersects, results);
+    }
+
+    /// Finds all boxes within `radius` of a point on a wrap-around world.
+    ///
+    /// Like `query_circle()`, but with the period set by `set_period()` distances are
+    /// measured the shortest way around. Each item is returned once. Without a period this is
+    /// the same as `query_circle()`.
     ///
     /// # Arguments
     /// * `center_x` - X coordinate of circle center
     /// * `center_y` - Y coordinate of circle center
     /// * `radius` - Radius of the circular region
-    /// * `results` - Output vector; will be cleared and populated with indices of all point items
-    ///              within the circular region
+    /// * `results` - Output vector; will be cleared and populated with matching box indices
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add_point(2.0, 50.0);   // Point 0
+    /// tree.add_point(90.0, 50.0);  // Point 1
+    /// tree.build();
+    /// tree.set_period(100.0, 100.0);
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_circle_wrapped(98.0, 50.0, 5.0, &mut results);
+    /// assert_eq!(results, vec![0]);  // 4 units away across the seam
+    /// ```
+    pub fn query_circle_wrapped(&self, center_x: f64, center_y: f64, radius: f64, results: &mut Vec<usize>) {
+        let Some(period) = self.period else {
+            self.query_circle(center_x, center_y, radius, results);
+            return;
+        };
+        if radius < 0.0 {
+            results.clear();
+            return;
+        }
+        let radius_sq = radius * radius;
+        let within = |node_box: &Box| {
+            let dx = wrapped_axis_distance(center_x, node_box.min_x, node_box.max_x, period.0);
+            let dy = wrapped_axis_distance(center_y, node_box.min_y, node_box.max_y, period.1);
+            dx * dx + dy * dy <= radius_sq
+        };
+        self.collect_leaves(within, within, results);
+    }
+
+    /// Finds the K nearest boxes to a point on a wrap-around world.
+    ///
+    /// Like `query_nearest_k()`, but with the period set by `set_period()` distances are
+    /// measured the shortest way around. Without a period this is the same as
+    /// `query_nearest_k()`.
+    ///
+    /// # Arguments
+    /// * `point_x` - X coordinate of the query point
+    /// * `point_y` - Y coordinate of the query point
+    /// * `k` - Number of nearest boxes to find
+    /// * `results` - Output vector; will be cleared and populated with K nearest box indices,
+    ///   sorted by distance (closest first)
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add_point(1.0, 50.0);   // Ship 0, just right of the seam
+    /// tree.add_point(60.0, 50.0);  // Ship 1
+    /// tree.build();
+    /// tree.set_period(100.0, 100.0);
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_nearest_k_wrapped(98.0, 50.0, 1, &mut results);
+    /// assert_eq!(results, vec![0]);  // 3 units away across the seam
+    /// ```
+    pub fn query_nearest_k_wrapped(&self, point_x: f64, point_y: f64, k: usize, results: &mut Vec<usize>) {
+        let Some((width, height)) = self.period else {
+            self.query_nearest_k(point_x, point_y, k, results);
+            return;
+        };
+        self.nearest_k_by(
+            |node_box| {
+                let dx = wrapped_axis_distance(point_x, node_box.min_x, node_box.max_x, width);
+                let dy = wrapped_axis_distance(point_y, node_box.min_y, node_box.max_y, height);
+                dx * dx + dy * dy
+            },
+            k,
+            results,
+        );
+    }
+
+    /// Finds all boxes that intersect or lie inside an arbitrary simple polygon.
+    ///
+    /// The polygon is given as a list of vertices and is implicitly closed (the last
+    /// vertex connects back to the first). Vertices may be in either winding order.
+    /// Tree nodes are pruned with exact polygon-box tests, and once a node lies fully
+    /// inside the polygon its children are accepted without further polygon tests.
+    /// This is useful for lasso selection or filtering by administrative boundaries.
+    ///
+    /// # Arguments
+    /// * `polygon` - Polygon vertices as `(x, y)`; fewer than 3 vertices yields no results
+    /// * `mode` - [`PolygonMode::Intersecting`] or [`PolygonMode::Contained`]
+    /// * `results` - Output vector; will be cleared and populated with matching box indices
     ///
     /// # Example
     /// ```
     /// use aabb::prelude::*;
     /// let mut tree = AABB::with_capacity(3);
-    /// tree.add(0.0, 0.0, 0.0, 0.0);  // Point 0
-    /// tree.add(1.0, 1.0, 1.0, 1.0);  // Point 1
-    /// tree.add(5.0, 5.0, 5.0, 5.0);  // Point 2
+    /// tree.add(1.0, 1.0, 2.0, 2.0);  // Box 0 (inside the triangle)
+    /// tree.add(7.0, 1.0, 9.0, 3.0);  // Box 1 (crosses the hypotenuse)
+    /// tree.add(8.0, 8.0, 9.0, 9.0);  // Box 2 (outside)
     /// tree.build();
     ///
+    /// let triangle = [(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)];
     /// let mut results = Vec::new();
-    /// tree.query_circle_points(0.0, 0.0, 1.5, &mut results);
-    /// // Results include points 0 and 1 (within radius)
+    /// tree.query_polygon(&triangle, PolygonMode::Intersecting, &mut results);
+    /// results.sort();
+    /// assert_eq!(results, vec![0, 1]);
+    ///
+    /// tree.query_polygon(&triangle, PolygonMode::Contained, &mut results);
+    /// assert_eq!(results, vec![0]);
     /// ```
-    pub fn query_circle_points(&self, center_x: f64, center_y: f64, radius: f64, results: &mut Vec<usize>) {
+    pub fn query_polygon(&self, polygon: &[(f64, f64)], mode: PolygonMode, results: &mut Vec<usize>) {
         results.clear();
-        if self.num_items == 0 || self.level_bounds.is_empty() || radius < 0.0 {
+        if self.num_items == 0 || self.level_bounds.is_empty() || polygon.len() < 3 {
             return;
         }
 
-        let radius_sq = radius * radius;
+        // Polygon bounding box for cheap rejection before the exact tests
+        let mut poly_box = Box::new(f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(x, y) in polygon {
+            poly_box.min_x = poly_box.min_x.min(x);
+            poly_box.min_y = poly_box.min_y.min(y);
+            poly_box.max_x = poly_box.max_x.max(x);
+            poly_box.max_y = poly_box.max_y.max(y);
+        }
+
+        // Queue entries carry whether the node is already known to be inside the polygon
         let mut queue = VecDeque::new();
         let mut node_index = self.total_nodes - 1;
-        
+        let mut inside = false;
+
         loop {
             let node_end = self.upper_bound(node_index);
             let end_pos = (node_index + self.node_size).min(node_end);
-            
+
             for pos in node_index..end_pos {
                 let node_box = self.get_box(pos);
-                
-                // Use axis_distance for parent nodes (they may have min_x < max_x)
-                // Use direct distance only for leaf nodes (points have min_x == max_x)
-                let dist_sq = if pos < self.num_items {
-                    // Leaf node - assume it's a point with min_x == max_x and min_y == max_y
-                    let dx = center_x - node_box.min_x;
-                    let dy = center_y - node_box.min_y;
-                    dx * dx + dy * dy
+
+                let child_inside = if inside {
+                    true
                 } else {
-                    // Parent node - use axis_distance for safety
-                    let dx = self.axis_distance(center_x, node_box.min_x, node_box.max_x);
-                    let dy = self.axis_distance(center_y, node_box.min_y, node_box.max_y);
-                    dx * dx + dy * dy
+                    if poly_box.max_x < node_box.min_x || poly_box.max_y < node_box.min_y
+                        || poly_box.min_x > node_box.max_x || poly_box.min_y > node_box.max_y
+                    {
+                        continue;
+                    }
+                    if !box_intersects_polygon(&node_box, polygon) {
+                        continue;
+                    }
+                    box_inside_polygon(&node_box, polygon)
                 };
 
-                if dist_sq <= radius_sq {
-                    let index = self.get_index(pos);
-                    if pos >= self.num_items {
-                        queue.push_back((index >> 2) as usize);
-                    } else {
-                        results.push(index as usize);
-                    }
+                let index = self.get_index(pos);
+                if pos >= self.num_items {
+                    queue.push_back(((index >> 2) as usize, child_inside));
+                } else if child_inside || mode == PolygonMode::Intersecting {
+                    results.push(index as usize);
                 }
             }
-            
-            if queue.is_empty() {
-                break;
-            }
 
-            node_index = queue.pop_front().unwrap();
+            match queue.pop_front() {
+                Some((next, next_inside)) => {
+                    node_index = next;
+                    inside = next_inside;
+                }
+                None => break,
+            }
         }
     }
 
-    /// Finds all boxes that intersect with a rectangle's directional movement path.
+    /// Finds all boxes that intersect a rotated (oriented) rectangle.
     ///
-    /// This query simulates moving a rectangle from its initial position in a given direction
-    /// for a specified distance, and returns all boxes intersecting the swept path. The sweep
-    /// region is the bounding box of the rectangle at its start and end positions.
-    /// This is useful for collision detection along movement paths, ray casting, and
-    /// predictive spatial queries (e.g., finding obstacles in a moving object's path).
+    /// The query rectangle is described by its center, its half extents along its own
+    /// axes, and a rotation angle in radians (counter-clockwise). Node and leaf boxes are
+    /// tested with the separating axis theorem, so unlike querying the rotated rectangle's
+    /// axis-aligned bounds, no boxes outside the rotated footprint are returned.
+    /// Boxes touching the rectangle at edges or corners count as intersecting.
+    /// This is useful for vehicle footprints, camera frusta projected to 2D, or
+    /// selection with a rotated marquee.
     ///
     /// # Arguments
-    /// * `min_x` - Left edge of the rectangle
-    /// * `min_y` - Bottom edge of the rectangle
-    /// * `max_x` - Right edge of the rectangle
-    /// * `max_y` - Top edge of the rectangle
-    /// * `dir_x` - X component of movement direction vector
-    /// * `dir_y` - Y component of movement direction vector
-    /// * `distance` - Distance to move in the direction (direction is normalized internally)
-    /// * `results` - Output vector; will be cleared and populated with indices of all boxes
-    ///              intersecting the swept movement path
+    /// * `center_x` - X coordinate of the rectangle center
+    /// * `center_y` - Y coordinate of the rectangle center
+    /// * `half_width` - Half extent along the rectangle's local x axis
+    /// * `half_height` - Half extent along the rectangle's local y axis
+    /// * `angle` - Rotation of the rectangle in radians (counter-clockwise)
+    /// * `results` - Output vector; will be cleared and populated with matching box indices
     ///
     /// # Example
     /// ```
     /// use aabb::prelude::*;
     /// let mut tree = AABB::with_capacity(3);
-    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
-    /// tree.add(3.0, 0.0, 4.0, 1.0);  // Box 1 (in the path)
-    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 2 (not in the path)
+    /// tree.add(4.0, 4.0, 5.0, 5.0);  // Box 0 (on the diagonal)
+    /// tree.add(8.0, 0.0, 9.0, 1.0);  // Box 1 (inside the axis-aligned bounds only)
+    /// tree.add(20.0, 20.0, 21.0, 21.0);  // Box 2 (far away)
     /// tree.build();
     ///
     /// let mut results = Vec::new();
-    /// // Move rectangle from (0,0)-(1,1) to the right for distance 3
-    /// tree.query_in_direction(0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 3.0, &mut results);
-    /// // Results include boxes 0 and 1
+    /// // Long thin rectangle along the diagonal y = x
+    /// tree.query_oriented_box(5.0, 5.0, 7.0, 0.5, std::f64::consts::FRAC_PI_4, &mut results);
+    /// assert_eq!(results, vec![0]);
     /// ```
-    pub fn query_in_direction(
+    pub fn query_oriented_box(
         &self,
-        min_x: f64,
-        min_y: f64,
-        max_x: f64,
-        max_y: f64,
-        dir_x: f64,
-        dir_y: f64,
-        distance: f64,
+        center_x: f64,
+        center_y: f64,
+        half_width: f64,
+        half_height: f64,
+        angle: f64,
         results: &mut Vec<usize>,
     ) {
         results.clear();
-        if self.num_items == 0 || self.level_bounds.is_empty() || distance < 0.0 {
-            return;
-        }
-        
-        // Normalize direction vector
-        let dir_len_sq = dir_x * dir_x + dir_y * dir_y;
-        if dir_len_sq <= 0.0 {
+        if self.num_items == 0 || self.level_bounds.is_empty() || half_width < 0.0 || half_height < 0.0 {
             return;
         }
-        let dir_len = dir_len_sq.sqrt();
-        let norm_dir_x = dir_x / dir_len;
-        let norm_dir_y = dir_y / dir_len;
-        
-        // Calculate movement vector
-        let dx = norm_dir_x * distance;
-        let dy = norm_dir_y * distance;
-        let sweep_min_x = min_x.min(min_x + dx);
-        let sweep_min_y = min_y.min(min_y + dy);
-        let sweep_max_x = max_x.max(max_x + dx);
-        let sweep_max_y = max_y.max(max_y + dy);
+
+        let obb = OrientedBox::new(center_x, center_y, half_width, half_height, angle);
 
         let mut queue = VecDeque::new();
         let mut node_index = self.total_nodes - 1;
-        
+
         loop {
             let node_end = self.upper_bound(node_index);
             let end_pos = (node_index + self.node_size).min(node_end);
-            
+
             for pos in node_index..end_pos {
                 let node_box = self.get_box(pos);
-                
-                // Check if box intersects the sweep area (AABB intersection)
-                if sweep_max_x < node_box.min_x || sweep_max_y < node_box.min_y ||
-                   sweep_min_x > node_box.max_x || sweep_min_y > node_box.max_y {
+                if !obb.intersects(&node_box) {
                     continue;
                 }
-                
+
                 let index = self.get_index(pos);
                 if pos >= self.num_items {
                     queue.push_back((index >> 2) as usize);
@@ -1659,195 +2659,1961 @@ impl HilbertRTree {
                     results.push(index as usize);
                 }
             }
-            
-            if queue.is_empty() {
-                break;
+
+            match queue.pop_front() {
+                Some(next) => node_index = next,
+                None => break,
             }
-            
-            node_index = queue.pop_front().unwrap();
         }
     }
 
-    /// Finds the K nearest boxes intersecting a rectangle's directional movement path.
+    /// Finds the first K intersecting boxes within a rectangular region.
     ///
-    /// This query finds boxes that intersect with a moving rectangle's swept path and
-    /// returns the K closest ones ordered by distance along the movement direction.
-    /// Distance is computed as the projection of each box's center onto the normalized
-    /// direction vector, making results ordered by "how far along the path" each box is.
-    /// This is useful for ordered collision detection, finding the closest obstacles
-    /// in a movement path, or sequential hit detection in a sweep.
+    /// This query finds boxes intersecting a query rectangle and stops after collecting K results.
+    /// Unlike `query_intersecting` which finds all matches, this variant returns early when K
+    /// results are found, making it more efficient when only a limited number of results are needed.
+    /// Results are included in the order they are encountered during tree traversal.
     ///
     /// # Arguments
-    /// * `min_x` - Left edge of the rectangle
-    /// * `min_y` - Bottom edge of the rectangle
-    /// * `max_x` - Right edge of the rectangle
-    /// * `max_y` - Top edge of the rectangle
-    /// * `dir_x` - X component of movement direction vector
-    /// * `dir_y` - Y component of movement direction vector
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
     /// * `k` - Maximum number of results to return
-    /// * `distance` - Distance to move in the direction (direction is normalized internally)
-    /// * `results` - Output vector; will be cleared and populated with up to K nearest box indices
-    ///              sorted by distance along the movement direction
+    /// * `results` - Output vector; will be cleared and populated with up to K matching box indices
     ///
     /// # Example
     /// ```
     /// use aabb::prelude::*;
-    /// let mut tree = AABB::with_capacity(4);
+    /// let mut tree = AABB::with_capacity(5);
     /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
-    /// tree.add(2.0, 0.0, 3.0, 1.0);  // Box 1 (first in path)
-    /// tree.add(4.0, 0.0, 5.0, 1.0);  // Box 2 (second in path)
-    /// tree.add(6.0, 6.0, 7.0, 7.0);  // Box 3 (not in path)
+    /// tree.add(0.5, 0.5, 1.5, 1.5);  // Box 1
+    /// tree.add(1.0, 1.0, 2.0, 2.0);  // Box 2
+    /// tree.add(1.5, 1.5, 2.5, 2.5);  // Box 3
+    /// tree.add(4.0, 4.0, 5.0, 5.0);  // Box 4
     /// tree.build();
     ///
     /// let mut results = Vec::new();
-    /// // Move rectangle right, find 2 nearest obstacles
-    /// tree.query_in_direction_k(0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 2, 10.0, &mut results);
-    /// // Results contain boxes 1 and 2 (ordered by distance along direction)
+    /// tree.query_intersecting_k(0.0, 0.0, 2.0, 2.0, 2, &mut results);
+    /// // Results contain at most 2 of the intersecting boxes
     /// ```
-    pub fn query_in_direction_k(
+    pub fn query_intersecting_k(
         &self,
         min_x: f64,
         min_y: f64,
         max_x: f64,
         max_y: f64,
-        dir_x: f64,
-        dir_y: f64,
         k: usize,
-        distance: f64,
         results: &mut Vec<usize>,
     ) {
         results.clear();
-        if self.num_items == 0 || self.level_bounds.is_empty() || distance < 0.0 || k == 0 {
+        if self.num_items == 0 || self.level_bounds.is_empty() || k == 0 {
             return;
         }
 
-        // Normalize direction vector
-        let dir_len_sq = dir_x * dir_x + dir_y * dir_y;
-        if dir_len_sq <= 0.0 {
-            results.clear();
-            return;
-        }
-        let dir_len = dir_len_sq.sqrt();
-        let norm_dir_x = dir_x / dir_len;
-        let norm_dir_y = dir_y / dir_len;
+        let mut queue = VecDeque::with_capacity(self.level_bounds.len() * 2);
+        let mut node_index = self.total_nodes - 1;
         
-        // Calculate movement vector
-        let dx = norm_dir_x * distance;
-        let dy = norm_dir_y * distance;
-        let sweep_min_x = min_x.min(min_x + dx);
-        let sweep_min_y = min_y.min(min_y + dy);
-        let sweep_max_x = max_x.max(max_x + dx);
-        let sweep_max_y = max_y.max(max_y + dy);
-
-        let mut candidates: Vec<(f64, usize)> = Vec::new();
-
-        let mut queue = Vec::with_capacity(self.level_bounds.len() * 2);
-        queue.push(self.total_nodes - 1);
+        loop {
+            if results.len() >= k {
+                break;
+            }
 
-        while let Some(node_idx) = queue.pop() {
-            let node_end = self.upper_bound(node_idx);
-            let end_pos = (node_idx + self.node_size).min(node_end);
+            let node_end = self.upper_bound(node_index);
+            let end_pos = (node_index + self.node_size).min(node_end);
+            
+            for pos in node_index..end_pos {
+                if results.len() >= k {
+                    break;
+                }
 
-            for pos in node_idx..end_pos {
                 let node_box = self.get_box(pos);
                 
-                // Check if box intersects the sweep area (AABB intersection)
-                if sweep_max_x < node_box.min_x || sweep_max_y < node_box.min_y ||
-                   sweep_min_x > node_box.max_x || sweep_min_y > node_box.max_y {
+                if max_x < node_box.min_x || max_y < node_box.min_y ||
+                   min_x > node_box.max_x || min_y > node_box.max_y {
                     continue;
                 }
-
-                let index = self.get_index(pos) as usize;
-
+                
+                let index = self.get_index(pos);
                 if pos < self.num_items {
-                    // Calculate distance to box center along direction
-                    let box_center_x = (node_box.min_x + node_box.max_x) / 2.0;
-                    let box_center_y = (node_box.min_y + node_box.max_y) / 2.0;
-                    
-                    let relative_x = box_center_x - min_x;
-                    let relative_y = box_center_y - min_y;
-                    
-                    // Distance along direction (use normalized direction)
-                    let dist_along_dir = relative_x * norm_dir_x + relative_y * norm_dir_y;
-                    candidates.push((dist_along_dir, index));
+                    results.push(index as usize);
                 } else {
-                    queue.push(index >> 2);
+                    queue.push_back((index >> 2) as usize);
                 }
             }
-        }
-
-        // Partial sort: get K smallest elements by distance
-        results.clear();
-        
-        if candidates.len() <= k {
-            // Fewer candidates than K, just sort all
-            candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
-            for (_, idx) in candidates {
-                results.push(idx);
-            }
-        } else {
-            // More candidates than K, use partial sort
-            candidates.select_nth_unstable_by(k - 1, |a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
-            // Now split at position k and sort only the K elements
-            let (k_smallest, _) = candidates.split_at_mut(k);
-            k_smallest.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
-            for (_, idx) in k_smallest {
-                results.push(*idx);
+            
+            if queue.is_empty() {
+                break;
             }
+
+            node_index = queue.pop_front().unwrap();
         }
     }
 
-    /// Retrieves the bounding box for an item by its ID.
+    /// Finds all boxes that intersect with a circular region.
     ///
-    /// Returns the axis-aligned bounding box (min_x, min_y, max_x, max_y) for the item
-    /// at the given ID. This allows users to retrieve stored bounding boxes without
-    /// needing to store them separately.
+    /// This query returns all boxes whose bounding boxes intersect with the circle
+    /// centered at `(center_x, center_y)` with the given `radius`. The distance check
+    /// uses the Euclidean distance from the circle's center to the nearest point in each box.
+    /// This is useful for circular range queries, area-of-effect searches, and radial filtering.
     ///
     /// # Arguments
-    /// * `item_id` - The ID of the item (0 to `num_items - 1`)
-    ///
-    /// # Returns
-    /// `Some((min_x, min_y, max_x, max_y))` if the item exists, `None` if the ID is out of bounds.
+    /// * `center_x` - X coordinate of circle center
+    /// * `center_y` - Y coordinate of circle center
+    /// * `radius` - Radius of the circular region
+    /// * `results` - Output vector; will be cleared and populated with indices of all boxes
+    ///              intersecting the circular region
     ///
     /// # Example
     /// ```
     /// use aabb::prelude::*;
-    /// let mut tree = AABB::with_capacity(2);
-    /// tree.add(0.0, 0.0, 2.0, 2.0);  // Item 0
-    /// tree.add(1.0, 1.0, 3.0, 3.0);  // Item 1
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(1.0, 1.0, 2.0, 2.0);  // Box 1
+    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 2
     /// tree.build();
     ///
-    /// let bbox = tree.get(0).unwrap();
-    /// assert_eq!(bbox, (0.0, 0.0, 2.0, 2.0));
+    /// let mut results = Vec::new();
+    /// tree.query_circle(1.0, 1.0, 1.5, &mut results);
+    /// // Results include boxes 0 and 1 (within circle), but not box 2
     /// ```
-    // Note: get() method removed to eliminate sorted_order dependency
-    // Use spatial query methods instead for accessing data
+    pub fn query_circle(&self, center_x: f64, center_y: f64, radius: f64, results: &mut Vec<usize>) {
+        results.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() || radius < 0.0 {
+            return;
+        }
+        
+        let radius_sq = radius * radius;
+        let mut queue = VecDeque::new();
+        let mut node_index = self.total_nodes - 1;
+        
+        loop {
+            let node_end = self.upper_bound(node_index);
+            let end_pos = (node_index + self.node_size).min(node_end);
+            
+            for pos in node_index..end_pos {
+                let node_box = self.get_box(pos);
+                // Distance from circle center to box
+                let dx = self.axis_distance(center_x, node_box.min_x, node_box.max_x);
+                let dy = self.axis_distance(center_y, node_box.min_y, node_box.max_y);
+                let dist_sq = dx * dx + dy * dy;
 
-    /// Get box at position using read_unaligned
-    #[inline(always)]
-    pub(crate) fn get_box(&self, pos: usize) -> Box {
-        let idx = HEADER_SIZE + pos * size_of::<Box>();
-        unsafe {
-            std::ptr::read_unaligned(&self.data[idx] as *const u8 as *const Box)
+                if dist_sq <= radius_sq {
+                    let index = self.get_index(pos);
+                    if pos >= self.num_items {
+                        queue.push_back((index >> 2) as usize);
+                    } else {
+                        results.push(index as usize);
+                    }
+                }
+            }
+            
+            if queue.is_empty() {
+                break;
+            }
+
+            node_index = queue.pop_front().unwrap();
         }
     }
 
-    /// Get 4 boxes at once for batch processing - single read_unaligned call
-    #[allow(dead_code)]
-    #[inline]
+    /// Finds all point items (stored as (x, x, y, y)) within a circular region.
+    ///
+    /// This is an optimized version of `query_circle()` specifically for point data.
+    /// For point items where min_x==max_x and min_y==max_y, this method computes
+    /// distances directly without the axis_distance() helper function for leaf nodes.
+    /// For parent nodes, axis_distance is used to maintain correctness.
+    /// This optimization provides approximately 30% faster queries on point clouds.
+    ///
+    /// **Important:** This method assumes all items in the tree are stored as points.
+    /// If the tree contains mixed data (both points and boxes), use `query_circle()` instead
+    /// for correct results. Calling this on non-point data will produce incorrect results.
+    ///
+    /// # Arguments
+    /// * `center_x` - X coordinate of circle center
+    /// * `center_y` - Y coordinate of circle center
+    /// * `radius` - Radius of the circular region
+    /// * `results` - Output vector; will be cleared and populated with indices of all point items
+    ///              within the circular region
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 0.0, 0.0);  // Point 0
+    /// tree.add(1.0, 1.0, 1.0, 1.0);  // Point 1
+    /// tree.add(5.0, 5.0, 5.0, 5.0);  // Point 2
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_circle_points(0.0, 0.0, 1.5, &mut results);
+    /// // Results include points 0 and 1 (within radius)
+    /// ```
+    pub fn query_circle_points(&self, center_x: f64, center_y: f64, radius: f64, results: &mut Vec<usize>) {
+        results.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() || radius < 0.0 {
+            return;
+        }
+
+        let radius_sq = radius * radius;
+        let mut queue = VecDeque::new();
+        let mut node_index = self.total_nodes - 1;
+        
+        loop {
+            let node_end = self.upper_bound(node_index);
+            let end_pos = (node_index + self.node_size).min(node_end);
+            
+            for pos in node_index..end_pos {
+                let node_box = self.get_box(pos);
+                
+                // Use axis_distance for parent nodes (they may have min_x < max_x)
+                // Use direct distance only for leaf nodes (points have min_x == max_x)
+                let dist_sq = if pos < self.num_items {
+                    // Leaf node - assume it's a point with min_x == max_x and min_y == max_y
+                    let dx = center_x - node_box.min_x;
+                    let dy = center_y - node_box.min_y;
+                    dx * dx + dy * dy
+                } else {
+                    // Parent node - use axis_distance for safety
+                    let dx = self.axis_distance(center_x, node_box.min_x, node_box.max_x);
+                    let dy = self.axis_distance(center_y, node_box.min_y, node_box.max_y);
+                    dx * dx + dy * dy
+                };
+
+                if dist_sq <= radius_sq {
+                    let index = self.get_index(pos);
+                    if pos >= self.num_items {
+                        queue.push_back((index >> 2) as usize);
+                    } else {
+                        results.push(index as usize);
+                    }
+                }
+            }
+            
+            if queue.is_empty() {
+                break;
+            }
+
+            node_index = queue.pop_front().unwrap();
+        }
+    }
+
+    /// Finds the K nearest boxes to a point using the given distance metric.
+    ///
+    /// Same as `query_nearest_k()` when called with [`Euclidean`], but the metric can be
+    /// any [`Metric`], e.g. [`Manhattan`] for grid-based pathing or [`Chebyshev`] for
+    /// chessboard-like moves. Distances are measured from the point to the nearest
+    /// point of each box, so boxes containing the point have distance 0.
+    ///
+    /// # Arguments
+    /// * `point_x` - X coordinate of the query point
+    /// * `point_y` - Y coordinate of the query point
+    /// * `k` - Number of nearest boxes to find
+    /// * `metric` - Distance metric
+    /// * `results` - Output vector; will be cleared and populated with K nearest box indices,
+    ///   sorted by distance (closest first)
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add_point(3.0, 3.0);  // Point 0: L1 distance 6, L∞ distance 3
+    /// tree.add_point(5.0, 0.0);  // Point 1: L1 distance 5, L∞ distance 5
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_nearest_k_with_metric(0.0, 0.0, 1, Manhattan, &mut results);
+    /// assert_eq!(results, vec![1]);
+    /// tree.query_nearest_k_with_metric(0.0, 0.0, 1, Chebyshev, &mut results);
+    /// assert_eq!(results, vec![0]);
+    /// ```
+    pub fn query_nearest_k_with_metric<M: Metric>(&self, point_x: f64, point_y: f64, k: usize, metric: M, results: &mut Vec<usize>) {
+        self.nearest_k_by(
+            |node_box| {
+                metric.distance(
+                    self.axis_distance(point_x, node_box.min_x, node_box.max_x),
+                    self.axis_distance(point_y, node_box.min_y, node_box.max_y),
+                )
+            },
+            k,
+            results,
+        );
+    }
+
+    /// Finds the K nearest point items (stored as (x, x, y, y)) using the given distance metric.
+    ///
+    /// Point counterpart of `query_nearest_k_with_metric()`: parent nodes are measured as
+    /// boxes, but leaf distances are computed directly from the stored point coordinates.
+    ///
+    /// **Important:** This method assumes all items in the tree are stored as points.
+    ///
+    /// # Arguments
+    /// * `point_x` - X coordinate of the query point
+    /// * `point_y` - Y coordinate of the query point
+    /// * `k` - Number of nearest points to find
+    /// * `metric` - Distance metric
+    /// * `results` - Output vector; will be cleared and populated with K nearest point indices,
+    ///   sorted by distance (closest first)
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add_point(1.0, 1.0);  // Point 0: L1 distance 2
+    /// tree.add_point(0.0, 3.0);  // Point 1: L1 distance 3
+    /// tree.add_point(1.5, 0.0);  // Point 2: L1 distance 1.5
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_nearest_k_points_with_metric(0.0, 0.0, 2, Manhattan, &mut results);
+    /// assert_eq!(results, vec![2, 0]);
+    /// ```
+    pub fn query_nearest_k_points_with_metric<M: Metric>(&self, point_x: f64, point_y: f64, k: usize, metric: M, results: &mut Vec<usize>) {
+        self.nearest_k_by_leaf(
+            |node_box| {
+                metric.distance(
+                    self.axis_distance(point_x, node_box.min_x, node_box.max_x),
+                    self.axis_distance(point_y, node_box.min_y, node_box.max_y),
+                )
+            },
+            |point| metric.distance((point.min_x - point_x).abs(), (point.min_y - point_y).abs()),
+            k,
+            results,
+        );
+    }
+
+    /// Finds all boxes within a radius of a point using the given distance metric.
+    ///
+    /// Same as `query_circle()` when called with [`Euclidean`]. With [`Manhattan`] the
+    /// region is a diamond; with [`Chebyshev`] it is an axis-aligned square, and the query
+    /// is answered directly as `query_intersecting()` on that square.
+    ///
+    /// # Arguments
+    /// * `center_x` - X coordinate of the center
+    /// * `center_y` - Y coordinate of the center
+    /// * `radius` - Maximum distance from the center
+    /// * `metric` - Distance metric
+    /// * `results` - Output vector; will be cleared and populated with indices of all boxes
+    ///   within `radius`
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add_point(2.0, 2.0);  // Point 0: L1 distance 4, L∞ distance 2
+    /// tree.add_point(3.0, 0.0);  // Point 1: L1 distance 3, L∞ distance 3
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_circle_with_metric(0.0, 0.0, 3.0, Manhattan, &mut results);
+    /// assert_eq!(results, vec![1]);
+    /// tree.query_circle_with_metric(0.0, 0.0, 2.5, Chebyshev, &mut results);
+    /// assert_eq!(results, vec![0]);
+    /// ```
+    pub fn query_circle_with_metric<M: Metric>(&self, center_x: f64, center_y: f64, radius: f64, metric: M, results: &mut Vec<usize>) {
+        if radius < 0.0 {
+            results.clear();
+            return;
+        }
+        if M::BALL_IS_BOX {
+            self.query_intersecting(center_x - radius, center_y - radius, center_x + radius, center_y + radius, results);
+            return;
+        }
+        let within = |node_box: &Box| {
+            metric.distance(
+                self.axis_distance(center_x, node_box.min_x, node_box.max_x),
+                self.axis_distance(center_y, node_box.min_y, node_box.max_y),
+            ) <= radius
+        };
+        self.collect_leaves(within, within, results);
+    }
+
+    /// Finds all point items (stored as (x, x, y, y)) within a radius using the given distance metric.
+    ///
+    /// Point counterpart of `query_circle_with_metric()`: leaf distances are computed
+    /// directly from the stored point coordinates.
+    ///
+    /// **Important:** This method assumes all items in the tree are stored as points.
+    ///
+    /// # Arguments
+    /// * `center_x` - X coordinate of the center
+    /// * `center_y` - Y coordinate of the center
+    /// * `radius` - Maximum distance from the center
+    /// * `metric` - Distance metric
+    /// * `results` - Output vector; will be cleared and populated with indices of all point
+    ///   items within `radius`
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add_point(1.0, 1.0);  // Point 0: L1 distance 2
+    /// tree.add_point(0.0, 3.0);  // Point 1: L1 distance 3
+    /// tree.add_point(1.5, 0.0);  // Point 2: L1 distance 1.5
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_circle_points_with_metric(0.0, 0.0, 2.0, Manhattan, &mut results);
+    /// results.sort();
+    /// assert_eq!(results, vec![0, 2]);
+    /// ```
+    pub fn query_circle_points_with_metric<M: Metric>(&self, center_x: f64, center_y: f64, radius: f64, metric: M, results: &mut Vec<usize>) {
+        if radius < 0.0 {
+            results.clear();
+            return;
+        }
+        if M::BALL_IS_BOX {
+            self.query_intersecting(center_x - radius, center_y - radius, center_x + radius, center_y + radius, results);
+            return;
+        }
+        self.collect_leaves(
+            |node_box| {
+                metric.distance(
+                    self.axis_distance(center_x, node_box.min_x, node_box.max_x),
+                    self.axis_distance(center_y, node_box.min_y, node_box.max_y),
+                ) <= radius
+            },
+            |point| metric.distance((point.min_x - center_x).abs(), (point.min_y - center_y).abs()) <= radius,
+            results,
+        );
+    }
+
+    /// Finds all boxes that intersect a longitude/latitude rectangle, wrapping across ±180°.
+    ///
+    /// Items are `(min_lon, min_lat, max_lon, max_lat)` boxes in degrees (see [`crate::geo`]).
+    /// A query with `min_lon > max_lon` crosses the antimeridian and covers
+    /// `[min_lon, 180]` and `[-180, max_lon]`; otherwise this is `query_intersecting()`.
+    ///
+    /// # Arguments
+    /// * `min_lon` - Western edge of the query rectangle
+    /// * `min_lat` - Southern edge of the query rectangle
+    /// * `max_lon` - Eastern edge of the query rectangle (less than `min_lon` to wrap)
+    /// * `max_lat` - Northern edge of the query rectangle
+    /// * `results` - Output vector; will be cleared and populated with matching box indices
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add_point(178.4, -18.1);   // Suva, Fiji
+    /// tree.add_point(-171.8, -13.8);  // Apia, Samoa
+    /// tree.add_point(151.2, -33.9);   // Sydney
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_intersecting_geo(170.0, -25.0, -165.0, -10.0, &mut results);
+    /// results.sort();
+    /// assert_eq!(results, vec![0, 1]);
+    /// ```
+    pub fn query_intersecting_geo(&self, min_lon: f64, min_lat: f64, max_lon: f64, max_lat: f64, results: &mut Vec<usize>) {
+        let intersects = |node_box: &Box| {
+            geo::lon_ranges_overlap(min_lon, max_lon, node_box.min_x, node_box.max_x)
+                && min_lat <= node_box.max_y
+                && node_box.min_y <= max_lat
+        };
+        self.collect_leaves(intersects, intersects, results);
+    }
+
+    /// Finds the K nearest boxes to a point by great-circle (haversine) distance.
+    ///
+    /// Items are `(min_lon, min_lat, max_lon, max_lat)` boxes in degrees (see [`crate::geo`]).
+    /// Distances are measured on the sphere, so neighbors across the antimeridian and near
+    /// the poles are found correctly; boxes containing the point have distance 0.
+    ///
+    /// # Arguments
+    /// * `lon` - Longitude of the query point in degrees
+    /// * `lat` - Latitude of the query point in degrees
+    /// * `k` - Number of nearest boxes to find
+    /// * `results` - Output vector; will be cleared and populated with K nearest box indices,
+    ///   sorted by distance (closest first)
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add_point(-179.0, 0.0);  // Item 0, 2 degrees east across the antimeridian
+    /// tree.add_point(175.0, 0.0);   // Item 1, 4 degrees west
+    /// tree.add_point(0.0, 0.0);     // Item 2, far away
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_nearest_k_geo(179.0, 0.0, 2, &mut results);
+    /// assert_eq!(results, vec![0, 1]);
+    /// ```
+    pub fn query_nearest_k_geo(&self, lon: f64, lat: f64, k: usize, results: &mut Vec<usize>) {
+        let cos_lat = lat.to_radians().cos();
+        self.nearest_k_by(
+            |node_box| geo::box_hav_dist(lon, lat, cos_lat, node_box.min_x, node_box.min_y, node_box.max_x, node_box.max_y),
+            k,
+            results,
+        );
+    }
+
+    /// Finds all boxes within a great-circle distance of a point.
+    ///
+    /// Items are `(min_lon, min_lat, max_lon, max_lat)` boxes in degrees (see [`crate::geo`]).
+    /// A box matches when its closest point is within `radius` kilometers on the sphere.
+    ///
+    /// # Arguments
+    /// * `lon` - Longitude of the center in degrees
+    /// * `lat` - Latitude of the center in degrees
+    /// * `radius` - Maximum distance in kilometers
+    /// * `results` - Output vector; will be cleared and populated with indices of all boxes
+    ///   within the distance
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add_point(-0.13, 51.51);  // London
+    /// tree.add_point(2.35, 48.86);   // Paris, about 344 km away
+    /// tree.add_point(13.40, 52.52);  // Berlin, about 930 km away
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_circle_geo(-0.13, 51.51, 400.0, &mut results);
+    /// results.sort();
+    /// assert_eq!(results, vec![0, 1]);
+    /// ```
+    pub fn query_circle_geo(&self, lon: f64, lat: f64, radius: f64, results: &mut Vec<usize>) {
+        if radius < 0.0 {
+            results.clear();
+            return;
+        }
+        let cos_lat = lat.to_radians().cos();
+        let bound = geo::km_to_hav(radius);
+        let within = |node_box: &Box| {
+            geo::box_hav_dist(lon, lat, cos_lat, node_box.min_x, node_box.min_y, node_box.max_x, node_box.max_y) <= bound
+        };
+        self.collect_leaves(within, within, results);
+    }
+
+    /// Counts the boxes that intersect with a given rectangular region.
+    ///
+    /// Returns the same number as `query_intersecting()` would produce, without
+    /// collecting indices. When a node box lies completely inside the query rectangle,
+    /// all leaves below it are counted at once without descending into the node.
+    /// This is useful for heatmaps, density estimates, or choosing a query strategy.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 2.0, 2.0);  // Box 0
+    /// tree.add(1.0, 1.0, 3.0, 3.0);  // Box 1
+    /// tree.add(4.0, 4.0, 5.0, 5.0);  // Box 2
+    /// tree.build();
+    ///
+    /// assert_eq!(tree.count_intersecting(0.5, 0.5, 2.5, 2.5), 2);
+    /// ```
+    pub fn count_intersecting(&self, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> usize {
+        let query = Box::new(min_x, min_y, max_x, max_y);
+        self.count_nodes(
+            |node_box| boxes_intersect(node_box, &query),
+            |node_box| box_contains(&query, node_box),
+        )
+    }
+
+    /// Counts the boxes that intersect with a circular region.
+    ///
+    /// Returns the same number as `query_circle()` would produce, without collecting
+    /// indices. Nodes whose box lies completely inside the circle are counted at once
+    /// from their leaf range without descending into them.
+    ///
+    /// # Arguments
+    /// * `center_x` - X coordinate of circle center
+    /// * `center_y` - Y coordinate of circle center
+    /// * `radius` - Radius of the circular region
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(1.0, 1.0, 2.0, 2.0);  // Box 1
+    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 2
+    /// tree.build();
+    ///
+    /// assert_eq!(tree.count_in_circle(1.0, 1.0, 1.5), 2);
+    /// ```
+    pub fn count_in_circle(&self, center_x: f64, center_y: f64, radius: f64) -> usize {
+        if radius < 0.0 {
+            return 0;
+        }
+        let radius_sq = radius * radius;
+        self.count_nodes(
+            |node_box| {
+                let dx = self.axis_distance(center_x, node_box.min_x, node_box.max_x);
+                let dy = self.axis_distance(center_y, node_box.min_y, node_box.max_y);
+                dx * dx + dy * dy <= radius_sq
+            },
+            |node_box| {
+                // Fully inside when the farthest corner is within the radius
+                let dx = (center_x - node_box.min_x).abs().max((node_box.max_x - center_x).abs());
+                let dy = (center_y - node_box.min_y).abs().max((node_box.max_y - center_y).abs());
+                dx * dx + dy * dy <= radius_sq
+            },
+        )
+    }
+
+    /// Checks whether any box intersects with a given rectangular region.
+    ///
+    /// Stops at the first intersecting box found, so this is much cheaper than
+    /// `query_intersecting()` when only a yes/no answer is needed.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(4.0, 4.0, 5.0, 5.0);  // Box 1
+    /// tree.build();
+    ///
+    /// assert!(tree.any_intersecting(0.5, 0.5, 2.0, 2.0));
+    /// assert!(!tree.any_intersecting(2.0, 2.0, 3.0, 3.0));
+    /// ```
+    pub fn any_intersecting(&self, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> bool {
+        let query = Box::new(min_x, min_y, max_x, max_y);
+        self.any_node(|node_box| boxes_intersect(node_box, &query))
+    }
+
+    /// Checks whether any box intersects with a circular region.
+    ///
+    /// Stops at the first box found within `radius` of the center.
+    ///
+    /// # Arguments
+    /// * `center_x` - X coordinate of circle center
+    /// * `center_y` - Y coordinate of circle center
+    /// * `radius` - Radius of the circular region
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(1);
+    /// tree.add(3.0, 0.0, 4.0, 1.0);  // Box 0
+    /// tree.build();
+    ///
+    /// assert!(tree.any_in_circle(0.0, 0.0, 3.0));
+    /// assert!(!tree.any_in_circle(0.0, 0.0, 2.9));
+    /// ```
+    pub fn any_in_circle(&self, center_x: f64, center_y: f64, radius: f64) -> bool {
+        if radius < 0.0 {
+            return false;
+        }
+        let radius_sq = radius * radius;
+        self.any_node(|node_box| {
+            let dx = self.axis_distance(center_x, node_box.min_x, node_box.max_x);
+            let dy = self.axis_distance(center_y, node_box.min_y, node_box.max_y);
+            dx * dx + dy * dy <= radius_sq
+        })
+    }
+
+    /// Checks whether any box contains a specific point.
+    ///
+    /// Stops at the first box containing the point (edges and corners included).
+    /// This is useful for hit testing when the hit object itself is not needed.
+    ///
+    /// # Arguments
+    /// * `x` - X coordinate of the query point
+    /// * `y` - Y coordinate of the query point
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(1);
+    /// tree.add(0.0, 0.0, 2.0, 2.0);  // Box 0
+    /// tree.build();
+    ///
+    /// assert!(tree.any_containing_point(2.0, 1.0));
+    /// assert!(!tree.any_containing_point(2.5, 1.0));
+    /// ```
+    pub fn any_containing_point(&self, x: f64, y: f64) -> bool {
+        self.any_node(|node_box| {
+            x >= node_box.min_x && x <= node_box.max_x && y >= node_box.min_y && y <= node_box.max_y
+        })
+    }
+
+    /// Finds all boxes intersecting a rectangle as ranges of leaf positions.
+    ///
+    /// Leaves are stored in Hilbert order, so all leaves below a node form one contiguous
+    /// range of positions. When a node box lies completely inside the query rectangle, its
+    /// whole leaf range is emitted at once instead of visiting every leaf. Ranges are sorted,
+    /// non-overlapping, and adjacent ranges are merged, so large viewport queries produce a
+    /// handful of ranges instead of millions of indices.
+    ///
+    /// Positions are not item IDs; use `leaf_ids()` to map ranges back to item IDs.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    /// * `ranges` - Output vector; will be cleared and populated with leaf position ranges
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(2.0, 2.0, 3.0, 3.0);  // Box 1
+    /// tree.add(8.0, 8.0, 9.0, 9.0);  // Box 2
+    /// tree.build();
+    ///
+    /// let mut ranges = Vec::new();
+    /// tree.query_intersecting_ranges(-1.0, -1.0, 10.0, 10.0, &mut ranges);
+    /// assert_eq!(ranges, vec![0..3]);
+    ///
+    /// let mut ids = Vec::new();
+    /// tree.query_intersecting_ranges(0.0, 0.0, 3.0, 3.0, &mut ranges);
+    /// tree.leaf_ids(&ranges, &mut ids);
+    /// ids.sort();
+    /// assert_eq!(ids, vec![0, 1]);
+    /// ```
+    pub fn query_intersecting_ranges(&self, min_x: f64, min_y: f64, max_x: f64, max_y: f64, ranges: &mut Vec<std::ops::Range<usize>>) {
+        ranges.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() {
+            return;
+        }
+
+        // Depth-first in position order, so ranges come out sorted and can be merged as we go
+        let query = Box::new(min_x, min_y, max_x, max_y);
+        let mut stack = vec![self.total_nodes - 1];
+        while let Some(pos) = stack.pop() {
+            let node_box = self.get_box(pos);
+            if !boxes_intersect(&node_box, &query) {
+                continue;
+            }
+            if pos >= self.num_items && !box_contains(&query, &node_box) {
+                stack.extend(self.children(pos).rev());
+                continue;
+            }
+            let range = self.leaf_range(pos);
+            match ranges.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => ranges.push(range),
+            }
+        }
+    }
+
+    /// Maps leaf position ranges to item IDs.
+    ///
+    /// Converts the output of `query_intersecting_ranges()` into the item IDs stored at
+    /// those positions, in Hilbert (position) order. Positions at or beyond `len()` are ignored.
+    ///
+    /// # Arguments
+    /// * `ranges` - Leaf position ranges
+    /// * `results` - Output vector; will be cleared and populated with item IDs
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(2.0, 2.0, 3.0, 3.0);  // Box 1
+    /// tree.build();
+    ///
+    /// let mut ids = Vec::new();
+    /// tree.leaf_ids(&[0..2], &mut ids);
+    /// ids.sort();
+    /// assert_eq!(ids, vec![0, 1]);
+    /// ```
+    pub fn leaf_ids(&self, ranges: &[std::ops::Range<usize>], results: &mut Vec<usize>) {
+        results.clear();
+        for range in ranges {
+            let end = range.end.min(self.num_items);
+            results.extend((range.start.min(end)..end).map(|pos| self.get_index(pos) as usize));
+        }
+    }
+
+    /// Aggregates the weights of all boxes intersecting a rectangular region.
+    ///
+    /// Returns the count, sum, minimum and maximum weight of the boxes that
+    /// `query_intersecting()` would return. Nodes lying completely inside the query
+    /// rectangle contribute their aggregate stored during `build()` without descending,
+    /// so large regions cost about as much as their boundary.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    ///
+    /// # Returns
+    /// `None` if the tree was built without weights (see `add_weighted()`), otherwise the
+    /// aggregate of matching items (`count == 0` when nothing matches).
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add_weighted(0.0, 0.0, 1.0, 1.0, 5.0);  // Box 0
+    /// tree.add_weighted(1.0, 1.0, 2.0, 2.0, 3.0);  // Box 1
+    /// tree.add_weighted(8.0, 8.0, 9.0, 9.0, 7.0);  // Box 2
+    /// tree.build();
+    ///
+    /// let aggregate = tree.aggregate_in_rect(0.0, 0.0, 3.0, 3.0).unwrap();
+    /// assert_eq!(aggregate.count, 2);
+    /// assert_eq!(aggregate.sum, 8.0);
+    /// assert_eq!((aggregate.min, aggregate.max), (3.0, 5.0));
+    /// ```
+    pub fn aggregate_in_rect(&self, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Option<Aggregate> {
+        if !self.has_aggregates() || self.num_items == 0 || self.level_bounds.is_empty() {
+            return None;
+        }
+
+        let query = Box::new(min_x, min_y, max_x, max_y);
+        let mut aggregate = Aggregate::default();
+        let mut stack = vec![self.total_nodes - 1];
+        while let Some(pos) = stack.pop() {
+            let node_box = self.get_box(pos);
+            if !boxes_intersect(&node_box, &query) {
+                continue;
+            }
+            if pos < self.num_items || box_contains(&query, &node_box) {
+                aggregate.merge(&self.node_aggregate(pos));
+            } else {
+                stack.extend(self.children(pos));
+            }
+        }
+        Some(aggregate)
+    }
+
+    /// Count leaves accepted by `matches`, descending only into nodes accepted by `matches`
+    /// and counting whole subtrees at once for nodes accepted by `covers`
+    fn count_nodes<M, C>(&self, matches: M, covers: C) -> usize
+    where
+        M: Fn(&Box) -> bool,
+        C: Fn(&Box) -> bool,
+    {
+        if self.num_items == 0 || self.level_bounds.is_empty() {
+            return 0;
+        }
+
+        let mut count = 0;
+        let mut stack = vec![self.total_nodes - 1];
+        while let Some(pos) = stack.pop() {
+            let node_box = self.get_box(pos);
+            if !matches(&node_box) {
+                continue;
+            }
+            if pos < self.num_items {
+                count += 1;
+            } else if covers(&node_box) {
+                count += self.leaf_range(pos).len();
+            } else {
+                stack.extend(self.children(pos));
+            }
+        }
+        count
+    }
+
+    /// Whether any leaf is accepted by `matches`, descending only into accepted nodes
+    fn any_node<M: Fn(&Box) -> bool>(&self, matches: M) -> bool {
+        if self.num_items == 0 || self.level_bounds.is_empty() {
+            return false;
+        }
+
+        let mut stack = vec![self.total_nodes - 1];
+        while let Some(pos) = stack.pop() {
+            if !matches(&self.get_box(pos)) {
+                continue;
+            }
+            if pos < self.num_items {
+                return true;
+            }
+            stack.extend(self.children(pos));
+        }
+        false
+    }
+
+    /// Finds all boxes that intersect with a rectangle's directional movement path.
+    ///
+    /// This query simulates moving a rectangle from its initial position in a given direction
+    /// for a specified distance, and returns all boxes intersecting the swept path. The sweep
+    /// region is the bounding box of the rectangle at its start and end positions.
+    /// This is useful for collision detection along movement paths, ray casting, and
+    /// predictive spatial queries (e.g., finding obstacles in a moving object's path).
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of the rectangle
+    /// * `min_y` - Bottom edge of the rectangle
+    /// * `max_x` - Right edge of the rectangle
+    /// * `max_y` - Top edge of the rectangle
+    /// * `dir_x` - X component of movement direction vector
+    /// * `dir_y` - Y component of movement direction vector
+    /// * `distance` - Distance to move in the direction (direction is normalized internally)
+    /// * `results` - Output vector; will be cleared and populated with indices of all boxes
+    ///              intersecting the swept movement path
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(3.0, 0.0, 4.0, 1.0);  // Box 1 (in the path)
+    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 2 (not in the path)
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// // Move rectangle from (0,0)-(1,1) to the right for distance 3
+    /// tree.query_in_direction(0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 3.0, &mut results);
+    /// // Results include boxes 0 and 1
+    /// ```
+    pub fn query_in_direction(
+        &self,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        dir_x: f64,
+        dir_y: f64,
+        distance: f64,
+        results: &mut Vec<usize>,
+    ) {
+        results.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() || distance < 0.0 {
+            return;
+        }
+        
+        // Normalize direction vector
+        let dir_len_sq = dir_x * dir_x + dir_y * dir_y;
+        if dir_len_sq <= 0.0 {
+            return;
+        }
+        let dir_len = dir_len_sq.sqrt();
+        let norm_dir_x = dir_x / dir_len;
+        let norm_dir_y = dir_y / dir_len;
+        
+        // Calculate movement vector
+        let dx = norm_dir_x * distance;
+        let dy = norm_dir_y * distance;
+        let sweep_min_x = min_x.min(min_x + dx);
+        let sweep_min_y = min_y.min(min_y + dy);
+        let sweep_max_x = max_x.max(max_x + dx);
+        let sweep_max_y = max_y.max(max_y + dy);
+
+        let mut queue = VecDeque::new();
+        let mut node_index = self.total_nodes - 1;
+        
+        loop {
+            let node_end = self.upper_bound(node_index);
+            let end_pos = (node_index + self.node_size).min(node_end);
+            
+            for pos in node_index..end_pos {
+                let node_box = self.get_box(pos);
+                
+                // Check if box intersects the sweep area (AABB intersection)
+                if sweep_max_x < node_box.min_x || sweep_max_y < node_box.min_y ||
+                   sweep_min_x > node_box.max_x || sweep_min_y > node_box.max_y {
+                    continue;
+                }
+                
+                let index = self.get_index(pos);
+                if pos >= self.num_items {
+                    queue.push_back((index >> 2) as usize);
+                } else {
+                    results.push(index as usize);
+                }
+            }
+            
+            if queue.is_empty() {
+                break;
+            }
+            
+            node_index = queue.pop_front().unwrap();
+        }
+    }
+
+    /// Finds the K nearest boxes intersecting a rectangle's directional movement path.
+    ///
+    /// This query finds boxes that intersect with a moving rectangle's swept path and
+    /// returns the K closest ones ordered by distance along the movement direction.
+    /// Distance is computed as the projection of each box's center onto the normalized
+    /// direction vector, making results ordered by "how far along the path" each box is.
+    /// This is useful for ordered collision detection, finding the closest obstacles
+    /// in a movement path, or sequential hit detection in a sweep.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of the rectangle
+    /// * `min_y` - Bottom edge of the rectangle
+    /// * `max_x` - Right edge of the rectangle
+    /// * `max_y` - Top edge of the rectangle
+    /// * `dir_x` - X component of movement direction vector
+    /// * `dir_y` - Y component of movement direction vector
+    /// * `k` - Maximum number of results to return
+    /// * `distance` - Distance to move in the direction (direction is normalized internally)
+    /// * `results` - Output vector; will be cleared and populated with up to K nearest box indices
+    ///              sorted by distance along the movement direction
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(4);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(2.0, 0.0, 3.0, 1.0);  // Box 1 (first in path)
+    /// tree.add(4.0, 0.0, 5.0, 1.0);  // Box 2 (second in path)
+    /// tree.add(6.0, 6.0, 7.0, 7.0);  // Box 3 (not in path)
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// // Move rectangle right, find 2 nearest obstacles
+    /// tree.query_in_direction_k(0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 2, 10.0, &mut results);
+    /// // Results contain boxes 1 and 2 (ordered by distance along direction)
+    /// ```
+    pub fn query_in_direction_k(
+        &self,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        dir_x: f64,
+        dir_y: f64,
+        k: usize,
+        distance: f64,
+        results: &mut Vec<usize>,
+    ) {
+        results.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() || distance < 0.0 || k == 0 {
+            return;
+        }
+
+        // Normalize direction vector
+        let dir_len_sq = dir_x * dir_x + dir_y * dir_y;
+        if dir_len_sq <= 0.0 {
+            results.clear();
+            return;
+        }
+        let dir_len = dir_len_sq.sqrt();
+        let norm_dir_x = dir_x / dir_len;
+        let norm_dir_y = dir_y / dir_len;
+        
+        // Calculate movement vector
+        let dx = norm_dir_x * distance;
+        let dy = norm_dir_y * distance;
+        let sweep_min_x = min_x.min(min_x + dx);
+        let sweep_min_y = min_y.min(min_y + dy);
+        let sweep_max_x = max_x.max(max_x + dx);
+        let sweep_max_y = max_y.max(max_y + dy);
+
+        let mut candidates: Vec<(f64, usize)> = Vec::new();
+
+        let mut queue = Vec::with_capacity(self.level_bounds.len() * 2);
+        queue.push(self.total_nodes - 1);
+
+        while let Some(node_idx) = queue.pop() {
+            let node_end = self.upper_bound(node_idx);
+            let end_pos = (node_idx + self.node_size).min(node_end);
+
+            for pos in node_idx..end_pos {
+                let node_box = self.get_box(pos);
+                
+                // Check if box intersects the sweep area (AABB intersection)
+                if sweep_max_x < node_box.min_x || sweep_max_y < node_box.min_y ||
+                   sweep_min_x > node_box.max_x || sweep_min_y > node_box.max_y {
+                    continue;
+                }
+
+                let index = self.get_index(pos) as usize;
+
+                if pos < self.num_items {
+                    // Calculate distance to box center along direction
+                    let box_center_x = (node_box.min_x + node_box.max_x) / 2.0;
+                    let box_center_y = (node_box.min_y + node_box.max_y) / 2.0;
+                    
+                    let relative_x = box_center_x - min_x;
+                    let relative_y = box_center_y - min_y;
+                    
+                    // Distance along direction (use normalized direction)
+                    let dist_along_dir = relative_x * norm_dir_x + relative_y * norm_dir_y;
+                    candidates.push((dist_along_dir, index));
+                } else {
+                    queue.push(index >> 2);
+                }
+            }
+        }
+
+        // Partial sort: get K smallest elements by distance
+        results.clear();
+        
+        if candidates.len() <= k {
+            // Fewer candidates than K, just sort all
+            candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            for (_, idx) in candidates {
+                results.push(idx);
+            }
+        } else {
+            // More candidates than K, use partial sort
+            candidates.select_nth_unstable_by(k - 1, |a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            // Now split at position k and sort only the K elements
+            let (k_smallest, _) = candidates.split_at_mut(k);
+            k_smallest.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            for (_, idx) in k_smallest {
+                results.push(*idx);
+            }
+        }
+    }
+
+    /// Finds the K first boxes hit by a rectangle moving along a direction (swept AABB).
+    ///
+    /// Unlike `query_in_direction_k()`, which orders boxes by the projection of their
+    /// centers, this query computes the exact time of impact of the moving rectangle
+    /// against each box, so large obstacles whose near face is close are reported first.
+    /// Each hit is returned as `(id, time_of_impact, contact_normal)`:
+    /// - `time_of_impact` is the fraction of the movement in `[0, 1]` at which the boxes
+    ///   first touch (`distance * time_of_impact` is the distance travelled until contact)
+    /// - `contact_normal` is the unit normal of the hit face of the obstacle, pointing
+    ///   back towards the moving rectangle; it is `(0.0, 0.0)` for boxes that already
+    ///   overlap the rectangle at the start of the movement (`time_of_impact == 0`)
+    ///
+    /// Nodes are visited best-first by their own time of impact, so the search stops as
+    /// soon as K hits are found. Boxes touching the swept rectangle count as hits.
+    /// This is useful for continuous collision detection in character controllers.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of the rectangle
+    /// * `min_y` - Bottom edge of the rectangle
+    /// * `max_x` - Right edge of the rectangle
+    /// * `max_y` - Top edge of the rectangle
+    /// * `dir_x` - X component of movement direction vector
+    /// * `dir_y` - Y component of movement direction vector
+    /// * `k` - Maximum number of hits to return
+    /// * `distance` - Distance to move in the direction (direction is normalized internally)
+    /// * `results` - Output vector; will be cleared and populated with up to K hits (earliest first)
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(3.0, -10.0, 20.0, 10.0);  // Box 0 (large wall, near face at x = 3)
+    /// tree.add(5.0, 0.0, 6.0, 1.0);      // Box 1 (small, center closer than box 0's center)
+    /// tree.build();
+    ///
+    /// let mut hits = Vec::new();
+    /// // Move the unit square at the origin to the right by 10
+    /// tree.query_swept_k(0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 2, 10.0, &mut hits);
+    /// assert_eq!(hits[0], (0, 0.2, (-1.0, 0.0)));
+    /// assert_eq!(hits[1], (1, 0.4, (-1.0, 0.0)));
+    /// ```
+    #[expect(clippy::too_many_arguments, reason = "same parameters as query_in_direction_k")]
+    pub fn query_swept_k(
+        &self,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        dir_x: f64,
+        dir_y: f64,
+        k: usize,
+        distance: f64,
+        results: &mut Vec<(usize, f64, (f64, f64))>,
+    ) {
+        results.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() || distance < 0.0 || k == 0 {
+            return;
+        }
+
+        use std::collections::BinaryHeap;
+        use std::cmp::Reverse;
+
+        // Normalize direction vector; a zero direction only reports initial overlaps
+        let dir_len_sq = dir_x * dir_x + dir_y * dir_y;
+        let (move_x, move_y) = if dir_len_sq > 0.0 {
+            let dir_len = dir_len_sq.sqrt();
+            (dir_x / dir_len * distance, dir_y / dir_len * distance)
+        } else {
+            (0.0, 0.0)
+        };
+        let moving = Box::new(min_x, min_y, max_x, max_y);
+
+        // Min-heap by time of impact; ties keep leaves (lower positions) ahead of parent nodes
+        let mut queue = BinaryHeap::new();
+        let root = self.total_nodes - 1;
+        if let Some((toi, _)) = swept_time_of_impact(&moving, move_x, move_y, &self.get_box(root)) {
+            queue.push(Reverse(HeapEntry { dist: toi, pos: root }));
+        }
+
+        // Children never hit earlier than their parent, so leaves pop in time-of-impact order
+        while let Some(Reverse(entry)) = queue.pop() {
+            if entry.pos < self.num_items {
+                // The contact normal is only needed for reported leaves
+                if let Some((toi, normal)) = swept_time_of_impact(&moving, move_x, move_y, &self.get_box(entry.pos)) {
+                    results.push((self.get_index(entry.pos) as usize, toi, normal));
+                }
+                if results.len() >= k {
+                    break;
+                }
+                continue;
+            }
+
+            for pos in self.children(entry.pos) {
+                if let Some((toi, _)) = swept_time_of_impact(&moving, move_x, move_y, &self.get_box(pos)) {
+                    queue.push(Reverse(HeapEntry { dist: toi, pos }));
+                }
+            }
+        }
+    }
+
+    /// Runs `query_intersecting()` for many rectangles at once.
+    ///
+    /// Queries are processed in Hilbert order of their centers so that consecutive
+    /// queries touch nearby parts of the tree, which improves cache locality for large
+    /// batches. With the `parallel` feature enabled the batch is split across threads.
+    /// The output is returned in the original query order.
+    ///
+    /// # Arguments
+    /// * `queries` - Query rectangles as `(min_x, min_y, max_x, max_y)`
+    ///
+    /// # Returns
+    /// One vector of matching box indices per query.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(2.0, 2.0, 3.0, 3.0);  // Box 1
+    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 2
+    /// tree.build();
+    ///
+    /// let results = tree.query_intersecting_batch(&[(0.5, 0.5, 2.5, 2.5), (4.0, 4.0, 7.0, 7.0)]);
+    /// assert_eq!(results.len(), 2);
+    /// assert_eq!(results[1], vec![2]);
+    /// ```
+    pub fn query_intersecting_batch(&self, queries: &[(f64, f64, f64, f64)]) -> Vec<Vec<usize>> {
+        let order = self.hilbert_order(queries.iter().map(|q| ((q.0 + q.2) / 2.0, (q.1 + q.3) / 2.0)));
+        self.run_batch(&order, |i, results| {
+            let (min_x, min_y, max_x, max_y) = queries[i];
+            self.query_intersecting(min_x, min_y, max_x, max_y, results);
+        })
+    }
+
+    /// Runs `query_intersecting()` for many rectangles at once with flat (CSR) output.
+    ///
+    /// Same as `query_intersecting_batch()`, but all results are written into a single
+    /// vector. The matches of query `i` are `results[offsets[i]..offsets[i + 1]]`.
+    /// Queries are answered in Hilbert order for cache locality, appending into one flat
+    /// buffer through a reused scratch vector, so no vector is allocated per query; the
+    /// matches are then copied into place in query order. With the `parallel` feature,
+    /// each thread answers a contiguous run of the Hilbert order into its own buffer.
+    /// The output buffers can be kept across calls.
+    ///
+    /// # Arguments
+    /// * `queries` - Query rectangles as `(min_x, min_y, max_x, max_y)`
+    /// * `offsets` - Output vector; will be cleared and populated with `queries.len() + 1` offsets
+    /// * `results` - Output vector; will be cleared and populated with the matching box indices
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(2.0, 2.0, 3.0, 3.0);  // Box 1
+    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 2
+    /// tree.build();
+    ///
+    /// let mut offsets = Vec::new();
+    /// let mut results = Vec::new();
+    /// tree.query_intersecting_batch_flat(&[(4.0, 4.0, 7.0, 7.0), (9.0, 9.0, 10.0, 10.0)], &mut offsets, &mut results);
+    /// assert_eq!(offsets, vec![0, 1, 1]);
+    /// assert_eq!(&results[offsets[0]..offsets[1]], &[2]);
+    /// ```
+    pub fn query_intersecting_batch_flat(
+        &self,
+        queries: &[(f64, f64, f64, f64)],
+        offsets: &mut Vec<usize>,
+        results: &mut Vec<usize>,
+    ) {
+        offsets.clear();
+        results.clear();
+        offsets.reserve(queries.len() + 1);
+        offsets.push(0);
+
+        let order = self.hilbert_order(queries.iter().map(|q| ((q.0 + q.2) / 2.0, (q.1 + q.3) / 2.0)));
+        let chunk_size = self.batch_flat_chunk_size(order.len());
+        let runs = self.run_batch_flat(queries, &order, chunk_size);
+
+        // Where each query's matches live: (run, start, end)
+        let mut spans = vec![(0, 0, 0); queries.len()];
+        for ((run_index, (_, ends)), run) in runs.iter().enumerate().zip(order.chunks(chunk_size)) {
+            let mut start = 0;
+            for (&i, &end) in run.iter().zip(ends) {
+                spans[i] = (run_index, start, end);
+                start = end;
+            }
+        }
+        results.reserve(runs.iter().map(|(matches, _)| matches.len()).sum());
+        for (run, start, end) in spans {
+            results.extend_from_slice(&runs[run].0[start..end]);
+            offsets.push(results.len());
+        }
+    }
+
+    /// Answer a run of queries (indices into `queries`) in the given order into one flat
+    /// buffer: all matches, and the end of each query's matches within them
+    fn answer_batch_run(&self, queries: &[(f64, f64, f64, f64)], run: &[usize]) -> (Vec<usize>, Vec<usize>) {
+        let mut scratch = Vec::new();
+        let mut matches = Vec::new();
+        let mut ends = Vec::with_capacity(run.len());
+        for &i in run {
+            let (min_x, min_y, max_x, max_y) = queries[i];
+            self.query_intersecting(min_x, min_y, max_x, max_y, &mut scratch);
+            matches.extend_from_slice(&scratch);
+            ends.push(matches.len());
+        }
+        (matches, ends)
+    }
+
+    /// Number of queries per run: a single run without the `parallel` feature
+    #[cfg(not(feature = "parallel"))]
+    fn batch_flat_chunk_size(&self, num_queries: usize) -> usize {
+        num_queries.max(1)
+    }
+
+    /// Number of queries per run: enough runs to balance the rayon thread pool
+    #[cfg(feature = "parallel")]
+    fn batch_flat_chunk_size(&self, num_queries: usize) -> usize {
+        num_queries.div_ceil(rayon::current_num_threads() * 4).max(1)
+    }
+
+    /// Answer contiguous runs of the Hilbert order one after another
+    #[cfg(not(feature = "parallel"))]
+    fn run_batch_flat(&self, queries: &[(f64, f64, f64, f64)], order: &[usize], chunk_size: usize) -> Vec<(Vec<usize>, Vec<usize>)> {
+        order.chunks(chunk_size).map(|run| self.answer_batch_run(queries, run)).collect()
+    }
+
+    /// Answer contiguous runs of the Hilbert order on the rayon thread pool
+    #[cfg(feature = "parallel")]
+    fn run_batch_flat(&self, queries: &[(f64, f64, f64, f64)], order: &[usize], chunk_size: usize) -> Vec<(Vec<usize>, Vec<usize>)> {
+        use rayon::prelude::*;
+
+        order.par_chunks(chunk_size).map(|run| self.answer_batch_run(queries, run)).collect()
+    }
+
+    /// Runs `query_nearest_k()` for many points at once.
+    ///
+    /// Query points are processed in Hilbert order for cache locality, and in parallel
+    /// with the `parallel` feature enabled. The output is returned in the original
+    /// query order, each entry sorted by distance (closest first).
+    ///
+    /// # Arguments
+    /// * `points` - Query points as `(x, y)`
+    /// * `k` - Number of nearest boxes to find per point
+    ///
+    /// # Returns
+    /// One vector of up to K nearest box indices per query point.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);  // Box 0
+    /// tree.add(2.0, 2.0, 3.0, 3.0);  // Box 1
+    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 2
+    /// tree.build();
+    ///
+    /// let results = tree.query_nearest_k_batch(&[(0.0, 0.0), (6.0, 6.0)], 1);
+    /// assert_eq!(results, vec![vec![0], vec![2]]);
+    /// ```
+    pub fn query_nearest_k_batch(&self, points: &[(f64, f64)], k: usize) -> Vec<Vec<usize>> {
+        let order = self.hilbert_order(points.iter().copied());
+        self.run_batch(&order, |i, results| {
+            let (x, y) = points[i];
+            self.query_nearest_k(x, y, k, results);
+        })
+    }
+
+    /// Finds all pairs of intersecting boxes between two trees (spatial join).
+    ///
+    /// Both trees are traversed together from their roots, descending only into pairs
+    /// of nodes whose boxes intersect, so large disjoint regions of either tree are
+    /// skipped at once. This is much faster than calling `query_intersecting()` once
+    /// per item of one tree and has better memory locality. Boxes touching at edges
+    /// or corners count as intersecting. Pairs are reported in traversal order.
+    ///
+    /// # Arguments
+    /// * `a` - First tree
+    /// * `b` - Second tree
+    /// * `f` - Callback invoked with `(item_in_a, item_in_b)` for every intersecting pair
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut parcels = AABB::new();
+    /// parcels.add(0.0, 0.0, 2.0, 2.0);  // Parcel 0
+    /// parcels.add(5.0, 5.0, 6.0, 6.0);  // Parcel 1
+    /// parcels.build();
+    ///
+    /// let mut zones = AABB::new();
+    /// zones.add(1.0, 1.0, 3.0, 3.0);  // Zone 0 (overlaps parcel 0)
+    /// zones.add(8.0, 8.0, 9.0, 9.0);  // Zone 1
+    /// zones.build();
+    ///
+    /// let mut pairs = Vec::new();
+    /// HilbertRTree::join_intersecting(&parcels, &zones, |a, b| pairs.push((a, b)));
+    /// assert_eq!(pairs, vec![(0, 0)]);
+    /// ```
+    pub fn join_intersecting<F: FnMut(usize, usize)>(a: &Self, b: &Self, mut f: F) {
+        join::traverse(a, b, false, boxes_intersect, |pos_a, pos_b| {
+            f(a.get_index(pos_a) as usize, b.get_index(pos_b) as usize);
+        });
+    }
+
+    /// Finds every pair of intersecting boxes within the tree (self-join broad phase).
+    ///
+    /// Each overlapping pair is reported exactly once as `(i, j)` with `i < j`; an item
+    /// is never paired with itself. The tree is traversed against itself in a single pass
+    /// instead of running one query per item, which is the typical broad phase for physics
+    /// engines. Boxes touching at edges or corners count as intersecting. The order of
+    /// the pairs is unspecified.
+    ///
+    /// # Arguments
+    /// * `results` - Output vector; will be cleared and populated with intersecting pairs
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 2.0, 2.0);  // Box 0
+    /// tree.add(1.0, 1.0, 3.0, 3.0);  // Box 1
+    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 2
+    /// tree.build();
+    ///
+    /// let mut pairs = Vec::new();
+    /// tree.all_intersecting_pairs(&mut pairs);
+    /// assert_eq!(pairs, vec![(0, 1)]);
+    /// ```
+    pub fn all_intersecting_pairs(&self, results: &mut Vec<(usize, usize)>) {
+        results.clear();
+        join::traverse(self, self, true, boxes_intersect, |pos_a, pos_b| {
+            results.push(self.ordered_pair(pos_a, pos_b));
+        });
+    }
+
+    /// Parallel version of `all_intersecting_pairs()` (requires the `parallel` feature).
+    ///
+    /// The top levels of the self-traversal are expanded until there are enough
+    /// independent node pairs to keep all threads busy, and each pair is then traversed
+    /// on the rayon thread pool. Produces the same set of pairs as the sequential version,
+    /// in a different order.
+    ///
+    /// # Arguments
+    /// * `results` - Output vector; will be cleared and populated with intersecting pairs
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 2.0, 2.0);  // Box 0
+    /// tree.add(1.0, 1.0, 3.0, 3.0);  // Box 1
+    /// tree.add(5.0, 5.0, 6.0, 6.0);  // Box 2
+    /// tree.build();
+    ///
+    /// let mut pairs = Vec::new();
+    /// tree.all_intersecting_pairs_parallel(&mut pairs);
+    /// assert_eq!(pairs, vec![(0, 1)]);
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn all_intersecting_pairs_parallel(&self, results: &mut Vec<(usize, usize)>) {
+        results.clear();
+        join::self_join_parallel(self, boxes_intersect, |a, b| self.ordered_pair(a, b), results);
+    }
+
+    /// Finds all pairs of boxes between two trees whose distance is at most `eps`.
+    ///
+    /// The distance between two boxes is the Euclidean distance between their closest
+    /// points (0 when they intersect). Both trees are traversed together and node pairs
+    /// farther apart than `eps` are pruned, like in `join_intersecting()`. This is useful
+    /// for snapping map features or matching detections between two data sets.
+    ///
+    /// # Arguments
+    /// * `a` - First tree
+    /// * `b` - Second tree
+    /// * `eps` - Maximum box-to-box distance (inclusive); negative values match nothing
+    /// * `f` - Callback invoked with `(item_in_a, item_in_b, distance)` for every matching pair
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut a = AABB::new();
+    /// a.add(0.0, 0.0, 1.0, 1.0);  // Item 0
+    /// a.build();
+    ///
+    /// let mut b = AABB::new();
+    /// b.add(4.0, 0.0, 5.0, 1.0);  // Item 0 (3 units away)
+    /// b.add(9.0, 9.0, 9.0, 9.0);  // Item 1 (far away)
+    /// b.build();
+    ///
+    /// let mut pairs = Vec::new();
+    /// HilbertRTree::join_within_distance(&a, &b, 3.0, |ia, ib, dist| pairs.push((ia, ib, dist)));
+    /// assert_eq!(pairs, vec![(0, 0, 3.0)]);
+    /// ```
+    pub fn join_within_distance<F: FnMut(usize, usize, f64)>(a: &Self, b: &Self, eps: f64, mut f: F) {
+        if eps < 0.0 {
+            return;
+        }
+        let eps_sq = eps * eps;
+        join::traverse(a, b, false, |box_a, box_b| box_distance_sq(box_a, box_b) <= eps_sq, |pos_a, pos_b| {
+            let dist = box_distance_sq(&a.get_box(pos_a), &b.get_box(pos_b)).sqrt();
+            f(a.get_index(pos_a) as usize, b.get_index(pos_b) as usize, dist);
+        });
+    }
+
+    /// Finds all pairs of boxes within the tree whose distance is at most `eps`.
+    ///
+    /// Self-join version of `join_within_distance()`: each pair is reported exactly once
+    /// as `(i, j, distance)` with `i < j`, and an item is never paired with itself.
+    /// The order of the pairs is unspecified. This is useful for deduplicating nearby
+    /// detections or clustering close features.
+    ///
+    /// # Arguments
+    /// * `eps` - Maximum box-to-box distance (inclusive); negative values match nothing
+    /// * `results` - Output vector; will be cleared and populated with `(i, j, distance)` triples
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add_point(0.0, 0.0);  // Point 0
+    /// tree.add_point(0.5, 0.0);  // Point 1
+    /// tree.add_point(5.0, 5.0);  // Point 2
+    /// tree.build();
+    ///
+    /// let mut pairs = Vec::new();
+    /// tree.pairs_within_distance(1.0, &mut pairs);
+    /// assert_eq!(pairs, vec![(0, 1, 0.5)]);
+    /// ```
+    pub fn pairs_within_distance(&self, eps: f64, results: &mut Vec<(usize, usize, f64)>) {
+        results.clear();
+        if eps < 0.0 {
+            return;
+        }
+        let eps_sq = eps * eps;
+        join::traverse(self, self, true, |box_a, box_b| box_distance_sq(box_a, box_b) <= eps_sq, |pos_a, pos_b| {
+            let dist = box_distance_sq(&self.get_box(pos_a), &self.get_box(pos_b)).sqrt();
+            let (id_a, id_b) = self.ordered_pair(pos_a, pos_b);
+            results.push((id_a, id_b, dist));
+        });
+    }
+
+    /// Finds the K closest pairs of boxes between two trees.
+    ///
+    /// Node pairs of both trees are visited best-first by the minimum distance between
+    /// their boxes, so the search stops as soon as no remaining pair can beat the K-th
+    /// closest pair found so far. Distances are Euclidean distances between the closest
+    /// points of the boxes (0 when they intersect). This is useful for clearance checks,
+    /// e.g. the minimum spacing between two layers of a PCB or CAD drawing.
+    ///
+    /// # Arguments
+    /// * `a` - First tree
+    /// * `b` - Second tree
+    /// * `k` - Number of closest pairs to find
+    /// * `results` - Output vector; will be cleared and populated with up to K
+    ///   `(item_in_a, item_in_b, distance)` triples sorted by distance (closest first)
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut layer_a = AABB::new();
+    /// layer_a.add(0.0, 0.0, 1.0, 1.0);  // Item 0
+    /// layer_a.add(10.0, 0.0, 11.0, 1.0);  // Item 1
+    /// layer_a.build();
+    ///
+    /// let mut layer_b = AABB::new();
+    /// layer_b.add(3.0, 0.0, 4.0, 1.0);  // Item 0 (2 from a0)
+    /// layer_b.add(11.5, 0.0, 12.0, 1.0);  // Item 1 (0.5 from a1)
+    /// layer_b.build();
+    ///
+    /// let mut results = Vec::new();
+    /// HilbertRTree::closest_pairs(&layer_a, &layer_b, 2, &mut results);
+    /// assert_eq!(results, vec![(1, 1, 0.5), (0, 0, 2.0)]);
+    /// ```
+    pub fn closest_pairs(a: &Self, b: &Self, k: usize, results: &mut Vec<(usize, usize, f64)>) {
+        results.clear();
+        if a.num_items == 0 || a.level_bounds.is_empty() || b.num_items == 0 || b.level_bounds.is_empty() || k == 0 {
+            return;
+        }
+
+        use std::collections::BinaryHeap;
+        use std::cmp::Reverse;
+
+        // Node pairs `(pos_a, pos_b)` by distance: max-heap for results, min-heap for the queue
+        let mut queue = BinaryHeap::new();
+        let mut result_heap: BinaryHeap<HeapEntry<(usize, usize)>> = BinaryHeap::with_capacity(k + 1);
+        let mut max_dist_sq = f64::INFINITY;
+
+        let root_a = a.total_nodes - 1;
+        let root_b = b.total_nodes - 1;
+        queue.push(Reverse(HeapEntry {
+            dist: box_distance_sq(&a.get_box(root_a), &b.get_box(root_b)),
+            pos: (root_a, root_b),
+        }));
+
+        let push_pair = |pending: &mut BinaryHeap<Reverse<HeapEntry<(usize, usize)>>>, bound_sq: f64, pos_a: usize, pos_b: usize| {
+            let dist_sq = box_distance_sq(&a.get_box(pos_a), &b.get_box(pos_b));
+            if dist_sq <= bound_sq {
+                pending.push(Reverse(HeapEntry { dist: dist_sq, pos: (pos_a, pos_b) }));
+            }
+        };
+
+        while let Some(Reverse(entry)) = queue.pop() {
+            // Remaining pairs are all farther than the current k-th result
+            if entry.dist > max_dist_sq {
+                break;
+            }
+
+            let (pos_a, pos_b) = entry.pos;
+            let leaf_a = pos_a < a.num_items;
+            let leaf_b = pos_b < b.num_items;
+
+            if leaf_a && leaf_b {
+                result_heap.push(entry);
+                if result_heap.len() > k {
+                    result_heap.pop();
+                }
+                if result_heap.len() == k
+                    && let Some(top) = result_heap.peek()
+                {
+                    max_dist_sq = top.dist;
+                }
+            } else if leaf_b {
+                for child_a in a.children(pos_a) {
+                    push_pair(&mut queue, max_dist_sq, child_a, pos_b);
+                }
+            } else if leaf_a {
+                for child_b in b.children(pos_b) {
+                    push_pair(&mut queue, max_dist_sq, pos_a, child_b);
+                }
+            } else {
+                for child_a in a.children(pos_a) {
+                    for child_b in b.children(pos_b) {
+                        push_pair(&mut queue, max_dist_sq, child_a, child_b);
+                    }
+                }
+            }
+        }
+
+        results.extend(result_heap.into_sorted_vec().into_iter().map(|e| {
+            (a.get_index(e.pos.0) as usize, b.get_index(e.pos.1) as usize, e.dist.sqrt())
+        }));
+    }
+
+    /// Builds the K-nearest-neighbor graph of all items (all-kNN).
+    ///
+    /// For every item, finds its K nearest other items by box-to-box distance (0 when
+    /// boxes intersect), excluding the item itself. Items are processed in the tree's
+    /// Hilbert order: the neighbors of the previous leaf are usually close to the current
+    /// one, so they provide a tight initial search radius and most of the tree is pruned
+    /// immediately instead of descending from the root unbounded for every item.
+    /// With the `parallel` feature enabled, contiguous runs of leaves are processed on
+    /// multiple threads.
+    ///
+    /// The graph is returned in CSR layout: the neighbors of item `i` are
+    /// `neighbors[offsets[i]..offsets[i + 1]]`, sorted by distance (closest first).
+    /// Every item has `min(k, len() - 1)` neighbors.
+    ///
+    /// # Arguments
+    /// * `k` - Number of neighbors per item
+    /// * `offsets` - Output vector; will be cleared and populated with `len() + 1` offsets
+    /// * `neighbors` - Output vector; will be cleared and populated with neighbor item ids
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(4);
+    /// tree.add_point(0.0, 0.0);  // Point 0
+    /// tree.add_point(1.0, 0.0);  // Point 1
+    /// tree.add_point(3.0, 0.0);  // Point 2
+    /// tree.add_point(7.0, 0.0);  // Point 3
+    /// tree.build();
+    ///
+    /// let mut offsets = Vec::new();
+    /// let mut neighbors = Vec::new();
+    /// tree.knn_graph(1, &mut offsets, &mut neighbors);
+    /// assert_eq!(offsets, vec![0, 1, 2, 3, 4]);
+    /// assert_eq!(neighbors, vec![1, 0, 1, 2]);
+    /// ```
+    pub fn knn_graph(&self, k: usize, offsets: &mut Vec<usize>, neighbors: &mut Vec<usize>) {
+        offsets.clear();
+        neighbors.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() {
+            return;
+        }
+
+        let num_items = self.num_items;
+        let degree = k.min(num_items - 1);
+        offsets.extend((0..=num_items).map(|i| i * degree));
+        if degree == 0 {
+            return;
+        }
+
+        // Neighbor lists indexed by leaf position, computed in Hilbert order
+        let mut by_pos = vec![0_usize; num_items * degree];
+
+        #[cfg(not(feature = "parallel"))]
+        self.knn_graph_range(0, degree, &mut by_pos);
+
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            let chunk = num_items.div_ceil(rayon::current_num_threads() * 8).max(1024);
+            by_pos
+                .par_chunks_mut(chunk * degree)
+                .enumerate()
+                .for_each(|(i, out)| self.knn_graph_range(i * chunk, degree, out));
+        }
+
+        // Scatter into item id order
+        neighbors.resize(num_items * degree, 0);
+        for (pos, list) in by_pos.chunks_exact(degree).enumerate() {
+            let id = self.get_index(pos) as usize;
+            neighbors[id * degree..(id + 1) * degree].copy_from_slice(list);
+        }
+    }
+
+    /// Compute the neighbor lists of consecutive leaves starting at `first_pos` into `out`
+    /// (`degree` item ids per leaf), seeding each search with the previous leaf's neighbors.
+    fn knn_graph_range(&self, first_pos: usize, degree: usize, out: &mut [usize]) {
+        let mut found: Vec<(f64, usize)> = Vec::with_capacity(degree);
+        let mut prev_positions: Vec<usize> = Vec::with_capacity(degree + 1);
+        let mut seed_dists: Vec<f64> = Vec::with_capacity(degree + 1);
+
+        for (slot, list) in out.chunks_exact_mut(degree).enumerate() {
+            let pos = first_pos + slot;
+            let query = self.get_box(pos);
+
+            // The previous leaf and its neighbors give at least `degree` real candidates,
+            // so the distance to the degree-th closest of them bounds the search radius
+            seed_dists.clear();
+            seed_dists.extend(
+                prev_positions.iter()
+                    .filter(|&&p| p != pos)
+                    .map(|&p| box_distance_sq(&query, &self.get_box(p))),
+            );
+            let bound_sq = if seed_dists.len() >= degree {
+                let (_, &mut kth, _) = seed_dists.select_nth_unstable_by(degree - 1, f64::total_cmp);
+                kth
+            } else {
+                f64::INFINITY
+            };
+
+            self.nearest_leaves(pos, |node_box| box_distance_sq(&query, node_box), degree, bound_sq, &mut found);
+
+            prev_positions.clear();
+            prev_positions.push(pos);
+            for (slot_id, &(_, leaf)) in list.iter_mut().zip(found.iter()) {
+                *slot_id = self.get_index(leaf) as usize;
+                prev_positions.push(leaf);
+            }
+        }
+    }
+
+    /// Best-first search for the `k` leaves with the smallest `dist_sq` (excluding leaf
+    /// `skip_pos`), considering only nodes within `bound_sq`. `dist_sq` must be a lower
+    /// bound for every box inside the node it is given. Fills `found` with
+    /// `(dist_sq, leaf_pos)` sorted by distance.
+    fn nearest_leaves<D>(&self, skip_pos: usize, dist_sq: D, k: usize, bound_sq: f64, found: &mut Vec<(f64, usize)>)
+    where
+        D: Fn(&Box) -> f64,
+    {
+        self.nearest_leaves_by(skip_pos, &dist_sq, &dist_sq, k, bound_sq, found);
+    }
+
+    /// `nearest_leaves()` measuring parent nodes with `node_dist` and leaves with `leaf_dist`
+    fn nearest_leaves_by<N, L>(&self, skip_pos: usize, node_dist: N, leaf_dist: L, k: usize, bound_sq: f64, found: &mut Vec<(f64, usize)>)
+    where
+        N: Fn(&Box) -> f64,
+        L: Fn(&Box) -> f64,
+    {
+        use std::collections::BinaryHeap;
+        use std::cmp::Reverse;
+
+        // Max-heap for results, min-heap for the node queue
+        let mut queue = BinaryHeap::new();
+        let mut result_heap: BinaryHeap<HeapEntry<usize>> = BinaryHeap::with_capacity(k + 1);
+        let mut max_dist_sq = bound_sq;
+
+        let root = self.total_nodes - 1;
+        queue.push(Reverse(HeapEntry { dist: node_dist(&self.get_box(root)), pos: root }));
+
+        while let Some(Reverse(entry)) = queue.pop() {
+            // Remaining entries are all farther than the current k-th result
+            if entry.dist > max_dist_sq {
+                break;
+            }
+
+            if entry.pos < self.num_items {
+                if entry.pos == skip_pos {
+                    continue;
+                }
+                result_heap.push(entry);
+                if result_heap.len() > k {
+                    result_heap.pop();
+                }
+                if result_heap.len() == k
+                    && let Some(top) = result_heap.peek()
+                {
+                    max_dist_sq = top.dist;
+                }
+            } else {
+                let children = self.children(entry.pos);
+                let children_are_leaves = children.start < self.num_items;
+                for child in children {
+                    let child_box = self.get_box(child);
+                    let child_dist_sq = if children_are_leaves { leaf_dist(&child_box) } else { node_dist(&child_box) };
+                    if child_dist_sq <= max_dist_sq {
+                        queue.push(Reverse(HeapEntry { dist: child_dist_sq, pos: child }));
+                    }
+                }
+            }
+        }
+
+        found.clear();
+        found.extend(result_heap.into_sorted_vec().into_iter().map(|e| (e.dist, e.pos)));
+    }
+
+    /// Item ids of two leaf positions as `(smaller, larger)`
+    #[inline(always)]
+    fn ordered_pair(&self, pos_a: usize, pos_b: usize) -> (usize, usize) {
+        let id_a = self.get_index(pos_a) as usize;
+        let id_b = self.get_index(pos_b) as usize;
+        (id_a.min(id_b), id_a.max(id_b))
+    }
+
+    /// Sort query indices by the Hilbert value of their centers (relative to tree bounds)
+    fn hilbert_order(&self, centers: impl Iterator<Item = (f64, f64)>) -> Vec<usize> {
+        let hilbert_width = MAX_HILBERT as f64 / (self.bounds.max_x - self.bounds.min_x);
+        let hilbert_height = MAX_HILBERT as f64 / (self.bounds.max_y - self.bounds.min_y);
+
+        let mut keyed: Vec<(u32, usize)> = centers
+            .enumerate()
+            .map(|(i, (x, y))| {
+                let scaled_x = (x - self.bounds.min_x) * hilbert_width;
+                let scaled_y = (y - self.bounds.min_y) * hilbert_height;
+                (hilbert_scaled_to_index(scaled_x, scaled_y), i)
+            })
+            .collect();
+        keyed.sort_unstable();
+        keyed.into_iter().map(|(_, i)| i).collect()
+    }
+
+    /// Run one query per entry of `order`, returning outputs in original query order
+    #[cfg(not(feature = "parallel"))]
+    fn run_batch<F>(&self, order: &[usize], query: F) -> Vec<Vec<usize>>
+    where
+        F: Fn(usize, &mut Vec<usize>) + Sync,
+    {
+        let mut outputs = vec![Vec::new(); order.len()];
+        for &i in order {
+            query(i, &mut outputs[i]);
+        }
+        outputs
+    }
+
+    /// Run one query per entry of `order` on the rayon thread pool, returning outputs
+    /// in original query order. Contiguous runs of `order` stay on one thread.
+    #[cfg(feature = "parallel")]
+    fn run_batch<F>(&self, order: &[usize], query: F) -> Vec<Vec<usize>>
+    where
+        F: Fn(usize, &mut Vec<usize>) + Sync,
+    {
+        use rayon::prelude::*;
+
+        let answered: Vec<(usize, Vec<usize>)> = order
+            .par_iter()
+            .map(|&i| {
+                let mut results = Vec::new();
+                query(i, &mut results);
+                (i, results)
+            })
+            .collect();
+
+        let mut outputs = vec![Vec::new(); order.len()];
+        for (i, results) in answered {
+            outputs[i] = results;
+        }
+        outputs
+    }
+
+    /// Retrieves the bounding box for an item by its ID.
+    ///
+    /// Returns the axis-aligned bounding box (min_x, min_y, max_x, max_y) for the item
+    /// at the given ID. This allows users to retrieve stored bounding boxes without
+    /// needing to store them separately.
+    ///
+    /// # Arguments
+    /// * `item_id` - The ID of the item (0 to `num_items - 1`)
+    ///
+    /// # Returns
+    /// `Some((min_x, min_y, max_x, max_y))` if the item exists, `None` if the ID is out of bounds.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(0.0, 0.0, 2.0, 2.0);  // Item 0
+    /// tree.add(1.0, 1.0, 3.0, 3.0);  // Item 1
+    /// tree.build();
+    ///
+    /// let bbox = tree.get(0).unwrap();
+    /// assert_eq!(bbox, (0.0, 0.0, 2.0, 2.0));
+    /// ```
+    // Note: get() method removed to eliminate sorted_order dependency
+    // Use spatial query methods instead for accessing data
+
+    /// Leaf position of an item (identity before `build()`, then the ID lookup table if
+    /// enabled, otherwise a scan of the leaf indices)
+    fn position_of(&self, item_id: usize) -> Option<usize> {
+        if item_id >= self.num_items {
+            return None;
+        }
+        if self.level_bounds.is_empty() {
+            return Some(item_id);
+        }
+        if let Some(pos) = self.table_position(item_id) {
+            return ((pos as usize) < self.num_items).then_some(pos as usize);
+        }
+        (0..self.num_items).find(|&pos| self.get_index(pos) as usize == item_id)
+    }
+
+    /// Get box at position using read_unaligned
+    #[inline(always)]
+    pub(crate) fn get_box(&self, pos: usize) -> Box {
+        let idx = HEADER_SIZE + pos * size_of::<Box>();
+        let bytes = &self.data.as_ref()[idx..idx + size_of::<Box>()];
+        unsafe {
+            std::ptr::read_unaligned(bytes.as_ptr() as *const Box)
+        }
+    }
+
+    /// Get 4 boxes at once for batch processing - single read_unaligned call
+    #[allow(dead_code)]
+    #[inline]
     pub(crate) fn get_boxes_batch(&self, start_pos: usize) -> [Box; 4] {
         let base_idx = HEADER_SIZE + start_pos * size_of::<Box>();
+        let bytes = &self.data.as_ref()[base_idx..base_idx + size_of::<[Box; 4]>()];
         unsafe {
-            std::ptr::read_unaligned(self.data.as_ptr().add(base_idx) as *const [Box; 4])
+            std::ptr::read_unaligned(bytes.as_ptr() as *const [Box; 4])
         }
     }
 
     /// Get index at position using read_unaligned
     #[inline(always)]
     pub(crate) fn get_index(&self, pos: usize) -> u32 {
-        let indices_start = HEADER_SIZE + self.total_nodes * size_of::<Box>();
+        let idx = HEADER_SIZE + self.total_nodes * size_of::<Box>() + pos * size_of::<u32>();
+        let bytes = &self.data.as_ref()[idx..idx + size_of::<u32>()];
         unsafe {
-            std::ptr::read_unaligned(&self.data[indices_start + pos * size_of::<u32>()] as *const u8 as *const u32)
+            std::ptr::read_unaligned(bytes.as_ptr() as *const u32)
         }
     }
 
@@ -1863,6 +4629,25 @@ impl HilbertRTree {
         }
     }
 
+    /// Contiguous range of leaf positions below the node at `pos` (the leaf itself for leaves)
+    #[inline]
+    fn leaf_range(&self, pos: usize) -> std::ops::Range<usize> {
+        let mut first = pos;
+        let mut last = pos;
+        while first >= self.num_items {
+            first = self.children(first).start;
+            last = self.children(last).end - 1;
+        }
+        first..last + 1
+    }
+
+    /// Positions of the children of the parent node at `pos`
+    #[inline(always)]
+    fn children(&self, pos: usize) -> std::ops::Range<usize> {
+        let first_child = (self.get_index(pos) >> 2) as usize;
+        first_child..(first_child + self.node_size).min(self.upper_bound(first_child))
+    }
+
     /// Find upper bound of a node in `level_bounds`
     #[inline(always)]
     fn upper_bound(&self, node_index: usize) -> usize {
@@ -1881,6 +4666,10 @@ impl HilbertRTree {
     /// to enable fast loading without rebuilding. The file format includes a magic number and version
     /// for integrity checking during load.
     ///
+    /// Trees without weights, ID lookup table or period are written as version 1, which every
+    /// release can load. Any of those optional sections makes the file version 2, so readers
+    /// that only understand version 1 reject it instead of silently dropping the sections.
+    ///
     /// # Arguments
     /// * `path` - File path where the tree will be saved
     ///
@@ -1896,12 +4685,44 @@ impl HilbertRTree {
     /// tree.save("my_tree.bin")?;
     /// ```
     pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
-        use std::io::Write;
         let mut file = std::fs::File::create(path)?;
-        
+        self.write_to(&mut file)
+    }
+
+    /// Serializes the tree into a byte vector in the same format as `save()`.
+    ///
+    /// The bytes can be queried in place with [`HilbertRTreeView::from_bytes`], for example
+    /// after embedding them with `include_bytes!`.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);
+    /// tree.add(2.0, 2.0, 3.0, 3.0);
+    /// tree.build();
+    ///
+    /// let bytes = tree.to_bytes();
+    /// let view = HilbertRTreeView::from_bytes(&bytes).unwrap();
+    /// assert_eq!(view.get(1), Some((2.0, 2.0, 3.0, 3.0)));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        // Writing into a Vec cannot fail
+        if self.write_to(&mut bytes).is_err() {
+            bytes.clear();
+        }
+        bytes
+    }
+
+    /// Write the `save()` format to any writer
+    fn write_to<W: std::io::Write>(&self, file: &mut W) -> std::io::Result<()> {
         // Write magic number and version (file header for validation)
+        // Version 2 when optional trailing sections follow the data, so that version-1
+        // readers (which ignore anything after the data) reject the file
+        let has_sections = self.is_weighted() || self.has_id_table() || self.period.is_some() || self.has_aggregates();
         file.write_all(&[0xfb])?;  // magic (f64 variant)
-        file.write_all(&[0x01])?;  // version 1 (f64 variant)
+        file.write_all(&[if has_sections { 0x02 } else { 0x01 }])?;  // version (f64 variant)
         
         // Write node_size
         file.write_all(&(self.node_size as u32).to_le_bytes())?;
@@ -1927,16 +4748,193 @@ impl HilbertRTree {
         file.write_all(&self.bounds.max_y.to_le_bytes())?;
         
         // Write data buffer
-        file.write_all(&(self.data.len() as u32).to_le_bytes())?;
-        file.write_all(&self.data)?;
+        let node_bytes = self.node_bytes();
+        file.write_all(&(node_bytes.len() as u32).to_le_bytes())?;
+        file.write_all(node_bytes)?;
+
+        // Optional trailing sections, each starting with a tag byte:
+        // 0x01 item weights, 0x02 ID lookup table (leaf position of every item id),
+        // 0x03 world period (width, height), 0x04 weight aggregate of every node
+        // (count as u64, sum, min, max) so views can answer `aggregate_in_rect()` in place
+        if self.is_weighted() {
+            file.write_all(&[0x01])?;
+            for item_id in 0..self.num_items {
+                file.write_all(&self.weight(item_id).to_le_bytes())?;
+            }
+        }
+        if self.has_id_table() {
+            file.write_all(&[0x02])?;
+            for item_id in 0..self.num_items {
+                let pos = self.table_position(item_id).unwrap_or_default();
+                file.write_all(&pos.to_le_bytes())?;
+            }
+        }
+        if let Some((width, height)) = self.period {
+            file.write_all(&[0x03])?;
+            file.write_all(&width.to_le_bytes())?;
+            file.write_all(&height.to_le_bytes())?;
+        }
+        if self.has_aggregates() {
+            file.write_all(&[0x04])?;
+            for pos in 0..self.total_nodes {
+                let aggregate = self.node_aggregate(pos);
+                file.write_all(&(aggregate.count as u64).to_le_bytes())?;
+                file.write_all(&aggregate.sum.to_le_bytes())?;
+                file.write_all(&aggregate.min.to_le_bytes())?;
+                file.write_all(&aggregate.max.to_le_bytes())?;
+            }
+        }
         
         Ok(())
     }
 
+    /// Checks the whole structure of a built tree.
+    ///
+    /// `HilbertRTreeView::from_bytes()` only checks the header so that opening does not
+    /// depend on the tree size. Call this once (O(n)) before querying bytes that may be
+    /// corrupt or untrusted: it verifies that every leaf holds a valid item id, every parent
+    /// node points into the level below, and the ID lookup table (if saved) matches the
+    /// leaves. `load()` runs it on every file.
+    ///
+    /// # Errors
+    /// Returns an `InvalidData` error describing the first inconsistency found.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);
+    /// tree.add(2.0, 2.0, 3.0, 3.0);
+    /// tree.build();
+    /// let bytes = tree.to_bytes();
+    ///
+    /// let view = HilbertRTreeView::from_bytes(&bytes).unwrap();
+    /// assert!(view.validate().is_ok());
+    /// ```
+    pub fn validate(&self) -> std::io::Result<()> {
+        self.check_header()?;
+        if (0..self.num_items).any(|pos| self.get_index(pos) as usize >= self.num_items) {
+            return Err(invalid_data("Invalid file format: leaf has an out-of-range item id"));
+        }
+        for level in 1..self.level_bounds.len() {
+            let children = if level == 1 { 0 } else { self.level_bounds[level - 2] }..self.level_bounds[level - 1];
+            for pos in self.level_bounds[level - 1]..self.level_bounds[level] {
+                let index = self.get_index(pos) as usize;
+                if !index.is_multiple_of(4) || !children.contains(&(index / 4)) {
+                    return Err(invalid_data("Invalid file format: node has an out-of-range child"));
+                }
+            }
+        }
+        let table_ok = !self.has_id_table() || (0..self.num_items).all(|item_id| {
+            self.table_position(item_id)
+                .is_some_and(|pos| (pos as usize) < self.num_items && self.get_index(pos as usize) as usize == item_id)
+        });
+        if !table_ok {
+            return Err(invalid_data("Invalid file format: ID lookup table does not match the leaves"));
+        }
+        Ok(())
+    }
+
+    /// Checks what takes constant time: node size, period, level bounds and that every node fits
+    fn check_header(&self) -> std::io::Result<()> {
+        let node_bytes = size_of::<Box>() + size_of::<u32>();
+        let needed = self.total_nodes.checked_mul(node_bytes).and_then(|n| n.checked_add(HEADER_SIZE));
+        if self.node_size < 2 || needed.is_none_or(|needed| self.node_bytes().len() < needed) {
+            return Err(invalid_data("Invalid file format: truncated or inconsistent tree data"));
+        }
+        if self.period.is_some_and(|(width, height)| !is_valid_period(width, height)) {
+            return Err(invalid_data("Invalid file format: period must be positive"));
+        }
+        if self.level_bounds.is_empty() {
+            return if self.num_items == 0 && self.total_nodes == 0 {
+                Ok(())
+            } else {
+                Err(invalid_data("Invalid file format: missing level bounds"))
+            };
+        }
+        let levels_ok = self.level_bounds[0] == self.num_items
+            && self.level_bounds.last() == Some(&self.total_nodes)
+            && self.level_bounds.windows(2).all(|w| w[0] < w[1]);
+        if !levels_ok {
+            return Err(invalid_data("Invalid file format: inconsistent level bounds"));
+        }
+        Ok(())
+    }
+
+    /// Exports the built tree in the Flatbush v3 binary layout with `Float64Array` coordinates.
+    ///
+    /// The bytes can be loaded in JavaScript with `Flatbush.from(buffer)` and queried there
+    /// without rebuilding; item ids are the same on both sides. Only the index itself is
+    /// exported (no weights, ID lookup table or period). See [`crate::flatbush`] for the layout.
+    ///
+    /// # Panics
+    /// Panics if the tree has not been built or is empty (Flatbush cannot represent an empty index).
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);
+    /// tree.add(2.0, 2.0, 3.0, 3.0);
+    /// tree.build();
+    ///
+    /// let bytes = tree.to_flatbush_bytes();
+    /// assert_eq!(&bytes[..2], &[0xfb, 0x38]);  // magic, version 3 + Float64Array
+    ///
+    /// let copy = AABB::from_flatbush_bytes(&bytes).unwrap();
+    /// let mut results = Vec::new();
+    /// copy.query_intersecting(0.5, 0.5, 2.5, 2.5, &mut results);
+    /// assert_eq!(results.len(), 2);
+    /// ```
+    pub fn to_flatbush_bytes(&self) -> Vec<u8> {
+        self.to_flatbush_bytes_as(FlatbushArrayType::Float64)
+    }
+
+    /// Exports the built tree in the Flatbush v3 binary layout with the given coordinate type.
+    ///
+    /// Coordinates are converted like a JavaScript typed array stores them, matching
+    /// what Flatbush itself would hold for the same data: nearest value for `Float32`,
+    /// truncation toward zero for integer types (values must be in range).
+    /// Because the conversion preserves order, every parent box still encloses its children.
+    ///
+    /// # Arguments
+    /// * `array_type` - Coordinate type of the exported buffer
+    ///
+    /// # Panics
+    /// Panics if the tree has not been built or is empty (Flatbush cannot represent an empty index).
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(0.0, 0.0, 100.0, 100.0);
+    /// tree.add(200.0, 200.0, 300.0, 300.0);
+    /// tree.build();
+    ///
+    /// // 8-byte header, 3 nodes * 4 * 2 bytes of coordinates, 3 * 2 bytes of indices
+    /// let bytes = tree.to_flatbush_bytes_as(FlatbushArrayType::Uint16);
+    /// assert_eq!(bytes.len(), 8 + 24 + 6);
+    /// ```
+    pub fn to_flatbush_bytes_as(&self, array_type: FlatbushArrayType) -> Vec<u8> {
+        assert!(!self.level_bounds.is_empty() && self.num_items > 0, "tree must be built with at least one item");
+        let layout = FlatbushLayout::new(array_type, self.node_size, self.num_items);
+        layout.encode(
+            (0..self.total_nodes).map(|pos| {
+                let node_box = self.get_box(pos);
+                [node_box.min_x, node_box.min_y, node_box.max_x, node_box.max_y]
+            }),
+            (0..self.total_nodes).map(|pos| self.get_index(pos)),
+        )
+    }
+
+}
+
+impl HilbertRTree {
     /// Loads a Hilbert R-tree from a file.
     ///
     /// Deserializes a tree that was previously saved with `save()`.
-    /// Validates the file format by checking the magic number and version.
+    /// Validates the file format by checking the magic number and version, then the
+    /// whole tree structure (see `validate()`).
     /// The loaded tree is immediately ready for querying without rebuilding.
     ///
     /// # Arguments
@@ -1953,82 +4951,345 @@ impl HilbertRTree {
     /// tree.query_intersecting(0.0, 0.0, 1.0, 1.0, &mut results);
     /// ```
     pub fn load<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
-        use std::io::Read;
-        let mut file = std::fs::File::open(path)?;
-        
-        // Read and validate magic number
-        let mut magic_buf = [0u8; 1];
-        file.read_exact(&mut magic_buf)?;
-        if magic_buf[0] != 0xfb {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Invalid file format: magic number mismatch",
-            ));
+        let bytes = std::fs::read(path)?;
+        let view = HilbertRTreeView::from_bytes(&bytes)?;
+        view.validate()?;
+        Ok(view.to_owned_tree())
+    }
+
+    /// Imports a Flatbush v3 buffer as a built tree.
+    ///
+    /// Accepts `index.data` produced by Flatbush in JavaScript (or by `to_flatbush_bytes()`)
+    /// with any coordinate array type; coordinates are widened to f64. The tree is ready
+    /// for querying without rebuilding and keeps Flatbush's item ids.
+    ///
+    /// # Arguments
+    /// * `bytes` - The complete Flatbush buffer
+    ///
+    /// # Errors
+    /// Returns an `InvalidData` error if the magic number, version or array type is wrong,
+    /// the length does not match the header, or a node index points outside the tree.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// // Flatbush with Uint8Array coordinates holding boxes (1, 2, 3, 4) and (5, 6, 7, 8)
+    /// let bytes = [
+    ///     0xfb, 0x31, 16, 0, 2, 0, 0, 0,      // header
+    ///     1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 7, 8, // two leaves and the root
+    ///     0, 0, 1, 0, 0, 0,                   // u16 indices
+    /// ];
+    /// let tree = AABB::from_flatbush_bytes(&bytes).unwrap();
+    /// assert_eq!(tree.get(1), Some((5.0, 6.0, 7.0, 8.0)));
+    /// ```
+    pub fn from_flatbush_bytes(bytes: &[u8]) -> std::io::Result<Self> {
+        let layout = FlatbushLayout::parse(bytes)?;
+        let total_nodes = layout.num_nodes;
+
+        let mut data = vec![0_u8; HEADER_SIZE + total_nodes * (size_of::<Box>() + size_of::<u32>())];
+        data[0] = 0xfb; // magic
+        data[1] = 0x01; // version 1 + double type (8)
+        data[2..8].copy_from_slice(&bytes[2..8]); // node_size and num_items
+        let indices_start = HEADER_SIZE + total_nodes * size_of::<Box>();
+        for pos in 0..total_nodes {
+            let [min_x, min_y, max_x, max_y] = layout.read_box(bytes, pos);
+            let box_ptr = &mut data[HEADER_SIZE + pos * size_of::<Box>()] as *mut u8 as *mut Box;
+            let idx_ptr = &mut data[indices_start + pos * size_of::<u32>()] as *mut u8 as *mut u32;
+            unsafe {
+                std::ptr::write_unaligned(box_ptr, Box::new(min_x, min_y, max_x, max_y));
+                std::ptr::write_unaligned(idx_ptr, layout.read_index(bytes, pos));
+            }
         }
-        
-        // Read and validate version
-        let mut version_buf = [0u8; 1];
-        file.read_exact(&mut version_buf)?;
-        if version_buf[0] != 0x01 {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Unsupported file version (expected f64 variant v1, got different version)",
-            ));
+
+        let [min_x, min_y, max_x, max_y] = layout.read_box(bytes, total_nodes - 1);
+        let allocated_capacity = data.capacity();
+        Ok(Self {
+            data,
+            level_bounds: layout.level_bounds,
+            node_size: layout.node_size,
+            num_items: layout.num_items,
+            position: 0,
+            bounds: Box::new(min_x, min_y, max_x, max_y),
+            total_nodes,
+            allocated_capacity,
+            weights: Vec::new(),
+            aggregates: Vec::new(),
+            id_lookup: false,
+            positions: Vec::new(),
+            period: None,
+            sections: None,
+        })
+    }
+}
+
+impl<'a> HilbertRTree<&'a [u8]> {
+    /// Opens a read-only view over bytes in the `save()` / `to_bytes()` format.
+    ///
+    /// Only the header is parsed: the magic number, version, level bounds and section
+    /// lengths are checked against the buffer size, so opening takes the same time for any
+    /// tree size. Node boxes and indices, the optional weights and the ID lookup table are
+    /// read in place from `bytes`; nothing is copied or rebuilt. The world period is kept.
+    ///
+    /// Node indices are not checked here. For bytes that may be corrupt or untrusted, call
+    /// `validate()` once before querying; otherwise a damaged buffer can make queries panic
+    /// or return wrong results (they never read outside `bytes`).
+    ///
+    /// # Arguments
+    /// * `bytes` - The complete serialized tree
+    ///
+    /// # Errors
+    /// Returns an `InvalidData` error if the magic number or version is wrong, the buffer is
+    /// truncated, or the header is inconsistent.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(3);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);
+    /// tree.add(2.0, 2.0, 3.0, 3.0);
+    /// tree.add(5.0, 5.0, 6.0, 6.0);
+    /// tree.build();
+    /// let bytes = tree.to_bytes();
+    ///
+    /// let view = HilbertRTreeView::from_bytes(&bytes).unwrap();
+    /// let mut results = Vec::new();
+    /// view.query_intersecting(0.5, 0.5, 2.5, 2.5, &mut results);
+    /// results.sort_unstable();
+    /// assert_eq!(results, vec![0, 1]);
+    /// ```
+    pub fn from_bytes(bytes: &'a [u8]) -> std::io::Result<Self> {
+        let mut reader = ByteReader { bytes, pos: 0 };
+        if reader.take(1)? != [0xfb] {
+            return Err(invalid_data("Invalid file format: magic number mismatch"));
         }
-        
-        // Read node_size
-        let mut buf = [0u8; 4];
-        file.read_exact(&mut buf)?;
-        let node_size = u32::from_le_bytes(buf) as usize;
-        
-        // Read num_items
-        file.read_exact(&mut buf)?;
-        let num_items = u32::from_le_bytes(buf) as usize;
-        
-        // Read total_nodes
-        file.read_exact(&mut buf)?;
-        let total_nodes = u32::from_le_bytes(buf) as usize;
-        
-        // Read level_bounds length
-        file.read_exact(&mut buf)?;
-        let level_bounds_len = u32::from_le_bytes(buf) as usize;
-        
-        // Read level_bounds
-        let mut level_bounds = Vec::with_capacity(level_bounds_len);
+        let version = reader.take(1)?[0];
+        if version != 0x01 && version != 0x02 {
+            return Err(invalid_data("Unsupported file version (expected f64 variant v1 or v2, got different version)"));
+        }
+        let node_size = reader.read_u32()? as usize;
+        let num_items = reader.read_u32()? as usize;
+        let total_nodes = reader.read_u32()? as usize;
+        let level_bounds_len = reader.read_u32()? as usize;
+        let mut level_bounds = Vec::with_capacity(level_bounds_len.min(64));
         for _ in 0..level_bounds_len {
-            file.read_exact(&mut buf)?;
-            level_bounds.push(u32::from_le_bytes(buf) as usize);
+            level_bounds.push(reader.read_u32()? as usize);
         }
-        
-        // Read bounds
-        let mut f64_buf = [0u8; 8];
-        file.read_exact(&mut f64_buf)?;
-        let min_x = f64::from_le_bytes(f64_buf);
-        file.read_exact(&mut f64_buf)?;
-        let min_y = f64::from_le_bytes(f64_buf);
-        file.read_exact(&mut f64_buf)?;
-        let max_x = f64::from_le_bytes(f64_buf);
-        file.read_exact(&mut f64_buf)?;
-        let max_y = f64::from_le_bytes(f64_buf);
-        
-        let bounds = Box::new(min_x, min_y, max_x, max_y);
-        
-        // Read data buffer
-        file.read_exact(&mut buf)?;
-        let data_len = u32::from_le_bytes(buf) as usize;
-        let mut data = vec![0u8; data_len];
-        file.read_exact(&mut data)?;
-        
-        Ok(Self {
-            data,
+        let min_x = reader.read_f64()?;
+        let min_y = reader.read_f64()?;
+        let max_x = reader.read_f64()?;
+        let max_y = reader.read_f64()?;
+        let data_len = reader.read_u32()? as usize;
+        let data_start = reader.skip(data_len)?;
+
+        // Sections are only located here; queries read them in place
+        let mut sections = SavedSections { nodes_end: data_len, weights: None, positions: None, aggregates: None };
+        let mut period = None;
+        if version == 0x01 && reader.pos < bytes.len() {
+            return Err(invalid_data("Invalid file format: trailing sections in a version 1 file"));
+        }
+        while let Ok(tag) = reader.take(1) {
+            match tag[0] {
+                0x01 => sections.weights = Some(reader.skip(num_items * size_of::<f64>())? - data_start),
+                0x02 => sections.positions = Some(reader.skip(num_items * size_of::<u32>())? - data_start),
+                0x03 => period = Some((reader.read_f64()?, reader.read_f64()?)),
+                0x04 => sections.aggregates = Some(reader.skip(total_nodes * AGGREGATE_SIZE)? - data_start),
+                _ => return Err(invalid_data("Invalid file format: unknown trailing section")),
+            }
+        }
+
+        let tree = Self {
+            data: &bytes[data_start..],
             level_bounds,
             node_size,
             num_items,
             position: 0,
-            bounds,
+            bounds: Box::new(min_x, min_y, max_x, max_y),
             total_nodes,
-            allocated_capacity: data_len,
-        })
+            allocated_capacity: 0,
+            weights: Vec::new(),
+            aggregates: Vec::new(),
+            id_lookup: sections.positions.is_some(),
+            positions: Vec::new(),
+            period,
+            sections: Some(sections),
+        };
+        tree.check_header()?;
+        Ok(tree)
+    }
+
+    /// Copy the view into an owned tree
+    fn to_owned_tree(&self) -> HilbertRTree {
+        let data = self.node_bytes().to_vec();
+        let weights = if self.is_weighted() {
+            (0..self.num_items).map(|item_id| self.weight(item_id)).collect()
+        } else {
+            Vec::new()
+        };
+        let positions = if self.has_id_table() {
+            (0..self.num_items).map(|item_id| self.table_position(item_id).unwrap_or_default()).collect()
+        } else {
+            Vec::new()
+        };
+        let aggregates = if self.has_aggregates() {
+            (0..self.total_nodes).map(|pos| self.node_aggregate(pos)).collect()
+        } else {
+            Vec::new()
+        };
+        HilbertRTree {
+            allocated_capacity: data.len(),
+            data,
+            level_bounds: self.level_bounds.clone(),
+            node_size: self.node_size,
+            num_items: self.num_items,
+            position: 0,
+            bounds: self.bounds,
+            total_nodes: self.total_nodes,
+            weights,
+            aggregates,
+            id_lookup: self.id_lookup,
+            positions,
+            period: self.period,
+            sections: None,
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl HilbertRTree {
+    /// Opens a file written by `save()` as a read-only memory map (requires the `mmap` feature).
+    ///
+    /// Like [`HilbertRTreeView::from_bytes`], only the header is parsed and checked; nodes,
+    /// weights and the ID lookup table are then queried in place. Nothing is copied or
+    /// rebuilt, so opening costs the same for any file size, and processes that open the same
+    /// file share one copy in the OS page cache. Pages are read from disk on first access.
+    /// Call `validate()` once (O(n)) if the file may be corrupt or untrusted.
+    ///
+    /// The file must not be modified or truncated while the tree is open; write a new file
+    /// and rename it over the old one instead.
+    ///
+    /// # Arguments
+    /// * `path` - File previously written by `save()`
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be opened or mapped, or `InvalidData` if its
+    /// header is not that of a saved tree.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABB::with_capacity(2);
+    /// tree.add(0.0, 0.0, 1.0, 1.0);
+    /// tree.add(2.0, 2.0, 3.0, 3.0);
+    /// tree.build();
+    /// let path = std::env::temp_dir().join("aabb_open_mmap_doc.bin");
+    /// tree.save(&path).unwrap();
+    ///
+    /// let mapped = AABB::open_mmap(&path).unwrap();
+    /// let mut results = Vec::new();
+    /// mapped.query_intersecting(2.5, 2.5, 4.0, 4.0, &mut results);
+    /// assert_eq!(results, vec![1]);
+    /// # drop(mapped);
+    /// # std::fs::remove_file(&path).ok();
+    /// ```
+    pub fn open_mmap<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<HilbertRTreeMmap> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the map is read-only; the caller must not modify the file while it is mapped,
+        // as documented above. Queries only read through bounds-checked slices of the map.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let (tree, start) = {
+            let view = HilbertRTreeView::from_bytes(&map)?;
+            let start = view.data.as_ptr().addr() - map.as_ptr().addr();
+            (view.with_data(()), start)
+        };
+        Ok(tree.with_data(MmapBuffer { map, start }))
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<S> HilbertRTree<S> {
+    /// The same tree over different storage holding the same node bytes
+    fn with_data<T>(self, data: T) -> HilbertRTree<T> {
+        HilbertRTree {
+            data,
+            level_bounds: self.level_bounds,
+            node_size: self.node_size,
+            num_items: self.num_items,
+            position: self.position,
+            bounds: self.bounds,
+            total_nodes: self.total_nodes,
+            allocated_capacity: self.allocated_capacity,
+            weights: self.weights,
+            aggregates: self.aggregates,
+            id_lookup: self.id_lookup,
+            positions: self.positions,
+            period: self.period,
+            sections: self.sections,
+        }
+    }
+}
+
+/// Little-endian cursor over a borrowed buffer, failing with `InvalidData` when it runs out
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> std::io::Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len());
+        let Some(end) = end else {
+            return Err(invalid_data("Invalid file format: unexpected end of data"));
+        };
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    /// Advance past `len` bytes, returning where they start
+    fn skip(&mut self, len: usize) -> std::io::Result<usize> {
+        let start = self.pos;
+        self.take(len).map(|_| start)
+    }
+
+    fn read_u32(&mut self) -> std::io::Result<u32> {
+        let mut buf = [0_u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_f64(&mut self) -> std::io::Result<f64> {
+        let mut buf = [0_u8; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(f64::from_le_bytes(buf))
+    }
+}
+
+/// Whether both axes of a world period are positive (infinite means the axis does not wrap)
+fn is_valid_period(width: f64, height: f64) -> bool {
+    width > 0.0 && height > 0.0
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+impl<S: AsRef<[u8]> + Sync> JoinNodes for HilbertRTree<S> {
+    type NodeBox = Box;
+
+    fn num_leaves(&self) -> usize {
+        self.num_items
+    }
+
+    fn root(&self) -> Option<usize> {
+        (self.num_items > 0 && !self.level_bounds.is_empty()).then(|| self.total_nodes - 1)
+    }
+
+    fn node_box(&self, pos: usize) -> Box {
+        self.get_box(pos)
+    }
+
+    fn node_children(&self, pos: usize) -> std::ops::Range<usize> {
+        self.children(pos)
     }
 }
 
@@ -2038,63 +5299,326 @@ impl Default for HilbertRTree {
     }
 }
 
-/// Hilbert curve index computation
-/// From <https://github.com/rawrunprotected/hilbert_curves> (public domain)
+/// Helper: Priority queue entry ordered by distance, then by node position(s) so that
+/// equal distances pop in a fixed order. `BinaryHeap<HeapEntry<_>>` is a max-heap;
+/// wrap entries in `Reverse` for a min-heap.
+#[derive(Clone, Copy, Debug)]
+struct HeapEntry<P> {
+    dist: f64,
+    pos: P,
+}
+
+impl<P: Ord> Ord for HeapEntry<P> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.dist.total_cmp(&other.dist).then_with(|| self.pos.cmp(&other.pos))
+    }
+}
+
+impl<P: Ord> PartialOrd for HeapEntry<P> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<P: Ord> PartialEq for HeapEntry<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl<P: Ord> Eq for HeapEntry<P> {}
+
+/// Helper: Time of impact of `moving` translated by (`move_x`, `move_y`) against `target`.
+/// Returns the fraction of the movement in `[0, 1]` and the contact normal of `target`,
+/// or `None` if the boxes never touch during the movement.
+#[inline]
+fn swept_time_of_impact(moving: &Box, move_x: f64, move_y: f64, target: &Box) -> Option<(f64, (f64, f64))> {
+    // Entry and exit times along one axis (slab test)
+    #[inline(always)]
+    fn axis_times(min: f64, max: f64, velocity: f64, target_min: f64, target_max: f64) -> Option<(f64, f64)> {
+        if velocity > 0.0 {
+            Some(((target_min - max) / velocity, (target_max - min) / velocity))
+        } else if velocity < 0.0 {
+            Some(((target_max - min) / velocity, (target_min - max) / velocity))
+        } else if max >= target_min && min <= target_max {
+            Some((f64::NEG_INFINITY, f64::INFINITY))
+        } else {
+            None
+        }
+    }
+
+    let (entry_x, exit_x) = axis_times(moving.min_x, moving.max_x, move_x, target.min_x, target.max_x)?;
+    let (entry_y, exit_y) = axis_times(moving.min_y, moving.max_y, move_y, target.min_y, target.max_y)?;
+
+    let entry = entry_x.max(entry_y);
+    let exit = exit_x.min(exit_y);
+    if entry > exit || exit < 0.0 || entry > 1.0 {
+        return None;
+    }
+
+    if entry <= 0.0 {
+        // Already overlapping at the start of the movement
+        return Some((0.0, (0.0, 0.0)));
+    }
+
+    let normal = if entry_x >= entry_y {
+        (-move_x.signum(), 0.0)
+    } else {
+        (0.0, -move_y.signum())
+    };
+    Some((entry, normal))
+}
+
+/// Helper: Whether two boxes intersect (touching edges or corners count)
+#[inline(always)]
+fn boxes_intersect(a: &Box, b: &Box) -> bool {
+    a.max_x >= b.min_x && a.max_y >= b.min_y && a.min_x <= b.max_x && a.min_y <= b.max_y
+}
+
+/// Helper: Whether box `outer` completely contains box `inner`
+#[inline(always)]
+fn box_contains(outer: &Box, inner: &Box) -> bool {
+    outer.min_x <= inner.min_x && outer.min_y <= inner.min_y && outer.max_x >= inner.max_x && outer.max_y >= inner.max_y
+}
+
+/// Helper: Squared Euclidean distance between the closest points of two boxes (0 if they intersect)
+#[inline(always)]
+fn box_distance_sq(a: &Box, b: &Box) -> f64 {
+    let dx = (b.min_x - a.max_x).max(a.min_x - b.max_x).max(0.0);
+    let dy = (b.min_y - a.max_y).max(a.min_y - b.max_y).max(0.0);
+    dx * dx + dy * dy
+}
+
+/// Helper: Squared distance from the point of `region` farthest from `target` to `target`
+/// (the largest box-to-point gap over all points of `region`)
+#[inline]
+fn farthest_gap_sq(region: &Box, target: &Box) -> f64 {
+    let dx = (target.min_x - region.min_x).max(region.max_x - target.max_x).max(0.0);
+    let dy = (target.min_y - region.min_y).max(region.max_y - target.max_y).max(0.0);
+    dx * dx + dy * dy
+}
+
+/// Helper: Squared distance between the two farthest points of two boxes
+#[inline]
+fn farthest_point_sq(a: &Box, b: &Box) -> f64 {
+    let dx = (a.max_x - b.min_x).max(b.max_x - a.min_x);
+    let dy = (a.max_y - b.min_y).max(b.max_y - a.min_y);
+    dx * dx + dy * dy
+}
+
+/// Helper: Distance from a coordinate to the interval [min, max] along an axis that wraps
+/// around with the given period (shortest way around; non-finite period means no wrap)
+#[inline(always)]
+fn wrapped_axis_distance(coordinate: f64, min: f64, max: f64, period: f64) -> f64 {
+    if !period.is_finite() {
+        return (min - coordinate).max(coordinate - max).max(0.0);
+    }
+    let extent = max - min;
+    if extent >= period {
+        return 0.0;
+    }
+    // Offset of the coordinate past `min`, in [0, period)
+    let offset = (coordinate - min).rem_euclid(period);
+    if offset <= extent {
+        0.0
+    } else {
+        (offset - extent).min(period - offset)
+    }
+}
+
+/// Helper: Whether closed intervals `[a_min, a_max]` and `[b_min, b_max]` overlap along an axis
+/// that wraps around with the given period (non-finite period means no wrap)
 #[inline(always)]
-fn interleave(mut x: u32) -> u32 {
-    x = (x | (x << 8)) & 0x00FF00FF;
-    x = (x | (x << 4)) & 0x0F0F0F0F;
-    x = (x | (x << 2)) & 0x33333333;
-    x = (x | (x << 1)) & 0x55555555;
-    x
+fn wrapped_axis_overlaps(a_min: f64, a_max: f64, b_min: f64, b_max: f64, period: f64) -> bool {
+    if !period.is_finite() {
+        return a_min <= b_max && b_min <= a_max;
+    }
+    let (a_extent, b_extent) = (a_max - a_min, b_max - b_min);
+    if a_extent + b_extent >= period {
+        return true;
+    }
+    // Start of b past the start of a, in [0, period)
+    let offset = (b_min - a_min).rem_euclid(period);
+    offset <= a_extent || offset >= period - b_extent
+}
+
+/// Helper: Squared distance between segment (x1, y1)-(x2, y2) and a box (zero if they touch)
+#[inline]
+fn segment_box_distance_sq(x1: f64, y1: f64, x2: f64, y2: f64, b: &Box) -> f64 {
+    if clip_segment_to_box(x1, y1, x2, y2, b).is_some() {
+        return 0.0;
+    }
+    // Disjoint convex shapes: the closest pair involves a segment endpoint or a box corner
+    let point_box = |x: f64, y: f64| {
+        let dx = (b.min_x - x).max(x - b.max_x).max(0.0);
+        let dy = (b.min_y - y).max(y - b.max_y).max(0.0);
+        dx * dx + dy * dy
+    };
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let len_sq = dx * dx + dy * dy;
+    let point_segment = |x: f64, y: f64| {
+        let t = if len_sq > 0.0 { (((x - x1) * dx + (y - y1) * dy) / len_sq).clamp(0.0, 1.0) } else { 0.0 };
+        let (ex, ey) = (x1 + t * dx - x, y1 + t * dy - y);
+        ex * ex + ey * ey
+    };
+    point_box(x1, y1)
+        .min(point_box(x2, y2))
+        .min(point_segment(b.min_x, b.min_y))
+        .min(point_segment(b.min_x, b.max_y))
+        .min(point_segment(b.max_x, b.min_y))
+        .min(point_segment(b.max_x, b.max_y))
+}
+
+/// Oriented rectangle prepared for separating axis tests against boxes
+#[derive(Clone, Copy, Debug)]
+struct OrientedBox {
+    center_x: f64,
+    center_y: f64,
+    half_width: f64,
+    half_height: f64,
+    /// Local x axis (cos, sin)
+    cos: f64,
+    sin: f64,
+    /// Axis-aligned bounds of the rotated rectangle
+    bounds: Box,
+}
+
+impl OrientedBox {
+    fn new(center_x: f64, center_y: f64, half_width: f64, half_height: f64, angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        let extent_x = half_width * cos.abs() + half_height * sin.abs();
+        let extent_y = half_width * sin.abs() + half_height * cos.abs();
+        Self {
+            center_x,
+            center_y,
+            half_width,
+            half_height,
+            cos,
+            sin,
+            bounds: Box::new(
+                center_x - extent_x,
+                center_y - extent_y,
+                center_x + extent_x,
+                center_y + extent_y,
+            ),
+        }
+    }
+
+    /// Separating axis test: world x/y axes first, then the rectangle's own axes
+    #[inline]
+    fn intersects(&self, b: &Box) -> bool {
+        if self.bounds.max_x < b.min_x || self.bounds.max_y < b.min_y
+            || self.bounds.min_x > b.max_x || self.bounds.min_y > b.max_y
+        {
+            return false;
+        }
+
+        let box_half_x = (b.max_x - b.min_x) / 2.0;
+        let box_half_y = (b.max_y - b.min_y) / 2.0;
+        let dx = (b.min_x + b.max_x) / 2.0 - self.center_x;
+        let dy = (b.min_y + b.max_y) / 2.0 - self.center_y;
+
+        // Local x axis (cos, sin)
+        let dist_u = (dx * self.cos + dy * self.sin).abs();
+        let radius_u = box_half_x * self.cos.abs() + box_half_y * self.sin.abs();
+        if dist_u > self.half_width + radius_u {
+            return false;
+        }
+
+        // Local y axis (-sin, cos)
+        let dist_v = (dy * self.cos - dx * self.sin).abs();
+        let radius_v = box_half_x * self.sin.abs() + box_half_y * self.cos.abs();
+        dist_v <= self.half_height + radius_v
+    }
+}
+
+/// Helper: Even-odd test whether a point lies inside a polygon
+#[inline]
+fn point_in_polygon(x: f64, y: f64, polygon: &[(f64, f64)]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (xi, yi) = polygon[i];
+        let (xj, yj) = polygon[j];
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
 }
 
-#[expect(non_snake_case)]
+/// Helper: Clip segment (x0, y0)-(x1, y1) against a box (Liang-Barsky).
+/// Returns the parametric range `[t0, t1]` of the segment inside the closed box.
 #[inline]
-fn hilbert_xy_to_index(x: u32, y: u32) -> u32 {
-    // Initial prefix scan round, prime with x and y
-    let mut a = x ^ y;
-    let mut b = 0xFFFF ^ a;
-    let mut c = 0xFFFF ^ (x | y);
-    let mut d = x & (y ^ 0xFFFF);
-    let mut A = a | (b >> 1);
-    let mut B = (a >> 1) ^ a;
-    let mut C = ((c >> 1) ^ (b & (d >> 1))) ^ c;
-    let mut D = ((a & (c >> 1)) ^ (d >> 1)) ^ d;
-
-    a = A;
-    b = B;
-    c = C;
-    d = D;
-    A = (a & (a >> 2)) ^ (b & (b >> 2));
-    B = (a & (b >> 2)) ^ (b & ((a ^ b) >> 2));
-    C ^= (a & (c >> 2)) ^ (b & (d >> 2));
-    D ^= (b & (c >> 2)) ^ ((a ^ b) & (d >> 2));
-
-    a = A;
-    b = B;
-    c = C;
-    d = D;
-    A = (a & (a >> 4)) ^ (b & (b >> 4));
-    B = (a & (b >> 4)) ^ (b & ((a ^ b) >> 4));
-    C ^= (a & (c >> 4)) ^ (b & (d >> 4));
-    D ^= (b & (c >> 4)) ^ ((a ^ b) & (d >> 4));
-
-    // Final round and projection
-    a = A;
-    b = B;
-    c = C;
-    d = D;
-    C ^= (a & (c >> 8)) ^ (b & (d >> 8));
-    D ^= (b & (c >> 8)) ^ ((a ^ b) & (d >> 8));
-
-    // Undo transformation prefix scan
-    a = C ^ (C >> 1);
-    b = D ^ (D >> 1);
-
-    // Recover index bits
-    let i0 = x ^ y;
-    let i1 = b | (0xFFFF ^ (i0 | a));
-
-    (interleave(i1) << 1) | interleave(i0)
+fn clip_segment_to_box(x0: f64, y0: f64, x1: f64, y1: f64, b: &Box) -> Option<(f64, f64)> {
+    let dx = x1 - x0;
+    let dy = y1 - y0;
+    let mut t0 = 0.0_f64;
+    let mut t1 = 1.0_f64;
+    for (p, q) in [
+        (-dx, x0 - b.min_x),
+        (dx, b.max_x - x0),
+        (-dy, y0 - b.min_y),
+        (dy, b.max_y - y0),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+            if t0 > t1 {
+                return None;
+            }
+        }
+    }
+    Some((t0, t1))
+}
+
+/// Helper: Whether a box and a polygon share at least one point
+fn box_intersects_polygon(b: &Box, polygon: &[(f64, f64)]) -> bool {
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (x0, y0) = polygon[j];
+        let (x1, y1) = polygon[i];
+        if clip_segment_to_box(x0, y0, x1, y1, b).is_some() {
+            return true;
+        }
+        j = i;
+    }
+    // No edge touches the box: either the box is fully inside the polygon or disjoint
+    point_in_polygon(b.min_x, b.min_y, polygon)
+}
+
+/// Helper: Whether a box lies completely inside a polygon
+fn box_inside_polygon(b: &Box, polygon: &[(f64, f64)]) -> bool {
+    if !point_in_polygon(b.min_x, b.min_y, polygon)
+        || !point_in_polygon(b.max_x, b.min_y, polygon)
+        || !point_in_polygon(b.max_x, b.max_y, polygon)
+        || !point_in_polygon(b.min_x, b.max_y, polygon)
+    {
+        return false;
+    }
+    // All corners inside: the box is contained unless a polygon edge cuts its interior
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (x0, y0) = polygon[j];
+        let (x1, y1) = polygon[i];
+        if let Some((t0, t1)) = clip_segment_to_box(x0, y0, x1, y1, b) {
+            let t = (t0 + t1) / 2.0;
+            let mx = x0 + (x1 - x0) * t;
+            let my = y0 + (y1 - y0) * t;
+            if t1 > t0 && mx > b.min_x && mx < b.max_x && my > b.min_y && my < b.max_y {
+                return false;
+            }
+        }
+        j = i;
+    }
+    true
 }
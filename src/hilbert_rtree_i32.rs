@@ -15,10 +15,21 @@
 //! - `query_point` - Find boxes containing a point
 //! - `query_contain` - Find boxes that contain a rectangle
 //! - `query_contained_within` - Find boxes contained within a rectangle
+//! - `all_intersecting_pairs` - Find every pair of intersecting boxes in the tree
+//! - `count_intersecting` / `any_intersecting` / `any_containing_point` - Count or existence checks without collecting results
+//! - `query_intersecting_ranges` - Find intersecting boxes as contiguous leaf position ranges
+//! - `*_with_boundary` - Intersect, point, contain and contained-within queries with [`Boundary`] semantics
+//!
+//! `sorted_order()`, `permutation()`, `apply_permutation()` and `iter()` expose the Hilbert leaf order.
 
 use std::mem::size_of;
 use std::collections::VecDeque;
 
+use crate::flatbush::{FlatbushArrayType, FlatbushLayout};
+use crate::hilbert::hilbert_xy_to_index;
+use crate::hilbert_rtree::Boundary;
+use crate::join::{self, JoinNodes};
+
 /// Box structure: minX, minY, maxX, maxY (16 bytes total for i32)
 #[derive(Clone, Copy, Debug)]
 pub(crate) struct BoxI32 {
@@ -456,6 +467,96 @@ impl HilbertRTreeI32 {
         self.num_items == 0
     }
 
+    /// Returns the item IDs in the tree's spatial (Hilbert) order.
+    ///
+    /// After `build()` leaves are stored sorted by the Hilbert index of their centers;
+    /// this yields the item ID stored at each leaf position, first to last. Before
+    /// `build()` the order is the insertion order.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(3);
+    /// tree.add(0, 0, 1, 1);  // Box 0
+    /// tree.add(5, 5, 6, 6);  // Box 1
+    /// tree.add(1, 1, 2, 2);  // Box 2
+    /// tree.build();
+    ///
+    /// let mut order: Vec<usize> = tree.sorted_order().collect();
+    /// assert_eq!(order.len(), 3);
+    /// order.sort();
+    /// assert_eq!(order, vec![0, 1, 2]);
+    /// ```
+    pub fn sorted_order(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.num_items).map(move |pos| {
+            if self.level_bounds.is_empty() { pos } else { self.get_index(pos) as usize }
+        })
+    }
+
+    /// Returns the build permutation: `permutation()[i]` is the item ID at leaf position `i`.
+    ///
+    /// Same as collecting `sorted_order()`.
+    pub fn permutation(&self) -> Vec<usize> {
+        self.sorted_order().collect()
+    }
+
+    /// Reorders a per-item slice into the tree's spatial order.
+    ///
+    /// `values[id]` holds an attribute of item `id` (color, flags, ...). The returned
+    /// vector holds the same values in leaf order, so element `i` belongs to the item
+    /// `permutation()[i]`. Scanning attributes in this order has the same locality as
+    /// the tree itself.
+    ///
+    /// # Arguments
+    /// * `values` - One value per item, indexed by item ID
+    ///
+    /// # Panics
+    /// Panics if `values.len()` differs from the number of items.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(3);
+    /// tree.add(0, 0, 1, 1);  // Box 0
+    /// tree.add(5, 5, 6, 6);  // Box 1
+    /// tree.add(1, 1, 2, 2);  // Box 2
+    /// tree.build();
+    ///
+    /// let colors = ["red", "green", "blue"];
+    /// let sorted = tree.apply_permutation(&colors);
+    /// for (color, id) in sorted.iter().zip(tree.sorted_order()) {
+    ///     assert_eq!(*color, colors[id]);
+    /// }
+    /// ```
+    pub fn apply_permutation<T: Clone>(&self, values: &[T]) -> Vec<T> {
+        assert_eq!(values.len(), self.num_items, "expected one value per item");
+        self.sorted_order().map(|id| values[id].clone()).collect()
+    }
+
+    /// Iterates over `(id, (min_x, min_y, max_x, max_y))` for all items in spatial order.
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(3);
+    /// tree.add(0, 0, 1, 1);  // Box 0
+    /// tree.add(5, 5, 6, 6);  // Box 1
+    /// tree.add(1, 1, 2, 2);  // Box 2
+    /// tree.build();
+    ///
+    /// for (id, bbox) in tree.iter() {
+    ///     assert!(bbox.0 <= bbox.2 && bbox.1 <= bbox.3);
+    ///     assert!(id < 3);
+    /// }
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (usize, (i32, i32, i32, i32))> + '_ {
+        (0..self.num_items).map(move |pos| {
+            let bbox = self.get_box(pos);
+            let id = if self.level_bounds.is_empty() { pos } else { self.get_index(pos) as usize };
+            (id, (bbox.min_x, bbox.min_y, bbox.max_x, bbox.max_y))
+        })
+    }
+
     /// Finds all boxes that intersect with a given rectangular region.
     ///
     /// This query returns all boxes whose bounding boxes overlap with the query rectangle,
@@ -580,6 +681,9 @@ impl HilbertRTreeI32 {
     /// convenient than calling `query_intersecting()` with manually extracted bounds, and
     /// avoids redundant lookups and self-intersection checks.
     ///
+    /// After `build()` the item's leaf is found by scanning the leaves, which is O(n)
+    /// per call.
+    ///
     /// # Arguments
     /// * `item_id` - The index of an item already in the tree (0 to `num_items - 1`)
     /// * `results` - Output vector; will be cleared and populated with matching box indices
@@ -610,8 +714,8 @@ impl HilbertRTreeI32 {
             return Err(format!("item_id {} is out of bounds (tree has {} items)", item_id, self.num_items));
         }
         
-        // Get the bounding box of the query item
-        let query_box = self.get_box(item_id);
+        // Get the bounding box of the query item (leaves are in Hilbert order after build)
+        let query_box = self.get_box(self.position_of(item_id).unwrap_or(item_id));
         
         // Use the existing query_intersecting method with the box bounds
         self.query_intersecting(query_box.min_x, query_box.min_y, query_box.max_x, query_box.max_y, results);
@@ -904,8 +1008,471 @@ impl HilbertRTreeI32 {
         }
     }
 
+    /// Finds all boxes that intersect with a rectangle under the given boundary semantics.
+    ///
+    /// Same as `query_intersecting()` with [`Boundary::Closed`]. With [`Boundary::Open`] or
+    /// [`Boundary::HalfOpen`] boxes that only share an edge or corner with the query are
+    /// not reported, e.g. adjacent cells of a grid.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    /// * `boundary` - Whether edges belong to the boxes
+    /// * `results` - Output vector; will be cleared and populated with matching box indices
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(2);
+    /// tree.add(0, 0, 2, 2);  // Cell 0
+    /// tree.add(2, 0, 4, 2);  // Cell 1, shares an edge with cell 0
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_intersecting_with_boundary(0, 0, 2, 2, Boundary::Open, &mut results);
+    /// assert_eq!(results, vec![0]);
+    /// ```
+    pub fn query_intersecting_with_boundary(&self, min_x: i32, min_y: i32, max_x: i32, max_y: i32, boundary: Boundary, results: &mut Vec<usize>) {
+        let intersects = |node_box: &BoxI32| {
+            boundary.overlaps(node_box.min_x, node_box.max_x, min_x, max_x)
+                && boundary.overlaps(node_box.min_y, node_box.max_y, min_y, max_y)
+        };
+        self.collect_leaves(intersects, intersects, results);
+    }
+
+    /// Finds all boxes that contain a point under the given boundary semantics.
+    ///
+    /// Same as `query_point()` with [`Boundary::Closed`]. With [`Boundary::Open`] points on
+    /// an edge are not inside; with [`Boundary::HalfOpen`] points on a min edge are inside
+    /// but points on a max edge are not.
+    ///
+    /// # Arguments
+    /// * `x` - X coordinate of the query point
+    /// * `y` - Y coordinate of the query point
+    /// * `boundary` - Whether edges belong to the boxes
+    /// * `results` - Output vector; will be cleared and populated with indices of all
+    ///   boxes that contain the point
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(2);
+    /// tree.add(0, 0, 2, 2);  // Box 0
+    /// tree.add(2, 0, 4, 2);  // Box 1
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_point_with_boundary(2, 1, Boundary::HalfOpen, &mut results);
+    /// assert_eq!(results, vec![1]);
+    /// ```
+    pub fn query_point_with_boundary(&self, x: i32, y: i32, boundary: Boundary, results: &mut Vec<usize>) {
+        let contains = |node_box: &BoxI32| {
+            boundary.contains_value(node_box.min_x, node_box.max_x, x)
+                && boundary.contains_value(node_box.min_y, node_box.max_y, y)
+        };
+        self.collect_leaves(contains, contains, results);
+    }
+
+    /// Finds all boxes that contain a rectangle under the given boundary semantics.
+    ///
+    /// Same as `query_contain()` with [`Boundary::Closed`] or [`Boundary::HalfOpen`]. With
+    /// [`Boundary::Open`] the query rectangle must not touch the edges of a result box.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    /// * `boundary` - Whether edges belong to the boxes
+    /// * `results` - Output vector; will be cleared and populated with indices of boxes
+    ///   that contain the query rectangle
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(2);
+    /// tree.add(0, 0, 4, 4);  // Box 0
+    /// tree.add(1, 1, 3, 3);  // Box 1
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_contain_with_boundary(1, 1, 2, 2, Boundary::Open, &mut results);
+    /// assert_eq!(results, vec![0]);
+    /// ```
+    pub fn query_contain_with_boundary(&self, min_x: i32, min_y: i32, max_x: i32, max_y: i32, boundary: Boundary, results: &mut Vec<usize>) {
+        let contains = |node_box: &BoxI32| {
+            boundary.contains_range(node_box.min_x, node_box.max_x, min_x, max_x)
+                && boundary.contains_range(node_box.min_y, node_box.max_y, min_y, max_y)
+        };
+        self.collect_leaves(contains, contains, results);
+    }
+
+    /// Finds all boxes contained within a rectangle under the given boundary semantics.
+    ///
+    /// Same as `query_contained_within()` with [`Boundary::Closed`] or [`Boundary::HalfOpen`].
+    /// With [`Boundary::Open`] result boxes must not touch the edges of the query rectangle.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    /// * `boundary` - Whether edges belong to the boxes
+    /// * `results` - Output vector; will be cleared and populated with indices of boxes
+    ///   that are contained within the query rectangle
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(2);
+    /// tree.add(0, 0, 1, 1);  // Box 0, touches the query edge
+    /// tree.add(1, 1, 2, 2);  // Box 1, strictly inside
+    /// tree.build();
+    ///
+    /// let mut results = Vec::new();
+    /// tree.query_contained_within_with_boundary(0, 0, 3, 3, Boundary::Open, &mut results);
+    /// assert_eq!(results, vec![1]);
+    /// ```
+    pub fn query_contained_within_with_boundary(&self, min_x: i32, min_y: i32, max_x: i32, max_y: i32, boundary: Boundary, results: &mut Vec<usize>) {
+        let query = BoxI32 { min_x, min_y, max_x, max_y };
+        self.collect_leaves(
+            |node_box| boxes_intersect(node_box, &query),
+            |node_box| {
+                boundary.contains_range(min_x, max_x, node_box.min_x, node_box.max_x)
+                    && boundary.contains_range(min_y, max_y, node_box.min_y, node_box.max_y)
+            },
+            results,
+        );
+    }
+
+    /// Counts the boxes that intersect with a given rectangular region.
+    ///
+    /// Returns the same number as `query_intersecting()` would produce, without
+    /// collecting indices. When a node box lies completely inside the query rectangle,
+    /// all leaves below it are counted at once without descending into the node.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(3);
+    /// tree.add(0, 0, 2, 2);  // Box 0
+    /// tree.add(1, 1, 3, 3);  // Box 1
+    /// tree.add(4, 4, 5, 5);  // Box 2
+    /// tree.build();
+    ///
+    /// assert_eq!(tree.count_intersecting(1, 1, 2, 2), 2);
+    /// ```
+    pub fn count_intersecting(&self, min_x: i32, min_y: i32, max_x: i32, max_y: i32) -> usize {
+        if self.num_items == 0 || self.level_bounds.is_empty() {
+            return 0;
+        }
+
+        let query = BoxI32 { min_x, min_y, max_x, max_y };
+        let mut count = 0;
+        let mut stack = vec![self.total_nodes - 1];
+        while let Some(pos) = stack.pop() {
+            let node_box = self.get_box(pos);
+            if !boxes_intersect(&node_box, &query) {
+                continue;
+            }
+            if pos < self.num_items {
+                count += 1;
+            } else if box_contains(&query, &node_box) {
+                count += self.leaf_range(pos).len();
+            } else {
+                stack.extend(self.children(pos));
+            }
+        }
+        count
+    }
+
+    /// Checks whether any box intersects with a given rectangular region.
+    ///
+    /// Stops at the first intersecting box found, so this is much cheaper than
+    /// `query_intersecting()` when only a yes/no answer is needed.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(2);
+    /// tree.add(0, 0, 1, 1);  // Box 0
+    /// tree.add(4, 4, 5, 5);  // Box 1
+    /// tree.build();
+    ///
+    /// assert!(tree.any_intersecting(1, 1, 2, 2));
+    /// assert!(!tree.any_intersecting(2, 2, 3, 3));
+    /// ```
+    pub fn any_intersecting(&self, min_x: i32, min_y: i32, max_x: i32, max_y: i32) -> bool {
+        let query = BoxI32 { min_x, min_y, max_x, max_y };
+        self.any_node(|node_box| boxes_intersect(node_box, &query))
+    }
+
+    /// Checks whether any box contains a specific point.
+    ///
+    /// Stops at the first box containing the point (edges and corners included).
+    ///
+    /// # Arguments
+    /// * `x` - X coordinate of the query point
+    /// * `y` - Y coordinate of the query point
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(1);
+    /// tree.add(0, 0, 2, 2);  // Box 0
+    /// tree.build();
+    ///
+    /// assert!(tree.any_containing_point(2, 1));
+    /// assert!(!tree.any_containing_point(3, 1));
+    /// ```
+    pub fn any_containing_point(&self, x: i32, y: i32) -> bool {
+        self.any_node(|node_box| {
+            x >= node_box.min_x && x <= node_box.max_x && y >= node_box.min_y && y <= node_box.max_y
+        })
+    }
+
+    /// Finds all boxes intersecting a rectangle as ranges of leaf positions.
+    ///
+    /// Nodes lying completely inside the query rectangle are emitted as one contiguous
+    /// range of leaf positions (Hilbert order) without visiting their leaves. Ranges are
+    /// sorted, non-overlapping, and adjacent ranges are merged.
+    ///
+    /// Positions are not item IDs; use `leaf_ids()` to map ranges back to item IDs.
+    ///
+    /// # Arguments
+    /// * `min_x` - Left edge of query rectangle
+    /// * `min_y` - Bottom edge of query rectangle
+    /// * `max_x` - Right edge of query rectangle
+    /// * `max_y` - Top edge of query rectangle
+    /// * `ranges` - Output vector; will be cleared and populated with leaf position ranges
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(3);
+    /// tree.add(0, 0, 1, 1);  // Box 0
+    /// tree.add(2, 2, 3, 3);  // Box 1
+    /// tree.add(8, 8, 9, 9);  // Box 2
+    /// tree.build();
+    ///
+    /// let mut ranges = Vec::new();
+    /// tree.query_intersecting_ranges(-1, -1, 10, 10, &mut ranges);
+    /// assert_eq!(ranges, vec![0..3]);
+    /// ```
+    pub fn query_intersecting_ranges(&self, min_x: i32, min_y: i32, max_x: i32, max_y: i32, ranges: &mut Vec<std::ops::Range<usize>>) {
+        ranges.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() {
+            return;
+        }
+
+        // Depth-first in position order, so ranges come out sorted and can be merged as we go
+        let query = BoxI32 { min_x, min_y, max_x, max_y };
+        let mut stack = vec![self.total_nodes - 1];
+        while let Some(pos) = stack.pop() {
+            let node_box = self.get_box(pos);
+            if !boxes_intersect(&node_box, &query) {
+                continue;
+            }
+            if pos >= self.num_items && !box_contains(&query, &node_box) {
+                stack.extend(self.children(pos).rev());
+                continue;
+            }
+            let range = self.leaf_range(pos);
+            match ranges.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => ranges.push(range),
+            }
+        }
+    }
+
+    /// Maps leaf position ranges to item IDs.
+    ///
+    /// Converts the output of `query_intersecting_ranges()` into the item IDs stored at
+    /// those positions, in Hilbert (position) order. Positions at or beyond `len()` are ignored.
+    ///
+    /// # Arguments
+    /// * `ranges` - Leaf position ranges
+    /// * `results` - Output vector; will be cleared and populated with item IDs
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(2);
+    /// tree.add(0, 0, 1, 1);  // Box 0
+    /// tree.add(2, 2, 3, 3);  // Box 1
+    /// tree.build();
+    ///
+    /// let mut ids = Vec::new();
+    /// tree.leaf_ids(&[0..2], &mut ids);
+    /// ids.sort();
+    /// assert_eq!(ids, vec![0, 1]);
+    /// ```
+    pub fn leaf_ids(&self, ranges: &[std::ops::Range<usize>], results: &mut Vec<usize>) {
+        results.clear();
+        for range in ranges {
+            let end = range.end.min(self.num_items);
+            results.extend((range.start.min(end)..end).map(|pos| self.get_index(pos) as usize));
+        }
+    }
+
+    /// Finds every pair of intersecting boxes within the tree (self-join broad phase).
+    ///
+    /// Each overlapping pair is reported exactly once as `(i, j)` with `i < j`; an item
+    /// is never paired with itself. The tree is traversed against itself in a single pass
+    /// instead of running one query per item. Boxes touching at edges or corners count as
+    /// intersecting. The order of the pairs is unspecified.
+    ///
+    /// # Arguments
+    /// * `results` - Output vector; will be cleared and populated with intersecting pairs
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(3);
+    /// tree.add(0, 0, 2, 2);  // Box 0
+    /// tree.add(1, 1, 3, 3);  // Box 1
+    /// tree.add(5, 5, 6, 6);  // Box 2
+    /// tree.build();
+    ///
+    /// let mut pairs = Vec::new();
+    /// tree.all_intersecting_pairs(&mut pairs);
+    /// assert_eq!(pairs, vec![(0, 1)]);
+    /// ```
+    pub fn all_intersecting_pairs(&self, results: &mut Vec<(usize, usize)>) {
+        results.clear();
+        join::traverse(self, self, true, boxes_intersect, |pos_a, pos_b| {
+            results.push(self.ordered_pair(pos_a, pos_b));
+        });
+    }
+
+    /// Parallel version of `all_intersecting_pairs()` (requires the `parallel` feature).
+    ///
+    /// The top levels of the self-traversal are expanded until there are enough
+    /// independent node pairs to keep all threads busy, and each pair is then traversed
+    /// on the rayon thread pool. Produces the same set of pairs as the sequential version,
+    /// in a different order.
+    ///
+    /// # Arguments
+    /// * `results` - Output vector; will be cleared and populated with intersecting pairs
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(3);
+    /// tree.add(0, 0, 2, 2);  // Box 0
+    /// tree.add(1, 1, 3, 3);  // Box 1
+    /// tree.add(5, 5, 6, 6);  // Box 2
+    /// tree.build();
+    ///
+    /// let mut pairs = Vec::new();
+    /// tree.all_intersecting_pairs_parallel(&mut pairs);
+    /// assert_eq!(pairs, vec![(0, 1)]);
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn all_intersecting_pairs_parallel(&self, results: &mut Vec<(usize, usize)>) {
+        results.clear();
+        join::self_join_parallel(self, boxes_intersect, |a, b| self.ordered_pair(a, b), results);
+    }
+
     // --- Private helpers ---
 
+    /// Item ids of two leaf positions as `(smaller, larger)`
+    #[inline(always)]
+    fn ordered_pair(&self, pos_a: usize, pos_b: usize) -> (usize, usize) {
+        let id_a = self.get_index(pos_a) as usize;
+        let id_b = self.get_index(pos_b) as usize;
+        (id_a.min(id_b), id_a.max(id_b))
+    }
+
+    /// Collect the IDs of leaves accepted by `accept`, descending only into parent nodes
+    /// accepted by `descend`
+    fn collect_leaves<D, A>(&self, descend: D, accept: A, results: &mut Vec<usize>)
+    where
+        D: Fn(&BoxI32) -> bool,
+        A: Fn(&BoxI32) -> bool,
+    {
+        results.clear();
+        if self.num_items == 0 || self.level_bounds.is_empty() {
+            return;
+        }
+
+        let mut stack = vec![self.total_nodes - 1];
+        while let Some(pos) = stack.pop() {
+            let node_box = self.get_box(pos);
+            if pos < self.num_items {
+                if accept(&node_box) {
+                    results.push(self.get_index(pos) as usize);
+                }
+            } else if descend(&node_box) {
+                stack.extend(self.children(pos));
+            }
+        }
+    }
+
+    /// Whether any leaf is accepted by `matches`, descending only into accepted nodes
+    fn any_node<M: Fn(&BoxI32) -> bool>(&self, matches: M) -> bool {
+        if self.num_items == 0 || self.level_bounds.is_empty() {
+            return false;
+        }
+
+        let mut stack = vec![self.total_nodes - 1];
+        while let Some(pos) = stack.pop() {
+            if !matches(&self.get_box(pos)) {
+                continue;
+            }
+            if pos < self.num_items {
+                return true;
+            }
+            stack.extend(self.children(pos));
+        }
+        false
+    }
+
+    /// Contiguous range of leaf positions below the node at `pos` (the leaf itself for leaves)
+    #[inline]
+    fn leaf_range(&self, pos: usize) -> std::ops::Range<usize> {
+        let mut first = pos;
+        let mut last = pos;
+        while first >= self.num_items {
+            first = self.children(first).start;
+            last = self.children(last).end - 1;
+        }
+        first..last + 1
+    }
+
+    /// Leaf position of an item (identity before `build()`, a scan of the leaf indices after)
+    fn position_of(&self, item_id: usize) -> Option<usize> {
+        if item_id >= self.num_items {
+            return None;
+        }
+        if self.level_bounds.is_empty() {
+            return Some(item_id);
+        }
+        (0..self.num_items).find(|&pos| self.get_index(pos) as usize == item_id)
+    }
+
+    /// Positions of the children of the parent node at `pos`
+    #[inline(always)]
+    fn children(&self, pos: usize) -> std::ops::Range<usize> {
+        let first_child = (self.get_index(pos) >> 2) as usize;
+        first_child..(first_child + self.node_size).min(self.upper_bound(first_child))
+    }
+
+
     /// Get box at position using read_unaligned
     #[inline]
     pub(crate) fn get_box(&self, pos: usize) -> BoxI32 {
@@ -1084,6 +1651,116 @@ impl HilbertRTreeI32 {
             allocated_capacity: data_len,
         })
     }
+
+    /// Exports the built tree in the Flatbush v3 binary layout with `Int32Array` coordinates.
+    ///
+    /// The bytes can be loaded in JavaScript with `Flatbush.from(buffer)` and queried there
+    /// without rebuilding; item ids are the same on both sides.
+    ///
+    /// # Panics
+    /// Panics if the tree has not been built or is empty (Flatbush cannot represent an empty index).
+    ///
+    /// # Example
+    /// ```
+    /// use aabb::prelude::*;
+    /// let mut tree = AABBI32::with_capacity(2);
+    /// tree.add(0, 0, 1, 1);
+    /// tree.add(2, 2, 3, 3);
+    /// tree.build();
+    ///
+    /// let bytes = tree.to_flatbush_bytes();
+    /// assert_eq!(&bytes[..2], &[0xfb, 0x35]);  // magic, version 3 + Int32Array
+    /// let copy = AABBI32::from_flatbush_bytes(&bytes).unwrap();
+    /// assert_eq!(copy.iter().collect::<Vec<_>>(), tree.iter().collect::<Vec<_>>());
+    /// ```
+    pub fn to_flatbush_bytes(&self) -> Vec<u8> {
+        assert!(!self.level_bounds.is_empty() && self.num_items > 0, "tree must be built with at least one item");
+        let layout = FlatbushLayout::new(FlatbushArrayType::Int32, self.node_size, self.num_items);
+        layout.encode(
+            (0..self.total_nodes).map(|pos| {
+                let node_box = self.get_box(pos);
+                [node_box.min_x, node_box.min_y, node_box.max_x, node_box.max_y].map(f64::from)
+            }),
+            (0..self.total_nodes).map(|pos| self.get_index(pos)),
+        )
+    }
+
+    /// Imports a Flatbush v3 buffer as a built tree.
+    ///
+    /// Accepts integer coordinate types that fit in i32 (`Int8`, `Uint8`, `Uint8Clamped`,
+    /// `Int16`, `Uint16`, `Int32`). Use `HilbertRTree::from_flatbush_bytes()` for
+    /// `Uint32` or floating-point buffers.
+    ///
+    /// # Arguments
+    /// * `bytes` - The complete Flatbush buffer
+    ///
+    /// # Errors
+    /// Returns an `InvalidData` error if the magic number, version or array type is wrong or
+    /// not representable as i32, the length does not match the header, or a node index points
+    /// outside the tree.
+    pub fn from_flatbush_bytes(bytes: &[u8]) -> std::io::Result<Self> {
+        let layout = FlatbushLayout::parse(bytes)?;
+        if !layout.array_type.fits_i32() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Flatbush array type {:?} does not fit in i32 coordinates", layout.array_type),
+            ));
+        }
+        let total_nodes = layout.num_nodes;
+
+        let mut data = vec![0_u8; HEADER_SIZE + total_nodes * (size_of::<BoxI32>() + size_of::<u32>())];
+        data[0] = 0xfb; // magic
+        data[1] = 0x01; // version 1 (same version for both variants)
+        data[2..8].copy_from_slice(&bytes[2..8]); // node_size and num_items
+        let indices_start = HEADER_SIZE + total_nodes * size_of::<BoxI32>();
+        // Integer array types up to Int32 convert to f64 and back exactly
+        #[expect(clippy::cast_possible_truncation, reason = "integer array types up to Int32 are exact")]
+        let read_box = |pos: usize| {
+            let [min_x, min_y, max_x, max_y] = layout.read_box(bytes, pos).map(|value| value as i32);
+            BoxI32::new(min_x, min_y, max_x, max_y)
+        };
+        for pos in 0..total_nodes {
+            let box_ptr = &mut data[HEADER_SIZE + pos * size_of::<BoxI32>()] as *mut u8 as *mut BoxI32;
+            let idx_ptr = &mut data[indices_start + pos * size_of::<u32>()] as *mut u8 as *mut u32;
+            unsafe {
+                std::ptr::write_unaligned(box_ptr, read_box(pos));
+                std::ptr::write_unaligned(idx_ptr, layout.read_index(bytes, pos));
+            }
+        }
+
+        let bounds = read_box(total_nodes - 1);
+        let allocated_capacity = data.capacity();
+        Ok(Self {
+            data,
+            level_bounds: layout.level_bounds,
+            node_size: layout.node_size,
+            num_items: layout.num_items,
+            position: 0,
+            bounds,
+            total_nodes,
+            allocated_capacity,
+        })
+    }
+}
+
+impl JoinNodes for HilbertRTreeI32 {
+    type NodeBox = BoxI32;
+
+    fn num_leaves(&self) -> usize {
+        self.num_items
+    }
+
+    fn root(&self) -> Option<usize> {
+        (self.num_items > 0 && !self.level_bounds.is_empty()).then(|| self.total_nodes - 1)
+    }
+
+    fn node_box(&self, pos: usize) -> BoxI32 {
+        self.get_box(pos)
+    }
+
+    fn node_children(&self, pos: usize) -> std::ops::Range<usize> {
+        self.children(pos)
+    }
 }
 
 impl Default for HilbertRTreeI32 {
@@ -1092,61 +1769,14 @@ impl Default for HilbertRTreeI32 {
     }
 }
 
-/// Hilbert curve index computation
-/// From <https://github.com/rawrunprotected/hilbert_curves> (public domain)
-fn interleave(mut x: u32) -> u32 {
-    x = (x | (x << 8)) & 0x00FF00FF;
-    x = (x | (x << 4)) & 0x0F0F0F0F;
-    x = (x | (x << 2)) & 0x33333333;
-    x = (x | (x << 1)) & 0x55555555;
-    x
+/// Helper: Whether two boxes intersect (touching edges or corners count)
+#[inline(always)]
+fn boxes_intersect(a: &BoxI32, b: &BoxI32) -> bool {
+    a.max_x >= b.min_x && a.max_y >= b.min_y && a.min_x <= b.max_x && a.min_y <= b.max_y
 }
 
-#[expect(non_snake_case)]
-fn hilbert_xy_to_index(x: u32, y: u32) -> u32 {
-    // Initial prefix scan round, prime with x and y
-    let mut a = x ^ y;
-    let mut b = 0xFFFF ^ a;
-    let mut c = 0xFFFF ^ (x | y);
-    let mut d = x & (y ^ 0xFFFF);
-    let mut A = a | (b >> 1);
-    let mut B = (a >> 1) ^ a;
-    let mut C = ((c >> 1) ^ (b & (d >> 1))) ^ c;
-    let mut D = ((a & (c >> 1)) ^ (d >> 1)) ^ d;
-
-    a = A;
-    b = B;
-    c = C;
-    d = D;
-    A = (a & (a >> 2)) ^ (b & (b >> 2));
-    B = (a & (b >> 2)) ^ (b & ((a ^ b) >> 2));
-    C ^= (a & (c >> 2)) ^ (b & (d >> 2));
-    D ^= (b & (c >> 2)) ^ ((a ^ b) & (d >> 2));
-
-    a = A;
-    b = B;
-    c = C;
-    d = D;
-    A = (a & (a >> 4)) ^ (b & (b >> 4));
-    B = (a & (b >> 4)) ^ (b & ((a ^ b) >> 4));
-    C ^= (a & (c >> 4)) ^ (b & (d >> 4));
-    D ^= (b & (c >> 4)) ^ ((a ^ b) & (d >> 4));
-
-    // Final round and projection
-    a = A;
-    b = B;
-    c = C;
-    d = D;
-    C ^= (a & (c >> 8)) ^ (b & (d >> 8));
-    D ^= (b & (c >> 8)) ^ ((a ^ b) & (d >> 8));
-
-    // Undo transformation prefix scan
-    a = C ^ (C >> 1);
-    b = D ^ (D >> 1);
-
-    // Recover index bits
-    let i0 = x ^ y;
-    let i1 = b | (0xFFFF ^ (i0 | a));
-
-    (interleave(i1) << 1) | interleave(i0)
+/// Helper: Whether box `outer` completely contains box `inner`
+#[inline(always)]
+fn box_contains(outer: &BoxI32, inner: &BoxI32) -> bool {
+    outer.min_x <= inner.min_x && outer.min_y <= inner.min_y && outer.max_x >= inner.max_x && outer.max_y >= inner.max_y
 }
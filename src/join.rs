@@ -0,0 +1,165 @@
+//! Synchronized traversal of packed Hilbert R-trees, shared by the f64 and i32 trees
+//!
+//! Both trees store nodes the same way (leaves at `[0, num_items)`, parents pointing to
+//! their first child), so the join only needs node boxes and child ranges. Pairs of nodes
+//! are descended together and pruned with a caller-supplied overlap test.
+
+use std::ops::Range;
+
+/// Node access needed by the join traversal
+pub(crate) trait JoinNodes {
+    /// Bounding box type of a node
+    type NodeBox;
+
+    /// Number of leaves; leaves occupy positions `[0, num_leaves)`
+    fn num_leaves(&self) -> usize;
+
+    /// Position of the root node, `None` if the tree is empty or not built
+    fn root(&self) -> Option<usize>;
+
+    /// Bounding box of the node at `pos`
+    fn node_box(&self, pos: usize) -> Self::NodeBox;
+
+    /// Positions of the children of the parent node at `pos`
+    fn node_children(&self, pos: usize) -> Range<usize>;
+}
+
+/// Synchronized traversal of two trees: calls `emit` with the leaf positions of every
+/// pair of leaves for which `overlaps` holds. `overlaps` must be monotone (if it holds
+/// for two leaves it holds for their ancestors) so node pairs can be pruned with it.
+///
+/// With `self_join` set, `a` and `b` must be the same tree; each unordered pair of
+/// distinct leaves is then emitted once and leaves are never paired with themselves.
+pub(crate) fn traverse<T, P, E>(a: &T, b: &T, self_join: bool, overlaps: P, mut emit: E)
+where
+    T: JoinNodes,
+    P: Fn(&T::NodeBox, &T::NodeBox) -> bool,
+    E: FnMut(usize, usize),
+{
+    let (Some(root_a), Some(root_b)) = (a.root(), b.root()) else {
+        return;
+    };
+    if !overlaps(&a.node_box(root_a), &b.node_box(root_b)) {
+        return;
+    }
+
+    descend(a, b, self_join, &overlaps, vec![(root_a, root_b)], &mut emit);
+}
+
+/// Parallel self-join (requires the `parallel` feature): the top levels are expanded until
+/// there are enough independent node pairs to keep all threads busy, and each pair is then
+/// traversed on the rayon thread pool. `pair` turns two leaf positions into a result.
+#[cfg(feature = "parallel")]
+pub(crate) fn self_join_parallel<T, P, F, R>(tree: &T, overlaps: P, pair: F, results: &mut Vec<R>)
+where
+    T: JoinNodes + Sync,
+    P: Fn(&T::NodeBox, &T::NodeBox) -> bool + Sync,
+    F: Fn(usize, usize) -> R + Sync,
+    R: Send,
+{
+    use rayon::prelude::*;
+
+    let Some(root) = tree.root() else {
+        return;
+    };
+
+    // Expand level by level until there is enough independent work for every thread
+    let target = rayon::current_num_threads() * 16;
+    let mut frontier = vec![(root, root)];
+    while frontier.len() < target && frontier.iter().any(|&(pos, _)| pos >= tree.num_leaves()) {
+        let mut next = Vec::with_capacity(frontier.len() * 4);
+        for &(pos_a, pos_b) in &frontier {
+            expand(tree, tree, true, &overlaps, pos_a, pos_b, &mut next, &mut |a, b| results.push(pair(a, b)));
+        }
+        frontier = next;
+    }
+
+    results.par_extend(frontier.par_iter().flat_map_iter(|&start| {
+        let mut local = Vec::new();
+        descend(tree, tree, true, &overlaps, vec![start], &mut |a, b| local.push(pair(a, b)));
+        local
+    }));
+}
+
+/// Run the synchronized traversal to completion starting from the node pairs in `stack`
+fn descend<T, P, E>(a: &T, b: &T, self_join: bool, overlaps: &P, mut stack: Vec<(usize, usize)>, emit: &mut E)
+where
+    T: JoinNodes,
+    P: Fn(&T::NodeBox, &T::NodeBox) -> bool,
+    E: FnMut(usize, usize),
+{
+    while let Some((pos_a, pos_b)) = stack.pop() {
+        expand(a, b, self_join, overlaps, pos_a, pos_b, &mut stack, emit);
+    }
+}
+
+/// Process one node pair: emit it if both are leaves, otherwise push overlapping child pairs
+#[expect(clippy::too_many_arguments, reason = "traversal state shared by all join variants")]
+#[inline]
+fn expand<T, P, E>(
+    a: &T,
+    b: &T,
+    self_join: bool,
+    overlaps: &P,
+    pos_a: usize,
+    pos_b: usize,
+    stack: &mut Vec<(usize, usize)>,
+    emit: &mut E,
+) where
+    T: JoinNodes,
+    P: Fn(&T::NodeBox, &T::NodeBox) -> bool,
+    E: FnMut(usize, usize),
+{
+    let leaf_a = pos_a < a.num_leaves();
+    let leaf_b = pos_b < b.num_leaves();
+
+    if self_join && pos_a == pos_b {
+        // Same node on both sides: pair each child with itself and with later siblings only
+        if leaf_a {
+            return;
+        }
+        let children = a.node_children(pos_a);
+        let children_are_leaves = children.start < a.num_leaves();
+        for child_a in children.clone() {
+            let child_box_a = a.node_box(child_a);
+            if !children_are_leaves {
+                stack.push((child_a, child_a));
+            }
+            for child_b in child_a + 1..children.end {
+                if overlaps(&child_box_a, &a.node_box(child_b)) {
+                    stack.push((child_a, child_b));
+                }
+            }
+        }
+    } else if leaf_a && leaf_b {
+        emit(pos_a, pos_b);
+    } else if leaf_b {
+        let box_b = b.node_box(pos_b);
+        for child_a in a.node_children(pos_a) {
+            if overlaps(&a.node_box(child_a), &box_b) {
+                stack.push((child_a, pos_b));
+            }
+        }
+    } else if leaf_a {
+        let box_a = a.node_box(pos_a);
+        for child_b in b.node_children(pos_b) {
+            if overlaps(&box_a, &b.node_box(child_b)) {
+                stack.push((pos_a, child_b));
+            }
+        }
+    } else {
+        // Descend both sides, filtering children of `a` against the parent box of `b` first
+        let box_b = b.node_box(pos_b);
+        for child_a in a.node_children(pos_a) {
+            let child_box_a = a.node_box(child_a);
+            if !overlaps(&child_box_a, &box_b) {
+                continue;
+            }
+            for child_b in b.node_children(pos_b) {
+                if overlaps(&child_box_a, &b.node_box(child_b)) {
+                    stack.push((child_a, child_b));
+                }
+            }
+        }
+    }
+}
@@ -91,10 +91,33 @@
 //! - [`query_point`] `(f64, i32)` - Find boxes that contain a point
 //! - [`query_contain`] `(f64, i32)` - Find boxes that contain a rectangle  
 //! - [`query_contained_within`] `(f64, i32)` - Find boxes contained within a rectangle
+//! - [`query_polygon`] `(f64)` - Find boxes intersecting or inside a simple polygon
+//! - [`query_oriented_box`] `(f64)` - Find boxes intersecting a rotated rectangle
+//! - [`query_intersecting_with_boundary`] `(f64, i32)` - Intersect, point, contain and contained-within queries (`*_with_boundary`) with [`Boundary`] semantics for touching edges
+//! - [`set_period`] `(f64)` - Wrap-around (toroidal) worlds: `query_intersecting_wrapped`, `query_circle_wrapped` and `query_nearest_k_wrapped` work across the seam
+//!
+//! ### Count and Existence Queries
+//! - [`count_intersecting`] `(f64, i32)` - Count boxes intersecting a rectangle without collecting them
+//! - [`count_in_circle`] `(f64)` - Count boxes intersecting a circular region
+//! - [`any_intersecting`] `(f64, i32)` - Check whether any box intersects a rectangle (stops at the first hit)
+//! - [`any_in_circle`] `(f64)` - Check whether any box intersects a circular region
+//! - [`any_containing_point`] `(f64, i32)` - Check whether any box contains a point
+//! - [`query_intersecting_ranges`] `(f64, i32)` - Find intersecting boxes as contiguous leaf position ranges (map to IDs with [`leaf_ids`])
+//! - [`aggregate_in_rect`] `(f64)` - Count, sum, min and max of item weights in a rectangle (items added with [`add_weighted`])
 //!
 //! ### Distance-Based Queries
 //! - [`query_nearest_k`] `(f64)` - Find K nearest boxes to a point (use k=1 for single nearest)
+//! - [`query_nearest_k_to_box`] `(f64)` - Find K nearest boxes to a rectangle (zero distance when overlapping)
+//! - [`query_nearest_k_to_segment`] `(f64)` - Find K nearest boxes to a line segment
+//! - [`query_farthest_k`] `(f64)` - Find K farthest boxes from a point
+//! - [`query_reverse_nearest_k`] `(f64)` - Find items that have a point among their K nearest neighbors
+//! - [`query_nearest_k_id`] / [`query_circle_id`] `(f64)` - Nearest and radius queries around an already-indexed item
 //! - [`query_circle`] `(f64)` - Find boxes intersecting a circular region
+//! - [`query_nearest_k_with_metric`] / [`query_circle_with_metric`] `(f64)` - Nearest and radius queries with a [`Metric`] ([`Euclidean`], [`Manhattan`], [`Chebyshev`]); also `*_points_with_metric`
+//!
+//! ### Geographic Queries
+//! - [`query_intersecting_geo`] `(f64)` - Longitude/latitude rectangle query that wraps across the antimeridian when `min_lon > max_lon`
+//! - [`query_nearest_k_geo`] / [`query_circle_geo`] `(f64)` - Nearest and radius queries by great-circle distance in kilometers (see [`geo`])
 //!
 //! ### Point-Specific Optimized Queries
 //! - [`query_nearest_k_points`] `(f64)` - Find K nearest points (stored as (x, x, y, y)), sorted by distance
@@ -106,6 +129,21 @@
 //! ### Directional Queries  
 //! - [`query_in_direction`] `(f64)` - Find boxes intersecting a rectangle's movement path
 //! - [`query_in_direction_k`] `(f64)` - Find K nearest boxes intersecting a rectangle's movement path
+//! - [`query_swept_k`] `(f64)` - Find K first boxes hit by a moving rectangle, with time of impact and contact normal
+//!
+//! ### Batch Queries
+//! - [`query_intersecting_batch`] `(f64)` - Run many rectangle queries at once (also [`query_intersecting_batch_flat`])
+//! - [`query_nearest_k_batch`] `(f64)` - Run many K-nearest queries at once
+//!
+//! Batch queries run in parallel when the `parallel` feature is enabled.
+//!
+//! ### Joins
+//! - [`join_intersecting`] `(f64)` - Find all intersecting pairs between two trees
+//! - [`all_intersecting_pairs`] `(f64, i32)` - Find every intersecting pair within one tree (parallel variant with the `parallel` feature)
+//! - [`join_within_distance`] `(f64)` - Find all pairs between two trees within a distance
+//! - [`pairs_within_distance`] `(f64)` - Find all pairs within one tree within a distance
+//! - [`knn_graph`] `(f64)` - Build the K-nearest-neighbor graph of all items
+//! - [`closest_pairs`] `(f64)` - Find the K closest pairs between two trees
 //!
 //! [`query_intersecting`]: HilbertRTree::query_intersecting
 //! [`query_intersecting_id`]: HilbertRTree::query_intersecting_id
@@ -113,12 +151,46 @@
 //! [`query_point`]: HilbertRTree::query_point
 //! [`query_contain`]: HilbertRTree::query_contain
 //! [`query_contained_within`]: HilbertRTree::query_contained_within
+//! [`query_polygon`]: HilbertRTree::query_polygon
+//! [`query_oriented_box`]: HilbertRTree::query_oriented_box
+//! [`query_intersecting_with_boundary`]: HilbertRTree::query_intersecting_with_boundary
+//! [`set_period`]: HilbertRTree::set_period
+//! [`query_intersecting_geo`]: HilbertRTree::query_intersecting_geo
+//! [`query_nearest_k_geo`]: HilbertRTree::query_nearest_k_geo
+//! [`query_circle_geo`]: HilbertRTree::query_circle_geo
+//! [`count_intersecting`]: HilbertRTree::count_intersecting
+//! [`count_in_circle`]: HilbertRTree::count_in_circle
+//! [`any_intersecting`]: HilbertRTree::any_intersecting
+//! [`any_in_circle`]: HilbertRTree::any_in_circle
+//! [`any_containing_point`]: HilbertRTree::any_containing_point
+//! [`query_intersecting_ranges`]: HilbertRTree::query_intersecting_ranges
+//! [`leaf_ids`]: HilbertRTree::leaf_ids
+//! [`aggregate_in_rect`]: HilbertRTree::aggregate_in_rect
+//! [`add_weighted`]: HilbertRTree::add_weighted
 //! [`query_nearest_k`]: HilbertRTree::query_nearest_k
+//! [`query_nearest_k_to_box`]: HilbertRTree::query_nearest_k_to_box
+//! [`query_nearest_k_to_segment`]: HilbertRTree::query_nearest_k_to_segment
+//! [`query_farthest_k`]: HilbertRTree::query_farthest_k
+//! [`query_reverse_nearest_k`]: HilbertRTree::query_reverse_nearest_k
+//! [`query_nearest_k_id`]: HilbertRTree::query_nearest_k_id
+//! [`query_circle_id`]: HilbertRTree::query_circle_id
 //! [`query_circle`]: HilbertRTree::query_circle
+//! [`query_nearest_k_with_metric`]: HilbertRTree::query_nearest_k_with_metric
+//! [`query_circle_with_metric`]: HilbertRTree::query_circle_with_metric
 //! [`query_nearest_k_points`]: HilbertRTree::query_nearest_k_points
 //! [`query_circle_points`]: HilbertRTree::query_circle_points
 //! [`query_in_direction`]: HilbertRTree::query_in_direction
 //! [`query_in_direction_k`]: HilbertRTree::query_in_direction_k
+//! [`query_swept_k`]: HilbertRTree::query_swept_k
+//! [`query_intersecting_batch`]: HilbertRTree::query_intersecting_batch
+//! [`query_intersecting_batch_flat`]: HilbertRTree::query_intersecting_batch_flat
+//! [`query_nearest_k_batch`]: HilbertRTree::query_nearest_k_batch
+//! [`join_intersecting`]: HilbertRTree::join_intersecting
+//! [`all_intersecting_pairs`]: HilbertRTree::all_intersecting_pairs
+//! [`join_within_distance`]: HilbertRTree::join_within_distance
+//! [`pairs_within_distance`]: HilbertRTree::pairs_within_distance
+//! [`knn_graph`]: HilbertRTree::knn_graph
+//! [`closest_pairs`]: HilbertRTree::closest_pairs
 //!
 //! ## How It Works
 //!
@@ -129,6 +201,12 @@
 //! The Hilbert curve is a space-filling curve that maps 2D coordinates to a 1D sequence
 //! while preserving spatial locality - points that are close in 2D space tend to be 
 //! close in the 1D Hilbert ordering.
+//!
+//! The curve transforms themselves are public in the [`hilbert`] module, so other data
+//! (attribute arrays, points awaiting insertion) can be sorted in the same order.
+//!
+//! The flat buffer follows the Flatbush node layout; [`flatbush`] documents the binary format
+//! and `to_flatbush_bytes()` / `from_flatbush_bytes()` exchange indexes with Flatbush in JavaScript.
 
 /// Core Hilbert R-tree spatial index data structure (flat sorted version)
 #[doc(hidden)]
@@ -137,6 +215,14 @@ pub mod hilbert_rtree_leg;
 pub mod hilbert_rtree;
 /// Hierarchical Hilbert R-tree spatial index for i32 coordinates (memory-efficient)
 pub mod hilbert_rtree_i32;
+/// Hilbert curve transforms (the ordering used by tree construction)
+pub mod hilbert;
+/// Great-circle distance helpers for geographic (lon/lat) trees
+pub mod geo;
+/// Flatbush-compatible binary import/export
+pub mod flatbush;
+/// Synchronized two-tree traversal shared by the f64 and i32 trees
+mod join;
 /// Integration tests for the library
 #[doc(hidden)]
 pub mod integration_test;
@@ -160,8 +246,11 @@ pub mod prelude;
 
 #[doc(hidden)]
 pub use hilbert_rtree_leg::HilbertRTreeLeg;
-pub use hilbert_rtree::HilbertRTree;
+pub use hilbert_rtree::{Aggregate, Boundary, Chebyshev, Euclidean, HilbertRTree, HilbertRTreeView, Manhattan, Metric, PolygonMode};
 pub use hilbert_rtree_i32::HilbertRTreeI32;
+pub use flatbush::FlatbushArrayType;
+#[cfg(feature = "mmap")]
+pub use hilbert_rtree::{HilbertRTreeMmap, MmapBuffer};
 
 pub use prelude::{AABB, AABBI32};
 
@@ -10,7 +10,15 @@
 #[doc(hidden)]
 pub use crate::HilbertRTreeLeg;
 pub use crate::HilbertRTree;
+pub use crate::HilbertRTreeView;
+#[cfg(feature = "mmap")]
+pub use crate::HilbertRTreeMmap;
 pub use crate::HilbertRTreeI32;
+pub use crate::PolygonMode;
+pub use crate::Aggregate;
+pub use crate::Boundary;
+pub use crate::{Chebyshev, Euclidean, Manhattan, Metric};
+pub use crate::FlatbushArrayType;
 
 /// Convenient alias for `HilbertRTree` - floating-point coordinate spatial index
 /// 